@@ -0,0 +1,75 @@
+use nalgebra::{UnitQuaternion, Vector3};
+
+/// Mahony-Filter: führt die integrierte Drehrate über einen PI-Regler auf die
+/// Richtungen von Schwerkraft und Magnetfeld zurück.
+/// Die Lage beschreibt die Drehung vom Sensor- ins Erdsystem (x Nord, y West, z oben).
+#[derive(Debug, Clone)]
+pub struct Mahony {
+    orientation: UnitQuaternion<f32>,
+    /// Gyro-Bias, den der I-Anteil gelernt hat, in rad/s
+    integral: Vector3<f32>,
+    kp: f32,
+    ki: f32,
+}
+
+impl Mahony {
+    pub fn new(kp: f32, ki: f32) -> Self {
+        Mahony {
+            orientation: UnitQuaternion::identity(),
+            integral: Vector3::zeros(),
+            kp,
+            ki,
+        }
+    }
+
+    pub fn orientation(&self) -> UnitQuaternion<f32> {
+        self.orientation
+    }
+
+    /// Startet aus der Lage, die allein Beschleunigung und Magnetfeld vorgeben,
+    /// damit der Filter nicht erst von der Einheitslage aus einschwingen muss
+    pub fn initialize(&mut self, accel: Vector3<f32>, mag: Vector3<f32>) {
+        let Some(up) = accel.try_normalize(f32::EPSILON) else {
+            return;
+        };
+        let Some(west) = up.cross(&mag).try_normalize(f32::EPSILON) else {
+            return;
+        };
+        let north = west.cross(&up);
+        // Zeilen = Erdachsen im Sensorsystem, also Sensor -> Erde
+        let rotation = nalgebra::Matrix3::from_rows(&[
+            north.transpose(),
+            west.transpose(),
+            up.transpose(),
+        ]);
+        self.orientation = UnitQuaternion::from_matrix(&rotation);
+        self.integral = Vector3::zeros();
+    }
+
+    /// Drehrate in rad/s, Beschleunigung und Magnetfeld in beliebigen Einheiten,
+    /// alle im Sensorsystem; `dt` in Sekunden
+    pub fn update(&mut self, gyro: Vector3<f32>, accel: Vector3<f32>, mag: Vector3<f32>, dt: f32) {
+        let mut error = Vector3::zeros();
+        let inverse = self.orientation.inverse();
+
+        if let Some(accel) = accel.try_normalize(f32::EPSILON) {
+            // Erwartete Richtung nach oben im Sensorsystem
+            let up = inverse * Vector3::z();
+            error += accel.cross(&up);
+
+            if let Some(mag) = mag.try_normalize(f32::EPSILON) {
+                // Magnetfeld ins Erdsystem drehen und die Deklination in y verwerfen
+                let h = self.orientation * mag;
+                let reference = Vector3::new((h.x * h.x + h.y * h.y).sqrt(), 0.0, h.z);
+                let expected = inverse * reference;
+                error += mag.cross(&expected);
+            }
+        }
+
+        if self.ki > 0.0 {
+            self.integral += self.ki * error * dt;
+        }
+        let rate = gyro + self.kp * error + self.integral;
+        self.orientation *= UnitQuaternion::from_scaled_axis(rate * dt);
+    }
+}
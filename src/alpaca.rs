@@ -1,11 +1,34 @@
-use ascom_alpaca::api::{AlignmentMode, Device, Telescope};
-use ascom_alpaca::{ ASCOMResult};
+use ascom_alpaca::api::{
+    AlignmentMode, Axis as TelescopeAxis, AxisRate, Device, DriveRate, EquatorialSystem,
+    Focuser, Rotator, Switch, Telescope,
+};
+use ascom_alpaca::{ASCOMError, ASCOMResult};
 use async_trait::async_trait;
+use atomic_struct_core::AtomicMember;
 use  crate::alt_az_driver::alt_az_driver;
-use crate::telescope_position::TelescopePosition;
+use crate::mount_backend::Axis;
+use crate::astronomy::local_sidereal_time;
+use crate::clock::clock;
+use crate::focuser::focuser;
+use crate::site::SiteLocation;
+use crate::switch::{switches, Switch as SwitchOutput};
+use crate::telescope_position::{AltAZPostion, EqPostion, TelescopePosition};
+use crate::tracking::TrackingRate;
 
 use crate::storage;
 
+/// Schaltet die Motortreiber sofort ab
+const ACTION_EMERGENCY_STOP: &str = "EmergencyStop";
+/// Gibt die Motortreiber nach einem Not-Aus wieder frei
+const ACTION_RELEASE_EMERGENCY_STOP: &str = "ReleaseEmergencyStop";
+/// Hebt einen Positionsfehler der Regelung über den Lagesensor auf
+const ACTION_CLEAR_FAULT: &str = "ClearFault";
+/// Feste UniqueIDs, damit Clients die Geräte über Neustarts hinweg wiedererkennen
+const TELESCOPE_UNIQUE_ID: &str = "f9340ead-5b9c-471f-87ec-423363246ec2";
+const ROTATOR_UNIQUE_ID: &str = "bda1409a-9cf9-4df7-ab48-f74005b9ed04";
+const FOCUSER_UNIQUE_ID: &str = "254a05c0-5363-448c-abd6-998aa159ffee";
+const SWITCH_UNIQUE_ID: &str = "0d0a2e75-7c0b-4605-91d0-11d0d5c8548a";
+
 pub(crate) async fn handle_alpaca(storage: &'static storage::Storage,) -> anyhow::Result<()> {
     let mut server = ascom_alpaca::Server {
         // helper macro to populate server information from your own Cargo.toml
@@ -18,7 +41,18 @@ pub(crate) async fn handle_alpaca(storage: &'static storage::Storage,) -> anyhow
     server.listen_addr.set_port(8000);
 
     // Create and register your device(s).
-    server.devices.register(AlpacaTelescope { storage});
+    server.devices.register(AlpacaTelescope {
+        storage,
+        target_right_ascension: AtomicMember::new(None),
+        target_declination: AtomicMember::new(None),
+    });
+    server.devices.register(AlpacaRotator { storage });
+    if focuser().is_some() {
+        server.devices.register(AlpacaFocuser);
+    }
+    if !switches().is_empty() {
+        server.devices.register(AlpacaSwitch);
+    }
 
     // Start the infinite server loop.
     server
@@ -31,6 +65,159 @@ pub(crate) async fn handle_alpaca(storage: &'static storage::Storage,) -> anyhow
 #[derive(Debug)]
 struct AlpacaTelescope {
     storage: &'static storage::Storage,
+    /// in Stunden
+    target_right_ascension: AtomicMember<Option<f64>>,
+    /// in Grad
+    target_declination: AtomicMember<Option<f64>>,
+}
+
+impl AlpacaTelescope {
+    /// Trägt den Standort als `[site.manual]` ein; ob er verwendet wird, entscheidet
+    /// `[site] policy`
+    async fn set_manual_site(&self, site: SiteLocation) -> ASCOMResult<()> {
+        site.validate().map_err(ASCOMError::invalid_value)?;
+        self.storage
+            .set_manual_site(site)
+            .await
+            .map_err(ASCOMError::unspecified)
+    }
+
+    fn check_coordinates(right_ascension: f64, declination: f64) -> ASCOMResult<EqPostion> {
+        if !(0.0..24.0).contains(&right_ascension) {
+            return Err(ASCOMError::invalid_value(format!(
+                "Right ascension {right_ascension} is outside 0..24h"
+            )));
+        }
+        if !(-90.0..=90.0).contains(&declination) {
+            return Err(ASCOMError::invalid_value(format!(
+                "Declination {declination} is outside -90..90°"
+            )));
+        }
+        Ok(EqPostion {
+            ra: right_ascension as f32,
+            dec: declination as f32,
+        })
+    }
+
+    fn check_alt_az(azimuth: f64, altitude: f64) -> ASCOMResult<AltAZPostion> {
+        if !(0.0..360.0).contains(&azimuth) || !(-90.0..=90.0).contains(&altitude) {
+            return Err(ASCOMError::invalid_value(format!(
+                "Azimuth {azimuth} / altitude {altitude} out of range"
+            )));
+        }
+        Ok(AltAZPostion {
+            alt: altitude as f32,
+            az: azimuth as f32,
+        })
+    }
+
+    /// Bei einer Alt/Az-Montierung ist die Azimutachse die primäre Achse
+    fn mount_axis(axis: TelescopeAxis) -> ASCOMResult<Axis> {
+        match axis {
+            TelescopeAxis::Primary => Ok(Axis::Az),
+            TelescopeAxis::Secondary => Ok(Axis::Alt),
+            TelescopeAxis::Tertiary => Err(ASCOMError::invalid_value(
+                "The tertiary axis cannot be moved, use the Rotator device",
+            )),
+        }
+    }
+
+    async fn target(&self) -> ASCOMResult<EqPostion> {
+        match (
+            self.target_right_ascension.get().await,
+            self.target_declination.get().await,
+        ) {
+            (Some(ra), Some(dec)) => Ok(EqPostion {
+                ra: ra as f32,
+                dec: dec as f32,
+            }),
+            _ => Err(ASCOMError::VALUE_NOT_SET),
+        }
+    }
+
+    async fn current_position(&self) -> ASCOMResult<EqPostion> {
+        let site = self.storage.get_position().await;
+        let position = alt_az_driver()
+            .get_current_position()
+            .await
+            .map_err(ASCOMError::unspecified)?;
+        Ok(position.get_eq(&site, clock().now().await))
+    }
+
+    async fn current_alt_az(&self) -> ASCOMResult<AltAZPostion> {
+        let site = self.storage.get_position().await;
+        let position = alt_az_driver()
+            .get_current_position()
+            .await
+            .map_err(ASCOMError::unspecified)?;
+        Ok(position.get_alt_az(&site, clock().now().await))
+    }
+
+    async fn ensure_unparked() -> ASCOMResult<()> {
+        if alt_az_driver().get_parked().await {
+            return Err(ASCOMError::INVALID_WHILE_PARKED);
+        }
+        Ok(())
+    }
+
+    async fn slew(&self, target: EqPostion, wait: bool) -> ASCOMResult<()> {
+        Self::ensure_unparked().await?;
+        if !alt_az_driver().get_tracking().await {
+            return Err(ASCOMError::invalid_operation(
+                "Tracking must be enabled for equatorial slews",
+            ));
+        }
+        println!("Slewing to RA: {}, Dec: {}", target.ra, target.dec);
+        alt_az_driver()
+            .limit_target(TelescopePosition::Eq(target))
+            .await
+            .map_err(ASCOMError::invalid_value)?;
+        self.target_right_ascension.set(Some(target.ra as f64)).await;
+        self.target_declination.set(Some(target.dec as f64)).await;
+        if wait {
+            alt_az_driver()
+                .slew_to(target)
+                .await
+                .map_err(ASCOMError::unspecified)
+        } else {
+            alt_az_driver()
+                .track(target)
+                .await
+                .map_err(ASCOMError::invalid_operation)
+        }
+    }
+
+    async fn slew_alt_az(&self, azimuth: f64, altitude: f64) -> ASCOMResult<()> {
+        Self::ensure_unparked().await?;
+        println!("Slewing to Azimuth: {}, Altitude: {}", azimuth, altitude);
+        if alt_az_driver().get_tracking().await {
+            return Err(ASCOMError::invalid_operation(
+                "SlewToAltAz is not allowed while tracking",
+            ));
+        }
+        let target = Self::check_alt_az(azimuth, altitude)?;
+        alt_az_driver()
+            .limit_target(TelescopePosition::AltAz(target))
+            .await
+            .map_err(ASCOMError::invalid_value)?;
+        alt_az_driver()
+            .slew_to_alt_az(target)
+            .await
+            .map_err(ASCOMError::invalid_operation)
+    }
+
+    async fn sync(&self, position: EqPostion) -> ASCOMResult<()> {
+        Self::ensure_unparked().await?;
+        println!("Syncing to RA: {}, Dec: {}", position.ra, position.dec);
+        self.target_right_ascension
+            .set(Some(position.ra as f64))
+            .await;
+        self.target_declination.set(Some(position.dec as f64)).await;
+        alt_az_driver()
+            .sync_to(position)
+            .await
+            .map_err(ASCOMError::unspecified)
+    }
 }
 
 #[async_trait]
@@ -40,7 +227,7 @@ impl Device for AlpacaTelescope {
     }
 
     fn unique_id(&self) -> &str {
-        "insert GUID here"
+        TELESCOPE_UNIQUE_ID
     }
     async fn description(&self) -> ASCOMResult<String> {
         Ok("OpenPiScope Telescope Device".to_owned())
@@ -55,12 +242,39 @@ impl Device for AlpacaTelescope {
     }
 
     async fn driver_info(&self) -> ASCOMResult<String> {
-        Ok("ascom-alpaca Rust webcam demo".to_owned())
+        Ok("OpenPiScope alt-az stepper mount".to_owned())
     }
 
     async fn driver_version(&self) -> ASCOMResult<String> {
         Ok(env!("CARGO_PKG_VERSION").to_owned())
     }
+
+    async fn supported_actions(&self) -> ASCOMResult<Vec<String>> {
+        Ok(vec![
+            ACTION_EMERGENCY_STOP.to_owned(),
+            ACTION_RELEASE_EMERGENCY_STOP.to_owned(),
+            ACTION_CLEAR_FAULT.to_owned(),
+        ])
+    }
+
+    async fn action(&self, action: String, _parameters: String) -> ASCOMResult<String> {
+        match action.as_str() {
+            ACTION_EMERGENCY_STOP => {
+                println!("Emergency stop");
+                alt_az_driver().emergency_stop().await;
+            }
+            ACTION_RELEASE_EMERGENCY_STOP => {
+                println!("Releasing emergency stop");
+                alt_az_driver().release_emergency_stop().await;
+            }
+            ACTION_CLEAR_FAULT => {
+                println!("Clearing pointing fault");
+                alt_az_driver().clear_fault().await;
+            }
+            _ => return Err(ASCOMError::ACTION_NOT_IMPLEMENTED),
+        }
+        Ok(String::new())
+    }
 }
 
 #[async_trait]
@@ -71,46 +285,190 @@ impl Telescope for AlpacaTelescope {
     }
 
     async fn slewing(&self) -> ASCOMResult<bool> {
-        Ok(false) // Replace with actual slewing status
+        Ok(alt_az_driver().get_slewing().await)
     }
     async fn slew_to_alt_az(&self, azimuth: f64, altitude: f64) -> ASCOMResult<()> {
-        // Implement the logic to slew to the specified azimuth and altitude
-        println!("Slewing to Azimuth: {}, Altitude: {}", azimuth, altitude);
-        let target = TelescopePosition::new_alt_az(altitude as f32, azimuth as f32);
-        alt_az_driver().set_target_position(Some(target)).await;
+        self.slew_alt_az(azimuth, altitude).await?;
+        alt_az_driver()
+            .wait_for_slew()
+            .await
+            .map_err(ASCOMError::unspecified)
+    }
+
+    async fn slew_to_alt_az_async(&self, azimuth: f64, altitude: f64) -> ASCOMResult<()> {
+        self.slew_alt_az(azimuth, altitude).await
+    }
+
+    async fn can_set_tracking(&self) -> ASCOMResult<bool> {
+        Ok(true)
+    }
+
+    async fn tracking(&self) -> ASCOMResult<bool> {
+        Ok(alt_az_driver().get_tracking().await)
+    }
+
+    async fn set_tracking(&self, tracking: bool) -> ASCOMResult<()> {
+        println!("Setting tracking to: {}", tracking);
+        if tracking {
+            Self::ensure_unparked().await?;
+            alt_az_driver()
+                .start_tracking()
+                .await
+                .map_err(ASCOMError::unspecified)?;
+        } else {
+            alt_az_driver().stop_tracking().await;
+        }
+        Ok(())
+    }
+
+    async fn tracking_rate(&self) -> ASCOMResult<DriveRate> {
+        Ok(alt_az_driver().get_tracking_rate().await.into())
+    }
 
+    async fn set_tracking_rate(&self, tracking_rate: DriveRate) -> ASCOMResult<()> {
+        alt_az_driver()
+            .change_tracking_rate(tracking_rate.into())
+            .await;
         Ok(())
     }
 
+    async fn tracking_rates(&self) -> ASCOMResult<Vec<DriveRate>> {
+        Ok(TrackingRate::ALL.into_iter().map(DriveRate::from).collect())
+    }
+
+    async fn can_move_axis(&self, axis: TelescopeAxis) -> ASCOMResult<bool> {
+        Ok(Self::mount_axis(axis).is_ok())
+    }
+
+    async fn axis_rates(&self, axis: TelescopeAxis) -> ASCOMResult<Vec<AxisRate>> {
+        let maximum = alt_az_driver()
+            .max_manual_rate(Self::mount_axis(axis)?)
+            .await
+            .map_err(ASCOMError::unspecified)?;
+        Ok(vec![AxisRate {
+            minimum: 0.0,
+            maximum: maximum as f64,
+        }])
+    }
+
+    async fn move_axis(&self, axis: TelescopeAxis, rate: f64) -> ASCOMResult {
+        let axis = Self::mount_axis(axis)?;
+        Self::ensure_unparked().await?;
+        println!("Moving {axis:?} axis at {rate}°/s");
+        alt_az_driver()
+            .move_axis(axis, rate as f32)
+            .await
+            .map_err(ASCOMError::invalid_value)
+    }
+
     async fn abort_slew(&self) -> ASCOMResult<()> {
-        // Implement the logic to abort the current slew operation
         println!("Aborting slew operation");
+        alt_az_driver().abort().await;
+        Ok(())
+    }
+
+
+    async fn slew_to_coordinates(&self, right_ascension: f64, declination: f64) -> ASCOMResult<()> {
+        let target = Self::check_coordinates(right_ascension, declination)?;
+        self.slew(target, true).await
+    }
+
+    async fn slew_to_coordinates_async(
+        &self,
+        right_ascension: f64,
+        declination: f64,
+    ) -> ASCOMResult<()> {
+        let target = Self::check_coordinates(right_ascension, declination)?;
+        self.slew(target, false).await
+    }
+
+    async fn slew_to_target(&self) -> ASCOMResult<()> {
+        let target = self.target().await?;
+        self.slew(target, true).await
+    }
+
+    async fn slew_to_target_async(&self) -> ASCOMResult<()> {
+        let target = self.target().await?;
+        self.slew(target, false).await
+    }
+
+    async fn sync_to_coordinates(&self, right_ascension: f64, declination: f64) -> ASCOMResult<()> {
+        let position = Self::check_coordinates(right_ascension, declination)?;
+        self.sync(position).await
+    }
+
+    async fn sync_to_alt_az(&self, azimuth: f64, altitude: f64) -> ASCOMResult<()> {
+        Self::ensure_unparked().await?;
+        if alt_az_driver().get_tracking().await {
+            return Err(ASCOMError::invalid_operation(
+                "SyncToAltAz is not allowed while tracking",
+            ));
+        }
+        let position = Self::check_alt_az(azimuth, altitude)?;
+        println!("Syncing to Azimuth: {}, Altitude: {}", azimuth, altitude);
+        alt_az_driver()
+            .sync_to_alt_az(position)
+            .await
+            .map_err(ASCOMError::unspecified)
+    }
+
+    async fn sync_to_target(&self) -> ASCOMResult<()> {
+        let position = self.target().await?;
+        self.sync(position).await
+    }
+
+    async fn target_right_ascension(&self) -> ASCOMResult<f64> {
+        self.target_right_ascension
+            .get()
+            .await
+            .ok_or(ASCOMError::VALUE_NOT_SET)
+    }
+
+    async fn set_target_right_ascension(&self, target_right_ascension: f64) -> ASCOMResult<()> {
+        Self::check_coordinates(target_right_ascension, 0.0)?;
+        self.target_right_ascension
+            .set(Some(target_right_ascension))
+            .await;
         Ok(())
     }
 
+    async fn target_declination(&self) -> ASCOMResult<f64> {
+        self.target_declination
+            .get()
+            .await
+            .ok_or(ASCOMError::VALUE_NOT_SET)
+    }
+
+    async fn set_target_declination(&self, target_declination: f64) -> ASCOMResult<()> {
+        Self::check_coordinates(0.0, target_declination)?;
+        self.target_declination.set(Some(target_declination)).await;
+        Ok(())
+    }
 
     async fn right_ascension(&self) -> ASCOMResult<f64> {
-        // Replace with actual logic to get the right ascension
-        Ok(0.0)
+        Ok(self.current_position().await?.ra as f64)
     }
     async fn declination(&self) -> ASCOMResult<f64> {
-        // Replace with actual logic to get the declination
-        Ok(0.0)
+        Ok(self.current_position().await?.dec as f64)
+    }
+
+    async fn equatorial_system(&self) -> ASCOMResult<EquatorialSystem> {
+        Ok(EquatorialSystem::J2000)
+    }
+
+    async fn sidereal_time(&self) -> ASCOMResult<f64> {
+        let position = self.storage.get_position().await;
+        Ok(local_sidereal_time(clock().now().await, position.longitude) / 15.0)
     }
 
     async fn azimuth(&self) -> ASCOMResult<f64> {
-        let orientation = self.storage.get_orientation().await.unwrap_or_default();
-        let azimuth = (orientation.euler.yaw as f64).to_degrees() + 180.0; // Adjusting to 0-360 range
-        if azimuth >= 360.0 {
-            return Ok(azimuth - 360.0);
-        }
-        Ok(azimuth)
+        let position = self.current_alt_az().await?;
+        Ok((position.az as f64).rem_euclid(360.0))
     }
 
     async fn altitude(&self) -> ASCOMResult<f64> {
-        let orientation = self.storage.get_orientation().await.unwrap_or_default();
-        let altitude = orientation.euler.pitch as f64;
-        Ok(altitude.to_degrees())
+        let position = self.current_alt_az().await?;
+        Ok(position.alt as f64)
     }
 
     async fn site_elevation(&self) -> ASCOMResult<f64> {
@@ -128,7 +486,7 @@ impl Telescope for AlpacaTelescope {
         Ok(position.longitude)
     }
     async fn utc_date(&self) -> ASCOMResult<std::time::SystemTime> {
-        Ok(std::time::SystemTime::now())
+        Ok(clock().now().await.into())
     }
     async fn can_slew_alt_az(&self) -> ASCOMResult<bool> {
         Ok(true) // Replace with actual logic to determine if slewing to Alt/Az is supported
@@ -137,33 +495,428 @@ impl Telescope for AlpacaTelescope {
         Ok(true) // Replace with actual logic to determine if async slewing to Alt/Az is supported
     }
 
+    async fn can_slew(&self) -> ASCOMResult<bool> {
+        Ok(true)
+    }
+
+    async fn can_slew_async(&self) -> ASCOMResult<bool> {
+        Ok(true)
+    }
+
+    async fn can_sync(&self) -> ASCOMResult<bool> {
+        Ok(true)
+    }
+
     async fn can_sync_alt_az(&self) -> ASCOMResult<bool> {
         Ok(true) // Replace with actual logic to determine if syncing to Alt/Az is supported
     }
 
     async fn set_site_elevation(&self, site_elevation: f64) -> ASCOMResult<()> {
-        // Implement the logic to set the site elevation
-        println!("Setting site elevation to: {}", site_elevation);
-        Ok(())
+        let mut site = self.storage.get_manual_site().await;
+        site.elevation = site_elevation as f32;
+        self.set_manual_site(site).await
     }
     async fn set_site_latitude(&self, site_latitude: f64) -> ASCOMResult<()> {
-        // Implement the logic to set the site latitude
-        println!("Setting site latitude to: {}", site_latitude);
-        Ok(())
+        let mut site = self.storage.get_manual_site().await;
+        site.latitude = site_latitude;
+        self.set_manual_site(site).await
     }
     async fn set_site_longitude(&self, site_longitude: f64) -> ASCOMResult<()> {
-        // Implement the logic to set the site longitude
-        println!("Setting site longitude to: {}", site_longitude);
-        Ok(())
+        let mut site = self.storage.get_manual_site().await;
+        site.longitude = site_longitude;
+        self.set_manual_site(site).await
     }
 
     async fn at_park(&self) -> ASCOMResult<bool> {
-        // Implement the logic to check if the telescope is parked
-        Ok(false) // Replace with actual parked status
+        Ok(alt_az_driver().get_parked().await)
     }
-    
+
     async fn at_home(&self) -> ASCOMResult<bool> {
-        // Implement the logic to check if the telescope is parked
-        Ok(false) // Replace with actual parked status
+        Ok(alt_az_driver().at_home().await)
+    }
+
+    async fn can_park(&self) -> ASCOMResult<bool> {
+        Ok(true)
+    }
+
+    async fn can_unpark(&self) -> ASCOMResult<bool> {
+        Ok(true)
+    }
+
+    async fn can_set_park(&self) -> ASCOMResult<bool> {
+        Ok(true)
+    }
+
+    async fn can_find_home(&self) -> ASCOMResult<bool> {
+        Ok(true)
+    }
+
+    async fn park(&self) -> ASCOMResult<()> {
+        println!("Parking");
+        alt_az_driver()
+            .park()
+            .await
+            .map_err(ASCOMError::unspecified)
+    }
+
+    async fn unpark(&self) -> ASCOMResult<()> {
+        println!("Unparking");
+        alt_az_driver()
+            .unpark()
+            .await
+            .map_err(ASCOMError::unspecified)
+    }
+
+    async fn set_park(&self) -> ASCOMResult<()> {
+        alt_az_driver()
+            .set_park_here()
+            .await
+            .map_err(ASCOMError::unspecified)
+    }
+
+    async fn find_home(&self) -> ASCOMResult<()> {
+        Self::ensure_unparked().await?;
+        println!("Finding home");
+        alt_az_driver()
+            .find_home()
+            .await
+            .map_err(ASCOMError::unspecified)
+    }
+}
+
+/// Bildfeldrotator: Position ist der Positionswinkel des Bildfelds, also die Stellung des
+/// Derotators plus der parallaktische Winkel. Ohne `[mount.derotator]` meldet er nur den
+/// parallaktischen Winkel.
+#[derive(Debug)]
+struct AlpacaRotator {
+    storage: &'static storage::Storage,
+}
+
+impl AlpacaRotator {
+    fn check_angle(position: f64) -> ASCOMResult<f32> {
+        if !position.is_finite() {
+            return Err(ASCOMError::invalid_value(format!(
+                "Rotator position {position} is not a number"
+            )));
+        }
+        Ok(position.rem_euclid(360.0) as f32)
+    }
+
+    fn ensure_derotator() -> ASCOMResult<()> {
+        if !alt_az_driver().has_derotator() {
+            return Err(ASCOMError::invalid_operation(
+                "No derotator configured in [mount.derotator]",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Device for AlpacaRotator {
+    fn static_name(&self) -> &str {
+        "OpenPiScope Rotator"
+    }
+
+    fn unique_id(&self) -> &str {
+        ROTATOR_UNIQUE_ID
+    }
+    async fn description(&self) -> ASCOMResult<String> {
+        Ok("OpenPiScope field derotator".to_owned())
+    }
+
+    async fn connected(&self) -> ASCOMResult<bool> {
+        Ok(true)
+    }
+
+    async fn set_connected(&self, _connected: bool) -> ASCOMResult {
+        Ok(())
+    }
+
+    async fn driver_info(&self) -> ASCOMResult<String> {
+        Ok("OpenPiScope field derotator".to_owned())
+    }
+
+    async fn driver_version(&self) -> ASCOMResult<String> {
+        Ok(env!("CARGO_PKG_VERSION").to_owned())
+    }
+}
+
+#[async_trait]
+impl Rotator for AlpacaRotator {
+    async fn is_moving(&self) -> ASCOMResult<bool> {
+        Ok(alt_az_driver().rotator_moving())
+    }
+
+    async fn mechanical_position(&self) -> ASCOMResult<f64> {
+        Ok(alt_az_driver().rotator_mechanical_position() as f64)
+    }
+
+    async fn position(&self) -> ASCOMResult<f64> {
+        Ok(alt_az_driver().rotator_position().await as f64)
+    }
+
+    async fn reverse(&self) -> ASCOMResult<bool> {
+        Ok(false)
+    }
+
+    async fn step_size(&self) -> ASCOMResult<f64> {
+        Self::ensure_derotator()?;
+        let config = self
+            .storage
+            .get_derotator_config()
+            .await
+            .map_err(ASCOMError::unspecified)?
+            .ok_or(ASCOMError::NOT_IMPLEMENTED)?;
+        Ok(1.0 / config.steps_per_degree() as f64)
+    }
+
+    async fn target_position(&self) -> ASCOMResult<f64> {
+        Ok(alt_az_driver().get_derotator_target().await as f64)
+    }
+
+    async fn halt(&self) -> ASCOMResult {
+        Self::ensure_derotator()?;
+        alt_az_driver().rotator_halt().await;
+        Ok(())
+    }
+
+    async fn move_(&self, position: f64) -> ASCOMResult {
+        Self::ensure_derotator()?;
+        let current = alt_az_driver().rotator_position().await as f64;
+        let angle = Self::check_angle(current + position)?;
+        alt_az_driver()
+            .rotator_move_to(angle)
+            .await
+            .map_err(ASCOMError::invalid_operation)
+    }
+
+    async fn move_absolute(&self, position: f64) -> ASCOMResult {
+        Self::ensure_derotator()?;
+        let angle = Self::check_angle(position)?;
+        alt_az_driver()
+            .rotator_move_to(angle)
+            .await
+            .map_err(ASCOMError::invalid_operation)
+    }
+
+    async fn move_mechanical(&self, position: f64) -> ASCOMResult {
+        Self::ensure_derotator()?;
+        let angle = Self::check_angle(position)?;
+        alt_az_driver()
+            .rotator_move_mechanical(angle)
+            .await
+            .map_err(ASCOMError::invalid_operation)
+    }
+
+    async fn sync(&self, position: f64) -> ASCOMResult {
+        Self::ensure_derotator()?;
+        let angle = Self::check_angle(position)?;
+        alt_az_driver()
+            .rotator_sync(angle)
+            .await
+            .map_err(ASCOMError::invalid_operation)
+    }
+}
+
+/// Fokussierer aus `[focuser]`, wird nur mit diesem Abschnitt registriert
+#[derive(Debug)]
+struct AlpacaFocuser;
+
+impl AlpacaFocuser {
+    fn focuser() -> ASCOMResult<&'static crate::focuser::Focuser> {
+        focuser().ok_or(ASCOMError::NOT_CONNECTED)
+    }
+}
+
+#[async_trait]
+impl Device for AlpacaFocuser {
+    fn static_name(&self) -> &str {
+        "OpenPiScope Focuser"
+    }
+
+    fn unique_id(&self) -> &str {
+        FOCUSER_UNIQUE_ID
+    }
+    async fn description(&self) -> ASCOMResult<String> {
+        Ok("OpenPiScope stepper focuser".to_owned())
+    }
+
+    async fn connected(&self) -> ASCOMResult<bool> {
+        Ok(true)
+    }
+
+    async fn set_connected(&self, _connected: bool) -> ASCOMResult {
+        Ok(())
+    }
+
+    async fn driver_info(&self) -> ASCOMResult<String> {
+        Ok("OpenPiScope stepper focuser".to_owned())
+    }
+
+    async fn driver_version(&self) -> ASCOMResult<String> {
+        Ok(env!("CARGO_PKG_VERSION").to_owned())
+    }
+}
+
+#[async_trait]
+impl Focuser for AlpacaFocuser {
+    async fn absolute(&self) -> ASCOMResult<bool> {
+        Ok(true)
+    }
+
+    async fn is_moving(&self) -> ASCOMResult<bool> {
+        Ok(Self::focuser()?.is_moving())
+    }
+
+    async fn max_increment(&self) -> ASCOMResult<i32> {
+        Ok(Self::focuser()?.config().max_increment() as i32)
+    }
+
+    async fn max_step(&self) -> ASCOMResult<i32> {
+        Ok(Self::focuser()?.config().max_step as i32)
+    }
+
+    async fn position(&self) -> ASCOMResult<i32> {
+        Ok(Self::focuser()?.position())
+    }
+
+    async fn step_size(&self) -> ASCOMResult<f64> {
+        Self::focuser()?
+            .config()
+            .step_size
+            .ok_or(ASCOMError::NOT_IMPLEMENTED)
+    }
+
+    async fn temp_comp(&self) -> ASCOMResult<bool> {
+        Ok(Self::focuser()?.temp_comp().await)
+    }
+
+    async fn set_temp_comp(&self, temp_comp: bool) -> ASCOMResult {
+        Self::focuser()?
+            .set_temp_comp(temp_comp)
+            .await
+            .map_err(ASCOMError::invalid_operation)
+    }
+
+    async fn temp_comp_available(&self) -> ASCOMResult<bool> {
+        Ok(Self::focuser()?.config().temperature_file.is_some())
+    }
+
+    async fn temperature(&self) -> ASCOMResult<f64> {
+        let focuser = Self::focuser()?;
+        if focuser.config().temperature_file.is_none() {
+            return Err(ASCOMError::NOT_IMPLEMENTED);
+        }
+        focuser
+            .temperature()
+            .await
+            .map(f64::from)
+            .ok_or(ASCOMError::VALUE_NOT_SET)
+    }
+
+    async fn halt(&self) -> ASCOMResult {
+        Self::focuser()?.halt();
+        Ok(())
+    }
+
+    async fn move_(&self, position: i32) -> ASCOMResult {
+        Self::focuser()?
+            .move_to(position)
+            .await
+            .map_err(ASCOMError::invalid_value)
+    }
+}
+
+/// Schaltausgänge aus `[[switch]]`, die Id ist der Index in der Konfiguration
+#[derive(Debug)]
+struct AlpacaSwitch;
+
+impl AlpacaSwitch {
+    fn switch(id: u32) -> ASCOMResult<&'static SwitchOutput> {
+        switches()
+            .get(id as usize)
+            .ok_or_else(|| ASCOMError::invalid_value(format!("Switch {id} does not exist")))
+    }
+}
+
+#[async_trait]
+impl Device for AlpacaSwitch {
+    fn static_name(&self) -> &str {
+        "OpenPiScope Switch"
+    }
+
+    fn unique_id(&self) -> &str {
+        SWITCH_UNIQUE_ID
+    }
+    async fn description(&self) -> ASCOMResult<String> {
+        Ok("OpenPiScope GPIO outputs".to_owned())
+    }
+
+    async fn connected(&self) -> ASCOMResult<bool> {
+        Ok(true)
+    }
+
+    async fn set_connected(&self, _connected: bool) -> ASCOMResult {
+        Ok(())
+    }
+
+    async fn driver_info(&self) -> ASCOMResult<String> {
+        Ok("OpenPiScope GPIO outputs".to_owned())
+    }
+
+    async fn driver_version(&self) -> ASCOMResult<String> {
+        Ok(env!("CARGO_PKG_VERSION").to_owned())
+    }
+}
+
+#[async_trait]
+impl Switch for AlpacaSwitch {
+    async fn max_switch(&self) -> ASCOMResult<i32> {
+        Ok(switches().len() as i32)
+    }
+
+    async fn can_write(&self, id: u32) -> ASCOMResult<bool> {
+        Self::switch(id).map(|_| true)
+    }
+
+    async fn get_switch(&self, id: u32) -> ASCOMResult<bool> {
+        Ok(Self::switch(id)?.value() > 0.0)
+    }
+
+    async fn get_switch_description(&self, id: u32) -> ASCOMResult<String> {
+        Ok(Self::switch(id)?.config().description.clone())
+    }
+
+    async fn get_switch_name(&self, id: u32) -> ASCOMResult<String> {
+        Ok(Self::switch(id)?.config().name.clone())
+    }
+
+    async fn get_switch_value(&self, id: u32) -> ASCOMResult<f64> {
+        Ok(Self::switch(id)?.value())
+    }
+
+    async fn min_switch_value(&self, id: u32) -> ASCOMResult<f64> {
+        Self::switch(id).map(|_| 0.0)
+    }
+
+    async fn max_switch_value(&self, id: u32) -> ASCOMResult<f64> {
+        Ok(Self::switch(id)?.config().max_value())
+    }
+
+    async fn set_switch(&self, id: u32, state: bool) -> ASCOMResult {
+        let switch = Self::switch(id)?;
+        let value = if state { switch.config().max_value() } else { 0.0 };
+        switch.set_value(value).map_err(ASCOMError::unspecified)
+    }
+
+    async fn set_switch_value(&self, id: u32, value: f64) -> ASCOMResult {
+        Self::switch(id)?
+            .set_value(value)
+            .map_err(ASCOMError::invalid_value)
+    }
+
+    async fn switch_step(&self, id: u32) -> ASCOMResult<f64> {
+        Self::switch(id).map(|_| 1.0)
     }
 }
@@ -1,149 +1,1047 @@
-use super::{stepper_axis::StepperAxis, telescope_position::TelescopePosition};
+use super::{
+    closed_loop::{pointing_error, ClosedLoopAction, ClosedLoopConfig},
+    field_rotation::FieldRotation,
+    motion_profile::MotionPhase,
+    mount_backend::{Axis, BackendKind, GpioBackend, MountBackend, SimulatedBackend},
+    mount_config::AxisConfig,
+    pointing_model::AlignmentStar,
+    telescope_position::{AltAZPostion, EqPostion, TelescopePosition},
+    tracking::TrackingRate,
+    zenith_pass::{self, ZenithPass},
+};
+use crate::clock::clock;
+use crate::sensor::SensorStatus;
 use crate::storage::storage;
-use atomic_struct_core::AtomicMember;
-use tokio::{sync::Mutex, task};
+use chrono::{DateTime, Utc};
+use open_pi_scope::alignment::Orientation;
+use serde::Serialize;
 
-use anyhow::Result;
-use rppal::gpio::{Gpio, OutputPin as RppalOutputPin};
-use std::sync::{Arc, OnceLock};
+use anyhow::{bail, Result};
+use rppal::gpio::{Gpio, Trigger};
+use std::{
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+const IDLE_INTERVAL: Duration = Duration::from_secs(1);
+/// Während der Nachführung wird das Ziel häufiger neu berechnet
+const TRACKING_INTERVAL: Duration = Duration::from_millis(250);
+/// Abfrageintervall beim Warten auf das Ende einer Bewegung
+const SLEW_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// Abstand zum Ziel in Grad, ab dem eine Achse als angekommen gilt
+const SETTLE_TOLERANCE: f32 = 0.05;
+/// So oft wird der nächste Zenitdurchgang neu vorhergesagt
+const ZENITH_PASS_INTERVAL: Duration = Duration::from_secs(30);
+/// Entprellzeit des Not-Aus-Tasters
+const ESTOP_DEBOUNCE: Duration = Duration::from_millis(20);
 
 pub fn alt_az_driver() -> &'static AltAzDriver {
     static ALT_AZ_DRIVER: OnceLock<AltAzDriver> = OnceLock::new();
-    ALT_AZ_DRIVER.get_or_init(|| {
-        let m = AltAzDriver::new_raw();
-        m
-    })
-}
-
-fn alt_axis() -> &'static Arc<Mutex<StepperAxis<RppalOutputPin, RppalOutputPin, RppalOutputPin>>> {
-    static ALT_AXIS: OnceLock<
-        Arc<Mutex<StepperAxis<RppalOutputPin, RppalOutputPin, RppalOutputPin>>>,
-    > = OnceLock::new();
-    ALT_AXIS.get_or_init(|| {
-        let gpio = Gpio::new().expect("Failed to initialize GPIO");
-        let alt_step = gpio
-            .get(17)
-            .expect("Failed to get GPIO pin 17")
-            .into_output();
-        let alt_dir = gpio
-            .get(27)
-            .expect("Failed to get GPIO pin 27")
-            .into_output();
-        let alt_en = gpio
-            .get(22)
-            .expect("Failed to get GPIO pin 22")
-            .into_output();
-
-        let ax = StepperAxis::new(
-            alt_step,
-            alt_dir,
-            Some(alt_en),
-            100.0, // steps per unit
-            10.0,  // max speed in units/sec
-            1.0,   // acceleration in units/sec^2
-        );
-
-        let m = Arc::new(Mutex::new(ax));
-        m
-    })
-}
-
-fn az_axis() -> &'static Arc<Mutex<StepperAxis<RppalOutputPin, RppalOutputPin, RppalOutputPin>>> {
-    static AZ_AXIS: OnceLock<
-        Arc<Mutex<StepperAxis<RppalOutputPin, RppalOutputPin, RppalOutputPin>>>,
-    > = OnceLock::new();
-    AZ_AXIS.get_or_init(|| {
-        let gpio = Gpio::new().expect("Failed to initialize GPIO");
-        let az_step = gpio
-            .get(18)
-            .expect("Failed to get GPIO pin 18")
-            .into_output();
-        let az_dir = gpio
-            .get(24)
-            .expect("Failed to get GPIO pin 24")
-            .into_output();
-        let az_en = gpio.get(4).expect("Failed to get GPIO pin 4").into_output();
-        let ax = StepperAxis::new(
-            az_step,
-            az_dir,
-            Some(az_en),
-            100.0, // steps per unit
-            10.0,  // max speed in units/sec
-            1.0,   // acceleration in units/sec^2
-        );
-        let m = Arc::new(Mutex::new(ax));
-        m
-    })
-}
-
-#[atomic_struct::atomic_struct]
-#[derive(Debug, Clone)]
-pub(crate) struct AltAzDriver {
-    pub(crate) target_position: Option<TelescopePosition>,
-    position_set: bool,
+    ALT_AZ_DRIVER.get_or_init(AltAzDriver::new_raw)
 }
 
-impl AltAzDriver {
-    pub fn new_raw() -> Self {
-        AltAzDriver {
-            target_position: AtomicMember::new(None),
-            position_set: AtomicMember::new(false), // position_set
+static BACKEND: OnceLock<Box<dyn MountBackend>> = OnceLock::new();
+
+/// Liest `[mount]` und richtet das gewählte Backend für alle Achsen ein.
+/// Muss vor allen anderen Funktionen dieses Moduls aufgerufen werden.
+pub(crate) async fn init_backend() -> Result<()> {
+    for axis in ["alt", "az"] {
+        if storage().get_optional_axis_config(axis).await?.is_none() {
+            println!("No [mount.{axis}] section in the configuration, using the built-in pins and speeds");
+        }
+    }
+    let alt = storage().get_axis_config("alt").await?;
+    let az = storage().get_axis_config("az").await?;
+    let derotator = storage().get_derotator_config().await?;
+    let backend: Box<dyn MountBackend> = match storage().get_backend_kind().await? {
+        BackendKind::Gpio => Box::new(GpioBackend::new(&alt, &az, derotator.as_ref())?),
+        BackendKind::Simulated => {
+            println!("Using simulated mount backend");
+            Box::new(SimulatedBackend::new(&alt, &az, derotator.as_ref()))
         }
+    };
+    if BACKEND.set(backend).is_err() {
+        bail!("Mount backend is already initialized");
+    }
+    Ok(())
+}
+
+fn backend() -> &'static dyn MountBackend {
+    BACKEND
+        .get()
+        .expect("init_backend must be called before the mount is used")
+        .as_ref()
+}
+
+pub(crate) use driver::AltAzDriver;
+
+/// Eigenes Modul, damit die Lint-Ausnahme für den Konstruktor mit einem Parameter pro Feld
+/// nicht für den ganzen Treiber gilt
+#[allow(clippy::too_many_arguments)]
+mod driver {
+    use super::{AltAZPostion, DateTime, TelescopePosition, TrackingRate, Utc, ZenithPass};
+    use atomic_struct_core::AtomicMember;
+
+    #[atomic_struct::atomic_struct]
+    #[derive(Debug, Clone)]
+    pub(crate) struct AltAzDriver {
+        pub(crate) target_position: Option<TelescopePosition>,
+        pub(super) position_set: bool,
+        pub(crate) tracking: bool,
+        pub(crate) tracking_rate: TrackingRate,
+        /// Zeitpunkt, ab dem ein äquatoriales Ziel nachgeführt wird
+        pub(super) tracking_since: DateTime<Utc>,
+        /// Not-Aus ausgelöst, Bewegungen bleiben bis zur Freigabe gesperrt
+        pub(crate) emergency_stop: bool,
+        /// Ein neues Ziel wurde angefahren und ist noch nicht erreicht
+        pub(crate) slewing: bool,
+        /// Eine Achse dreht sich über `move_axis`, ohne Ziel
+        pub(super) manual_move: bool,
+        /// In Parkposition, Bewegungen bleiben bis `unpark` gesperrt
+        pub(crate) parked: bool,
+        /// Letzte Abweichung Lagesensor − Schrittzähler, solange die Regelung aktiv ist
+        pub(super) pointing_error: Option<AltAZPostion>,
+        /// Schrittverlust oder rutschende Kupplung, Bewegungen bleiben bis `clear_fault` gesperrt
+        pub(crate) fault: Option<String>,
+        /// Vorhergesagter Zenitdurchgang des nachgeführten Ziels
+        pub(crate) zenith_pass: Option<ZenithPass>,
+        /// Positionswinkel des Bildfelds, den der Derotator hält, in Grad
+        pub(super) derotator_angle: Option<f32>,
+        /// Ziel des letzten Rotator-Auftrags als Positionswinkel in Grad
+        pub(crate) derotator_target: f32,
+    }
+
+    impl AltAzDriver {
+        pub fn new_raw() -> Self {
+            AltAzDriver {
+                target_position: AtomicMember::new(None),
+                position_set: AtomicMember::new(false), // position_set
+                tracking: AtomicMember::new(false),
+                tracking_rate: AtomicMember::new(TrackingRate::default()),
+                tracking_since: AtomicMember::new(Utc::now()),
+                emergency_stop: AtomicMember::new(false),
+                slewing: AtomicMember::new(false),
+                manual_move: AtomicMember::new(false),
+                parked: AtomicMember::new(false),
+                pointing_error: AtomicMember::new(None),
+                fault: AtomicMember::new(None),
+                zenith_pass: AtomicMember::new(None),
+                derotator_angle: AtomicMember::new(None),
+                derotator_target: AtomicMember::new(0.0),
+            }
+        }
+    }
+}
+
+/// Zustand einer Achse
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, utoipa::ToSchema)]
+pub enum AxisState {
+    Idle,
+    Accelerating,
+    Cruising,
+    Decelerating,
+    Tracking,
+}
+
+impl From<MotionPhase> for AxisState {
+    fn from(value: MotionPhase) -> Self {
+        match value {
+            MotionPhase::Idle => AxisState::Idle,
+            MotionPhase::Accelerating => AxisState::Accelerating,
+            MotionPhase::Cruising => AxisState::Cruising,
+            MotionPhase::Decelerating => AxisState::Decelerating,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct AxisStatus {
+    pub state: AxisState,
+    /// in Grad
+    pub position: f32,
+    /// in Grad
+    pub target: f32,
+    /// in Grad/s
+    pub velocity: f32,
+    /// Lagesensor − Schrittzähler in Grad, nur bei aktiver Regelung
+    pub pointing_error: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct MountStatus {
+    pub slewing: bool,
+    pub tracking: bool,
+    pub tracking_rate: TrackingRate,
+    pub emergency_stop: bool,
+    pub parked: bool,
+    pub fault: Option<String>,
+    /// Nächster Zenitdurchgang, in dem die Azimutachse zurückbleibt
+    pub zenith_pass: Option<ZenithPass>,
+    pub field_rotation: FieldRotation,
+    pub alt: AxisStatus,
+    pub az: AxisStatus,
+    /// Nur mit `[mount.derotator]`
+    pub derotator: Option<AxisStatus>,
+}
+
+/// Achsstellung laut Schrittzähler, ohne Pointing-Modell
+fn axis_position() -> AltAZPostion {
+    AltAZPostion {
+        alt: backend().position(Axis::Alt),
+        az: backend().position(Axis::Az),
     }
+}
+
+/// Stellung nahe `current`, die `angle` modulo 360° entspricht
+fn nearest_angle(current: f32, angle: f32) -> f32 {
+    current + (angle - current + 180.0).rem_euclid(360.0) - 180.0
+}
+
+fn axis_settled(axis: Axis) -> bool {
+    let motion = backend().status(axis);
+    !motion.moving || (motion.target - motion.position).abs() <= SETTLE_TOLERANCE
+}
+
+/// Höhe und Azimut, wie sie der Lagesensor sieht
+fn orientation_alt_az(orientation: &Orientation) -> AltAZPostion {
+    AltAZPostion {
+        alt: orientation.euler.pitch,
+        az: orientation.euler.yaw,
+    }
+}
 
+impl AltAzDriver {
+    /// Ort am Himmel, auf den die Montierung laut Schrittzähler und Pointing-Modell zeigt
     pub async fn get_current_position(&self) -> Result<TelescopePosition> {
-        let alt = alt_axis().lock().await.position();
-        let az = az_axis().lock().await.position();
-        Ok(TelescopePosition::new_alt_az(alt, az))
+        let sky = storage().get_pointing_model().await.sky_position(axis_position());
+        Ok(TelescopePosition::new_alt_az(sky.alt, sky.az))
+    }
+
+    async fn ensure_motion_allowed(&self) -> Result<()> {
+        if self.get_emergency_stop().await {
+            bail!("Emergency stop is active");
+        }
+        if self.get_parked().await {
+            bail!("Mount is parked");
+        }
+        if let Some(fault) = self.get_fault().await {
+            bail!("Pointing fault: {fault}");
+        }
+        Ok(())
+    }
+
+    /// Führt ein äquatoriales Ziel ab jetzt mit der eingestellten Rate nach
+    pub async fn track(&self, target: EqPostion) -> Result<()> {
+        self.ensure_motion_allowed().await?;
+        clock().ensure_trusted().await?;
+        self.limit_target(TelescopePosition::Eq(target)).await?;
+        self.set_manual_move(false).await;
+        self.set_tracking_since(clock().now().await).await;
+        self.set_zenith_pass(None).await;
+        self.set_target_position(Some(TelescopePosition::Eq(target)))
+            .await;
+        self.set_tracking(true).await;
+        self.set_slewing(true).await;
+        Ok(())
+    }
+
+    /// Wechselt die Nachführrate. Ein nachgeführtes Ziel wird vorher auf seine bis jetzt
+    /// abgedriftete Position gesetzt, sonst spränge es um die gesamte Drift seit `track`.
+    pub async fn change_tracking_rate(&self, rate: TrackingRate) {
+        if let (true, Some(TelescopePosition::Eq(target))) =
+            (self.get_tracking().await, self.get_target_position().await)
+        {
+            let now = clock().now().await;
+            let drifted = self
+                .get_tracking_rate()
+                .await
+                .apply(target, self.get_tracking_since().await, now);
+            self.set_target_position(Some(TelescopePosition::Eq(drifted)))
+                .await;
+            self.set_tracking_since(now).await;
+            // Die Vorhersage hängt von der Rate ab
+            self.set_zenith_pass(None).await;
+        }
+        self.set_tracking_rate(rate).await;
+    }
+
+    /// Fährt eine Position an; äquatoriale Ziele werden danach mit `rate` nachgeführt
+    pub async fn goto(&self, position: TelescopePosition, rate: TrackingRate) -> Result<()> {
+        self.ensure_motion_allowed().await?;
+        match position {
+            TelescopePosition::Eq(target) => {
+                self.set_tracking_rate(rate).await;
+                self.track(target).await
+            }
+            TelescopePosition::AltAz(target) => {
+                self.set_tracking(false).await;
+                self.slew_to_alt_az(target).await
+            }
+        }
+    }
+
+    /// Fährt eine feste Höhe/Azimut an, ohne nachzuführen
+    pub async fn slew_to_alt_az(&self, target: AltAZPostion) -> Result<()> {
+        self.ensure_motion_allowed().await?;
+        let target = self.limit_target(TelescopePosition::AltAz(target)).await?;
+        self.move_to_alt_az(target).await
+    }
+
+    /// Fährt Park- bzw. Home-Position an; das Horizontprofil gilt dafür nicht
+    async fn slew_to_fixed_position(&self, target: AltAZPostion) -> Result<()> {
+        self.ensure_motion_allowed().await?;
+        storage().get_limits().await.check_altitude_range(target)?;
+        self.move_to_alt_az(target).await
+    }
+
+    async fn move_to_alt_az(&self, target: AltAZPostion) -> Result<()> {
+        self.set_manual_move(false).await;
+        self.set_target_position(Some(TelescopePosition::AltAz(target)))
+            .await;
+        self.set_slewing(true).await;
+        self.go_to_target_position().await
+    }
+
+    /// Dreht eine Achse dauerhaft mit `rate` Grad/s (ASCOM MoveAxis), 0 bremst sie ab.
+    /// Ziel und Nachführung werden verworfen; an der Höhen- bzw. Kabelgrenze hält die
+    /// Achse an.
+    pub async fn move_axis(&self, axis: Axis, rate: f32) -> Result<()> {
+        if rate == 0.0 {
+            backend().stop(axis);
+            return Ok(());
+        }
+        self.ensure_motion_allowed().await?;
+        let max_speed = self.max_manual_rate(axis).await?;
+        if !rate.is_finite() || rate.abs() > max_speed {
+            bail!("Rate {rate}°/s is outside ±{max_speed}°/s");
+        }
+        if let Some(limit) = self.manual_limit(axis, rate, 0.0).await {
+            bail!(limit);
+        }
+        self.set_tracking(false).await;
+        self.set_target_position(None).await;
+        self.set_manual_move(true).await;
+        self.set_slewing(true).await;
+        backend().set_velocity(axis, rate);
+        Ok(())
+    }
+
+    /// Höchste Rate für `move_axis` in Grad/s, die `max_speed` der Achse
+    pub async fn max_manual_rate(&self, axis: Axis) -> Result<f32> {
+        Ok(manual_axis_config(axis).await?.max_speed)
+    }
+
+    /// Warum sich `axis` nicht weiter in Richtung `rate` drehen darf, wenn sie noch
+    /// `braking` Grad bis zum Stillstand braucht. Es gelten nur Höhenbereich und
+    /// Kabelgrenzen, nicht das Horizontprofil.
+    async fn manual_limit(&self, axis: Axis, rate: f32, braking: f32) -> Option<String> {
+        let limits = storage().get_limits().await;
+        match axis {
+            Axis::Alt => {
+                let model = storage().get_pointing_model().await;
+                let alt = model.sky_position(axis_position()).alt;
+                let ahead = alt + braking * rate.signum();
+                let limit = if rate < 0.0 {
+                    limits.min_altitude
+                } else {
+                    limits.max_altitude
+                };
+                ((ahead - limit) * rate >= 0.0)
+                    .then(|| format!("Altitude limit of {limit:.1}° reached (at {alt:.1}°)"))
+            }
+            Axis::Az => {
+                let az = backend().position(Axis::Az);
+                let ahead = az + braking * rate.signum();
+                let limit = if rate < 0.0 { limits.az_min } else { limits.az_max };
+                limit.filter(|limit| (ahead - limit) * rate >= 0.0).map(|limit| {
+                    format!("Cable wrap limit of {limit}° reached (azimuth axis at {az:.1}°)")
+                })
+            }
+            Axis::Derotator => None,
+        }
+    }
+
+    /// Bremst Achsen über `move_axis` rechtzeitig vor einer Grenze ab
+    async fn check_manual_move(&self) {
+        if !self.get_manual_move().await {
+            return;
+        }
+        for axis in [Axis::Alt, Axis::Az] {
+            let motion = backend().status(axis);
+            if motion.velocity == 0.0 {
+                continue;
+            }
+            let braking = match manual_axis_config(axis).await {
+                Ok(config) => config.braking_distance(motion.velocity.abs()),
+                Err(_) => 0.0,
+            };
+            if let Some(limit) = self.manual_limit(axis, motion.velocity, braking).await {
+                if motion.phase != MotionPhase::Decelerating {
+                    println!("Stopping manual move: {limit}");
+                }
+                backend().stop(axis);
+            }
+        }
+    }
+
+    /// Prüft ein Ziel gegen `[mount.limits]`; liefert die Höhe/Azimut, die angefahren wird
+    pub async fn limit_target(&self, target: TelescopePosition) -> Result<AltAZPostion> {
+        let site = storage().get_position().await;
+        let alt_az = target.get_alt_az(&site, clock().now().await);
+        storage().get_limits().await.apply(alt_az)
+    }
+
+    /// Wartet, bis beide Achsen ihr Ziel erreicht haben
+    pub async fn wait_for_slew(&self) -> Result<()> {
+        while self.update_slewing().await {
+            tokio::time::sleep(SLEW_POLL_INTERVAL).await;
+        }
+        if self.get_target_position().await.is_none() {
+            bail!("Slew aborted");
+        }
+        Ok(())
     }
+
+    /// Beendet `slewing`, sobald beide Achsen ihr Ziel erreicht haben bzw. stehen
+    async fn update_slewing(&self) -> bool {
+        let settled = if self.get_manual_move().await {
+            // Ohne Ziel zählt nur der Stillstand
+            !backend().status(Axis::Alt).moving && !backend().status(Axis::Az).moving
+        } else {
+            axis_settled(Axis::Alt) && axis_settled(Axis::Az)
+        };
+        if self.get_slewing().await && settled {
+            self.set_slewing(false).await;
+            self.set_manual_move(false).await;
+        }
+        self.get_slewing().await
+    }
+
+    pub async fn status(&self) -> MountStatus {
+        let tracking = self.get_tracking().await;
+        let slewing = self.get_slewing().await;
+        let error = self.get_pointing_error().await;
+        let axis_status = |axis: Axis| {
+            let motion = backend().status(axis);
+            AxisStatus {
+                state: if tracking && !slewing {
+                    AxisState::Tracking
+                } else {
+                    motion.phase.into()
+                },
+                position: motion.position,
+                target: motion.target,
+                velocity: motion.velocity,
+                pointing_error: error.and_then(|error| match axis {
+                    Axis::Alt => Some(error.alt),
+                    Axis::Az => Some(error.az),
+                    Axis::Derotator => None,
+                }),
+            }
+        };
+        MountStatus {
+            slewing,
+            tracking,
+            tracking_rate: self.get_tracking_rate().await,
+            emergency_stop: self.get_emergency_stop().await,
+            parked: self.get_parked().await,
+            fault: self.get_fault().await,
+            zenith_pass: self.get_zenith_pass().await,
+            field_rotation: self.field_rotation().await,
+            alt: axis_status(Axis::Alt),
+            az: axis_status(Axis::Az),
+            derotator: backend()
+                .has_axis(Axis::Derotator)
+                .then(|| axis_status(Axis::Derotator)),
+        }
+    }
+
+    /// Bildfelddrehung am aktuellen Ziel, ohne Ziel an der aktuellen Position
+    pub async fn field_rotation(&self) -> FieldRotation {
+        let position = match self.get_target_position().await {
+            Some(target) => self.target_alt_az(target, clock().now().await).await,
+            None => storage().get_pointing_model().await.sky_position(axis_position()),
+        };
+        let site = storage().get_position().await;
+        FieldRotation::at(position, &site, self.get_tracking_rate().await)
+    }
+
+    pub fn has_derotator(&self) -> bool {
+        backend().has_axis(Axis::Derotator)
+    }
+
+    async fn ensure_derotator_allowed(&self) -> Result<()> {
+        if !self.has_derotator() {
+            bail!("No derotator configured in [mount.derotator]");
+        }
+        if self.get_emergency_stop().await {
+            bail!("Emergency stop is active");
+        }
+        Ok(())
+    }
+
+    /// Positionswinkel des Bildfelds in Grad: Derotator-Stellung plus parallaktischer Winkel.
+    /// Ohne Derotator ist das der parallaktische Winkel.
+    pub async fn rotator_position(&self) -> f32 {
+        let parallactic_angle = self.field_rotation().await.parallactic_angle;
+        (backend().position(Axis::Derotator) + parallactic_angle).rem_euclid(360.0)
+    }
+
+    /// Stellung der Derotator-Achse in Grad
+    pub fn rotator_mechanical_position(&self) -> f32 {
+        backend().position(Axis::Derotator).rem_euclid(360.0)
+    }
+
+    pub fn rotator_moving(&self) -> bool {
+        !axis_settled(Axis::Derotator)
+    }
+
+    /// Dreht das Bildfeld auf den Positionswinkel und hält ihn, während sich der
+    /// parallaktische Winkel ändert
+    pub async fn rotator_move_to(&self, angle: f32) -> Result<()> {
+        self.ensure_derotator_allowed().await?;
+        let angle = angle.rem_euclid(360.0);
+        self.set_derotator_target(angle).await;
+        self.set_derotator_angle(Some(angle)).await;
+        self.update_derotator().await;
+        Ok(())
+    }
+
+    /// Fährt die Derotator-Achse auf eine feste Stellung, die Bildfelddrehung wird bis
+    /// zum nächsten `rotator_move_to` nicht mehr ausgeglichen
+    pub async fn rotator_move_mechanical(&self, position: f32) -> Result<()> {
+        self.ensure_derotator_allowed().await?;
+        let parallactic_angle = self.field_rotation().await.parallactic_angle;
+        self.set_derotator_angle(None).await;
+        self.set_derotator_target((position + parallactic_angle).rem_euclid(360.0))
+            .await;
+        let current = backend().position(Axis::Derotator);
+        backend().move_to(Axis::Derotator, nearest_angle(current, position));
+        Ok(())
+    }
+
+    /// Übernimmt den Positionswinkel als aktuellen Wert, ohne den Derotator zu bewegen
+    pub async fn rotator_sync(&self, angle: f32) -> Result<()> {
+        if !self.has_derotator() {
+            bail!("No derotator configured in [mount.derotator]");
+        }
+        let angle = angle.rem_euclid(360.0);
+        let parallactic_angle = self.field_rotation().await.parallactic_angle;
+        let current = backend().position(Axis::Derotator);
+        backend().set_position(Axis::Derotator, nearest_angle(current, angle - parallactic_angle));
+        self.set_derotator_target(angle).await;
+        if self.get_derotator_angle().await.is_some() {
+            self.set_derotator_angle(Some(angle)).await;
+        }
+        Ok(())
+    }
+
+    /// Hält den Derotator an, bis zum nächsten Auftrag wird nicht mehr nachgeführt
+    pub async fn rotator_halt(&self) {
+        self.set_derotator_angle(None).await;
+        backend().stop(Axis::Derotator);
+    }
+
+    /// Gleicht die Bildfelddrehung aus. Ohne vorgegebenen Positionswinkel wird während der
+    /// Nachführung der aktuelle festgehalten.
+    async fn update_derotator(&self) {
+        if !self.has_derotator() || self.get_emergency_stop().await {
+            return;
+        }
+        let angle = match self.get_derotator_angle().await {
+            Some(angle) => angle,
+            // Erst nach dem Abbremsen festhalten, sonst läuft die Achse zurück
+            None if self.get_tracking().await && !backend().status(Axis::Derotator).moving => {
+                let angle = self.rotator_position().await;
+                self.set_derotator_target(angle).await;
+                self.set_derotator_angle(Some(angle)).await;
+                angle
+            }
+            None => return,
+        };
+        let parallactic_angle = self.field_rotation().await.parallactic_angle;
+        let current = backend().position(Axis::Derotator);
+        backend().move_to(Axis::Derotator, nearest_angle(current, angle - parallactic_angle));
+    }
+
+    /// Fährt ein äquatoriales Ziel an, wartet bis beide Achsen angekommen sind
+    /// und führt es danach weiter nach
+    pub async fn slew_to(&self, target: EqPostion) -> Result<()> {
+        self.track(target).await?;
+        self.go_to_target_position().await?;
+        self.wait_for_slew().await
+    }
+
+    /// Fährt ohne Nachführung in die Parkposition und sperrt danach weitere Bewegungen
+    pub async fn park(&self) -> Result<()> {
+        if self.get_parked().await {
+            return Ok(());
+        }
+        self.set_tracking(false).await;
+        self.slew_to_fixed_position(storage().get_park_position().await)
+            .await?;
+        self.wait_for_slew().await?;
+        self.set_parked(true).await;
+        self.persist_position().await?;
+        storage().set_parked(true).await
+    }
+
+    pub async fn unpark(&self) -> Result<()> {
+        self.set_parked(false).await;
+        storage().set_parked(false).await
+    }
+
+    /// Übernimmt die aktuelle Position als Parkposition
+    pub async fn set_park_here(&self) -> Result<()> {
+        let site = storage().get_position().await;
+        let position = self.get_current_position().await?;
+        storage()
+            .set_park_position(position.get_alt_az(&site, clock().now().await))
+            .await
+    }
+
+    /// Fährt die Home-Position an. Ohne Endschalter ist das die in der Konfiguration
+    /// eingetragene Achsstellung.
+    pub async fn find_home(&self) -> Result<()> {
+        self.ensure_motion_allowed().await?;
+        self.set_tracking(false).await;
+        self.slew_to_fixed_position(storage().get_home_position().await)
+            .await?;
+        self.wait_for_slew().await
+    }
+
+    pub async fn at_home(&self) -> bool {
+        let home = storage().get_home_position().await;
+        let position = storage().get_pointing_model().await.sky_position(axis_position());
+        !self.get_slewing().await
+            && !backend().status(Axis::Alt).moving
+            && !backend().status(Axis::Az).moving
+            && (position.alt - home.alt).abs() <= SETTLE_TOLERANCE
+            && ((position.az - home.az + 180.0).rem_euclid(360.0) - 180.0).abs() <= SETTLE_TOLERANCE
+    }
+
+    /// Speichert die Achsstellung, damit sie nach einem Neustart wiederhergestellt wird
+    async fn persist_position(&self) -> Result<()> {
+        storage().set_last_position(axis_position()).await
+    }
+
+    /// Bremst beide Achsen kontrolliert ab und verwirft Ziel und Nachführung
+    pub async fn abort(&self) {
+        self.set_tracking(false).await;
+        self.set_target_position(None).await;
+        backend().stop(Axis::Alt);
+        backend().stop(Axis::Az);
+        // ASCOM erwartet Slewing = false direkt nach AbortSlew, nicht erst nach dem Abbremsen
+        self.set_slewing(false).await;
+        self.set_manual_move(false).await;
+    }
+
+    /// Schaltet die Treiber sofort über den EN-Pin ab. Bewegungen bleiben gesperrt,
+    /// bis `release_emergency_stop` aufgerufen wird.
+    pub async fn emergency_stop(&self) {
+        backend().disable();
+        self.set_emergency_stop(true).await;
+        self.set_tracking(false).await;
+        self.set_target_position(None).await;
+        self.set_slewing(false).await;
+        self.set_manual_move(false).await;
+    }
+
+    /// Hebt den Not-Aus auf und schaltet die Treiber wieder ein
+    pub async fn release_emergency_stop(&self) {
+        self.set_emergency_stop(false).await;
+        backend().enable();
+    }
+
+    /// Hebt einen Positionsfehler auf. Ist der Lagesensor verfügbar, übernehmen die
+    /// Schrittzähler seine Position, damit der Fehler nicht sofort wieder auslöst.
+    pub async fn clear_fault(&self) {
+        if let Ok(config) = storage().get_closed_loop_config().await {
+            if let Some(sensor) = sensor_alt_az(&config).await {
+                self.set_current_position(TelescopePosition::AltAz(sensor))
+                    .await;
+            }
+        }
+        self.set_fault(None).await;
+    }
+
+    /// Vergleicht die Schrittzähler mit dem Lagesensor. Kleine Abweichungen werden
+    /// schrittweise nachgezogen, große halten die Montierung an.
+    async fn check_closed_loop(&self, config: &ClosedLoopConfig) {
+        let sensor = if config.enabled && self.get_position_set().await {
+            sensor_alt_az(config).await
+        } else {
+            None
+        };
+        let Some(sensor) = sensor else {
+            self.set_pointing_error(None).await;
+            return;
+        };
+        let stepper = axis_position();
+        let model = storage().get_pointing_model().await;
+        let error = pointing_error(sensor, model.sky_position(stepper));
+        self.set_pointing_error(Some(error)).await;
+
+        // Während einer Bewegung hinkt der Sensor den Schrittzählern hinterher
+        if self.get_slewing().await
+            || backend().status(Axis::Alt).moving
+            || backend().status(Axis::Az).moving
+            || self.get_emergency_stop().await
+            || self.get_fault().await.is_some()
+        {
+            return;
+        }
+
+        match config.evaluate(error) {
+            ClosedLoopAction::Fault => {
+                let fault = format!(
+                    "orientation sensor and step counters differ by {:.2}° alt, {:.2}° az",
+                    error.alt, error.az
+                );
+                println!("Pointing fault: {fault}");
+                self.set_fault(Some(fault)).await;
+                self.abort().await;
+            }
+            ClosedLoopAction::Correct(correction) => {
+                if correction.alt != 0.0 {
+                    backend().set_position(Axis::Alt, stepper.alt + correction.alt);
+                }
+                if correction.az != 0.0 {
+                    backend().set_position(Axis::Az, stepper.az + correction.az);
+                }
+            }
+        }
+    }
+
+    /// Nimmt die Koordinaten als Referenzstern für das Pointing-Modell auf, ohne die Motoren zu bewegen
+    pub async fn sync_to(&self, position: EqPostion) -> Result<()> {
+        let site = storage().get_position().await;
+        let alt_az = position.to_alt_az(&site, clock().now().await);
+        self.add_alignment_star(alt_az).await?;
+        if self.get_tracking().await {
+            // Der Stern ist übernommen, auch wenn das neue Ziel nicht nachgeführt werden kann
+            if let Err(e) = self.track(position).await {
+                println!("Could not track the synced position: {e}");
+                self.set_tracking(false).await;
+                self.hold_position().await;
+            }
+        } else {
+            self.hold_position().await;
+        }
+        Ok(())
+    }
+
+    /// Übernimmt Höhe/Azimut als tatsächliche Position. Die Drehung zwischen gemeldeter Lage
+    /// und dieser Position landet in `AlignmentData.correction`, die Motoren bleiben stehen.
+    pub async fn sync_to_alt_az(&self, position: AltAZPostion) -> Result<()> {
+        if storage().alingment_data.get_alignment().await.is_none() {
+            println!("No orientation reported yet, sync only updates the pointing model");
+        }
+        self.add_alignment_star(position).await?;
+        self.hold_position().await;
+        Ok(())
+    }
+
+    /// Hält die Achsen an ihrer aktuellen Stellung, auch wenn sich das Pointing-Modell ändert
+    async fn hold_position(&self) {
+        if self.get_target_position().await.is_some() {
+            let model = storage().get_pointing_model().await;
+            let position = model.sky_position(axis_position());
+            self.set_target_position(Some(TelescopePosition::AltAz(position)))
+                .await;
+        }
+    }
+
+    pub async fn remove_alignment_star(&self, index: usize) -> Result<()> {
+        storage().remove_alignment_star(index).await?;
+        if !self.get_tracking().await {
+            self.hold_position().await;
+        }
+        Ok(())
+    }
+
+    /// Verwirft alle Referenzsterne, danach gelten die Schrittzähler wieder unverändert
+    pub async fn clear_alignment(&self) -> Result<()> {
+        storage().clear_alignment().await?;
+        if !self.get_tracking().await {
+            self.hold_position().await;
+        }
+        Ok(())
+    }
+
+    /// Hält die aktuelle Achsstellung zusammen mit dem Ort `sky` des angefahrenen Sterns fest
+    /// und bestimmt Korrektur und Pointing-Modell neu
+    pub async fn add_alignment_star(&self, sky: AltAZPostion) -> Result<()> {
+        if !self.get_position_set().await {
+            self.set_current_position(TelescopePosition::AltAz(sky))
+                .await;
+        }
+        let mount = axis_position();
+        let sensor = storage().alingment_data.get_alignment().await;
+        storage()
+            .add_alignment_star(AlignmentStar {
+                alt: sky.alt,
+                az: sky.az,
+                mount_alt: mount.alt,
+                mount_az: mount.az,
+                sensor: sensor.map(|q| [q.w, q.i, q.j, q.k]),
+            })
+            .await
+    }
+
+    /// Startet die Nachführung auf dem aktuellen Ziel bzw. der aktuellen Position
+    pub async fn start_tracking(&self) -> Result<()> {
+        let target = match self.get_target_position().await {
+            Some(target) => target,
+            None => self.get_current_position().await?,
+        };
+        let site = storage().get_position().await;
+        self.track(target.get_eq(&site, clock().now().await)).await
+    }
+
+    /// Beendet die Nachführung und hält die aktuelle Höhe/Azimut
+    pub async fn stop_tracking(&self) {
+        self.set_tracking(false).await;
+        if let Some(target) = self.get_target_position().await {
+            let alt_az = self.target_alt_az(target, clock().now().await).await;
+            self.set_target_position(Some(TelescopePosition::AltAz(alt_az)))
+                .await;
+        }
+    }
+
+    async fn target_alt_az(&self, target: TelescopePosition, now: DateTime<Utc>) -> AltAZPostion {
+        let site = storage().get_position().await;
+        match target {
+            TelescopePosition::Eq(eq) => {
+                let since = self.get_tracking_since().await;
+                self.get_tracking_rate()
+                    .await
+                    .apply(eq, since, now)
+                    .to_alt_az(&site, now)
+            }
+            TelescopePosition::AltAz(_) => target.get_alt_az(&site, now),
+        }
+    }
+
     async fn go_to_target_position(&self) -> Result<()> {
-        let target = self.target_position.get().await;
+        if self.get_emergency_stop().await {
+            return Ok(());
+        }
+        let target = self.get_target_position().await;
         if let Some(target) = target {
-            let alt_az_target = target.get_alt_az();
-            let alt = alt_az_target.alt;
-            let az = alt_az_target.az;
-            let handle1 = task::spawn(async move {
-                let mut alt_axis_handle = alt_axis().lock().await;
-                alt_axis_handle.set_position(alt);
-            });
-            let handle2 = task::spawn(async move {
-                let mut az_axis_handler = az_axis().lock().await;
-                az_axis_handler.set_position(az.clone());
+            let limits = storage().get_limits().await;
+            let alt_az_target = self.target_alt_az(target, clock().now().await).await;
+            let model = storage().get_pointing_model().await;
+            let now = clock().now().await;
+            let zenith_pass = self.current_zenith_pass(now).await;
+            if zenith_pass.is_some_and(|pass| pass.is_active(now)) {
+                // Bis die Achse das Ziel wieder eingeholt hat, gilt die Montierung als in Bewegung
+                self.set_slewing(true).await;
+            }
+            // Feste Höhe/Azimut-Ziele wurden beim Anfahren geprüft, Park- und Home-Position
+            // dürfen unter dem Horizontprofil liegen
+            let sky = match target {
+                TelescopePosition::Eq(_) => limits.apply(alt_az_target),
+                TelescopePosition::AltAz(_) => limits
+                    .check_altitude_range(alt_az_target)
+                    .map(|()| alt_az_target),
+            };
+            let mount_target = sky.and_then(|sky| {
+                let mount = model.mount_position(sky);
+                let current = backend().position(Axis::Az);
+                // Im Zenitdurchgang nicht umkehren, wenn das Ziel mehr als 180° vorauseilt
+                let az = match zenith_pass {
+                    Some(pass) => limits.wrap_azimuth_towards(current, mount.az, pass.direction)?,
+                    None => limits.wrap_azimuth(current, mount.az)?,
+                };
+                Ok(AltAZPostion { alt: mount.alt, az })
             });
+            match mount_target {
+                Ok(mount_target) => {
+                    // Beide Achsen laufen in ihren eigenen Step-Threads gleichzeitig los
+                    backend().move_to(Axis::Alt, mount_target.alt);
+                    backend().move_to(Axis::Az, mount_target.az);
+                }
+                // Ein nachgeführtes Objekt ist unter die Grenze gesunken
+                Err(e) => {
+                    println!("Stopping at the mount limits: {e}");
+                    self.abort().await;
+                }
+            }
+        }
+        Ok(())
+    }
 
-            // Auf beide warten
-            let _ = tokio::join!(handle1, handle2);
+    /// Sagt den nächsten Zenitdurchgang des nachgeführten Ziels voraus und meldet ihn einmal
+    async fn update_zenith_pass(&self) -> Result<()> {
+        let now = clock().now().await;
+        // Einen laufenden Durchgang behalten, bis die Achse das Ziel eingeholt hat
+        if self.current_zenith_pass(now).await.is_some() {
+            return Ok(());
         }
+        let pass = match (self.get_tracking().await, self.get_target_position().await) {
+            (true, Some(TelescopePosition::Eq(target))) => {
+                let max_speed = storage().get_axis_config("az").await?.max_speed;
+                zenith_pass::predict(
+                    target,
+                    self.get_tracking_rate().await,
+                    self.get_tracking_since().await,
+                    &storage().get_position().await,
+                    now,
+                    max_speed,
+                )
+            }
+            _ => None,
+        };
+        if let Some(pass) = pass {
+            let known = self.get_zenith_pass().await;
+            if known.is_none_or(|known| known.culmination != pass.culmination) {
+                println!(
+                    "Zenith pass at {}: azimuth would need {:.1}°/s, limited to {:.1}°/s from {} to {}",
+                    pass.culmination, pass.max_az_rate, pass.max_speed, pass.start, pass.end
+                );
+            }
+        }
+        self.set_zenith_pass(pass).await;
         Ok(())
     }
 
+    /// Zenitdurchgang, in dem die Azimutachse gerade steckt oder den sie noch aufholt
+    async fn current_zenith_pass(&self, now: DateTime<Utc>) -> Option<ZenithPass> {
+        let pass = self.get_zenith_pass().await?;
+        if !self.get_tracking().await || now < pass.start {
+            return None;
+        }
+        (pass.is_active(now) || self.get_slewing().await).then_some(pass)
+    }
+
+    /// Setzt die Schrittzähler so, dass die Montierung laut Pointing-Modell auf `position` zeigt
     async fn set_current_position(&self, position: TelescopePosition) {
-        let position = position.get_alt_az();
-        let mut alt_axis_handle = alt_axis().lock().await;
-        alt_axis_handle.set_position(position.alt);
-        let mut az_axis_handle = az_axis().lock().await;
-        az_axis_handle.set_position(position.az);
+        let site = storage().get_position().await;
+        let position = position.get_alt_az(&site, clock().now().await);
+        let model = storage().get_pointing_model().await;
+        self.set_axis_position(model.mount_position(position)).await;
+    }
+
+    /// Setzt die Achspositionen; ab dann gilt die Position als bekannt und wird gespeichert
+    async fn set_axis_position(&self, position: AltAZPostion) {
+        backend().set_position(Axis::Alt, position.alt);
+        backend().set_position(Axis::Az, position.az);
+        self.set_position_set(true).await;
     }
 }
 
 pub(crate) async fn run_alt_az_driver() -> Result<()> {
     let driver_handle = alt_az_driver(); // Initialize the AltAz driver
+    driver_handle.set_parked(storage().get_parked().await).await;
+
+    // Gespeicherte Achsstellung hat Vorrang vor der Schätzung aus dem Lagesensor
+    if let Some(position) = storage().get_last_position().await {
+        driver_handle.set_axis_position(position).await;
+    }
+    let closed_loop = storage().get_closed_loop_config().await?;
+    let save_interval = storage().get_position_save_interval().await?;
+    let mut saved_position = storage().get_last_position().await;
+    let mut last_save = Instant::now();
+    // Ziel (über den Beginn der Nachführung) und Zeitpunkt der letzten Vorhersage
+    let mut zenith_prediction: Option<(DateTime<Utc>, Instant)> = None;
 
     loop {
-        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        let interval = if driver_handle.get_tracking().await || driver_handle.get_slewing().await {
+            TRACKING_INTERVAL
+        } else {
+            IDLE_INTERVAL
+        };
+        tokio::time::sleep(interval).await;
 
         let orientation = storage().get_orientation().await;
 
         if let Some(orientation) = orientation {
             if !driver_handle.get_position_set().await {
-                let target = TelescopePosition::new_alt_az(
-                    orientation.euler.pitch as f32,
-                    orientation.euler.yaw as f32,
-                );
+                let target = TelescopePosition::AltAz(orientation_alt_az(&orientation));
                 driver_handle.set_current_position(target).await;
-                driver_handle.set_position_set(true).await;
             }
         }
+        let tracking_since = driver_handle.get_tracking_since().await;
+        if zenith_prediction.is_none_or(|(since, checked)| {
+            since != tracking_since || checked.elapsed() >= ZENITH_PASS_INTERVAL
+        }) {
+            if let Err(e) = driver_handle.update_zenith_pass().await {
+                println!("Could not predict the zenith pass: {e}");
+            }
+            zenith_prediction = Some((tracking_since, Instant::now()));
+        }
         driver_handle.go_to_target_position().await?;
+        driver_handle.update_derotator().await;
+        driver_handle.check_manual_move().await;
+        driver_handle.update_slewing().await;
+        driver_handle.check_closed_loop(&closed_loop).await;
+
+        // Jeder Schreibvorgang geht auf die SD-Karte, daher nur im eingestellten Abstand
+        if save_interval.is_some_and(|interval| last_save.elapsed() >= interval) {
+            let position = axis_position();
+            if driver_handle.get_position_set().await && saved_position != Some(position) {
+                if let Err(e) = driver_handle.persist_position().await {
+                    println!("Could not save mount position: {e}");
+                }
+                saved_position = Some(position);
+            }
+            last_save = Instant::now();
+        }
+    }
+}
+
+/// Speichert beim Beenden die Achsstellung
+pub(crate) async fn shutdown() {
+    let driver = alt_az_driver();
+    if BACKEND.get().is_none() || !driver.get_position_set().await {
+        return;
+    }
+    if let Err(e) = driver.persist_position().await {
+        println!("Could not save mount position: {e}");
+    }
+}
+
+/// `[mount.alt]` bzw. `[mount.az]` für `move_axis`
+async fn manual_axis_config(axis: Axis) -> Result<AxisConfig> {
+    match axis {
+        Axis::Alt => storage().get_axis_config("alt").await,
+        Axis::Az => storage().get_axis_config("az").await,
+        Axis::Derotator => bail!("The derotator is moved through the ASCOM Rotator"),
+    }
+}
+
+/// Position aus dem Lagesensor, sofern er läuft und ausreichend kalibriert ist
+async fn sensor_alt_az(config: &ClosedLoopConfig) -> Option<AltAZPostion> {
+    let SensorStatus::Running { calibration, .. } = storage().get_sensor_status().await else {
+        return None;
+    };
+    if calibration.system < config.min_calibration {
+        return None;
+    }
+    storage()
+        .get_orientation()
+        .await
+        .map(|orientation| orientation_alt_az(&orientation))
+}
+
+/// Überwacht einen optionalen Not-Aus-Taster (gegen Masse, interner Pull-up)
+pub(crate) async fn handle_estop_button() -> Result<()> {
+    let Some(pin) = storage().get_estop_pin().await else {
+        return Ok(());
+    };
+    let gpio = Gpio::new()?;
+    let mut input = gpio.get(pin)?.into_input_pullup();
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    input.set_async_interrupt(Trigger::FallingEdge, Some(ESTOP_DEBOUNCE), move |_| {
+        // Direkt im Interrupt-Thread abschalten, nicht erst im Tokio-Task
+        backend().disable();
+        let _ = sender.send(());
+    })?;
+
+    while receiver.recv().await.is_some() {
+        println!("Emergency stop button pressed");
+        alt_az_driver().emergency_stop().await;
     }
+    Ok(())
 }
@@ -1,10 +1,12 @@
 use open_pi_scope::{alignment::Orientation, gnss, magnetic::MagneticData};
 use utoipa_axum::{routes,  router::OpenApiRouter};
-use axum::{response::{IntoResponse, Response}, Json};
+use axum::{extract::{Path, Query}, http::StatusCode, response::{IntoResponse, Response}, Json};
 use tokio::net::TcpListener;
 use utoipa_swagger_ui::SwaggerUi;
 
-use crate::storage::storage;
+use serde::Deserialize;
+
+use crate::{alt_az_driver::{alt_az_driver, MountStatus}, catalog::{self, CatalogEntry, ObjectKind}, clock::{clock, ClockStatus}, field_rotation::FieldRotation, limits::{parse_horizon, LimitsConfig}, pointing_model::ModelStatistics, sensor::SensorStatus, site::{SitePolicy, SiteStatus}, storage::storage, tracking::TrackingRate};
 
 
 pub(crate) async fn handle_web() -> anyhow::Result<()> {
@@ -14,7 +16,24 @@ pub(crate) async fn handle_web() -> anyhow::Result<()> {
 let (router, api) = OpenApiRouter::new()
     .routes(routes!(get_gnss_data))
     .routes(routes!(magnetic_data))
+    .routes(routes!(site, set_site))
+    .routes(routes!(time))
+    .routes(routes!(clear_manual_site))
     .routes(routes!(alignment_data))
+    .routes(routes!(orientation_sensor_status))
+    .routes(routes!(mount_status))
+    .routes(routes!(field_rotation))
+    .routes(routes!(abort_slew))
+    .routes(routes!(emergency_stop))
+    .routes(routes!(release_emergency_stop))
+    .routes(routes!(clear_fault))
+    .routes(routes!(pointing_model))
+    .routes(routes!(clear_alignment))
+    .routes(routes!(remove_alignment_star))
+    .routes(routes!(catalog_search))
+    .routes(routes!(goto))
+    .routes(routes!(limits))
+    .routes(routes!(set_horizon, clear_horizon))
     .split_for_parts();
 
     let router = router.merge(SwaggerUi::new("/swagger-ui").url("/apidoc/openapi.json", api));
@@ -54,6 +73,87 @@ async fn magnetic_data()->Response{
 }
 
 
+#[utoipa::path(
+    get,
+    path = "/api/site",
+    responses(
+        (status = 200, description = "Site in use, where it comes from and the [site] configuration", body = SiteStatus)
+    )
+)]
+async fn site()->Response{
+    let status=storage().get_site_status().await;
+   Json(&status).into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/time",
+    responses(
+        (status = 200, description = "Offset of the system clock against GNSS/PPS time and whether it is trusted", body = ClockStatus)
+    )
+)]
+async fn time()->Response{
+    let status=clock().status().await;
+   Json(&status).into_response()
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+struct SiteUpdate {
+    policy: Option<SitePolicy>,
+    /// Manual site in degrees, fields that are left out keep their value
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    /// in m above sea level
+    elevation: Option<f32>,
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/site",
+    request_body = SiteUpdate,
+    responses(
+        (status = 200, description = "Manual site and policy saved in [site]", body = SiteStatus),
+        (status = 400, description = "Coordinates out of range or policy \"manual\" without a manual site"),
+        (status = 500, description = "Config could not be written")
+    )
+)]
+async fn set_site(Json(update): Json<SiteUpdate>)->Response{
+    let storage=storage();
+    if update.latitude.is_some() || update.longitude.is_some() || update.elevation.is_some() {
+        let mut site=storage.get_manual_site().await;
+        site.latitude=update.latitude.unwrap_or(site.latitude);
+        site.longitude=update.longitude.unwrap_or(site.longitude);
+        site.elevation=update.elevation.unwrap_or(site.elevation);
+        if let Err(e)=site.validate() {
+            return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
+        }
+        if let Err(e)=storage.set_manual_site(site).await {
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+        }
+    }
+    if let Some(policy)=update.policy {
+        if let Err(e)=storage.set_site_policy(policy).await {
+            return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
+        }
+    }
+    Json(&storage.get_site_status().await).into_response()
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/site/manual",
+    responses(
+        (status = 200, description = "Manual site removed"),
+        (status = 409, description = "Policy is \"manual\", the site is still needed")
+    )
+)]
+async fn clear_manual_site()->Response{
+    match storage().clear_manual_site().await {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(e) => (StatusCode::CONFLICT, e.to_string()).into_response(),
+    }
+}
+
 #[utoipa::path(
     get,
     path = "/api/alignment",
@@ -66,4 +166,239 @@ async fn alignment_data()->Response{
     let storage = storage();
     let orientation_data=storage.get_orientation().await;
    Json(&orientation_data).into_response()
-}   
\ No newline at end of file
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/mount/status",
+    responses(
+        (status = 200, description = "Slewing/tracking state and motion phase of both axes", body = MountStatus)
+    )
+)]
+async fn mount_status()->Response{
+    let status=alt_az_driver().status().await;
+   Json(&status).into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/mount/field-rotation",
+    responses(
+        (status = 200, description = "Parallactic angle and field rotation rate at the current target", body = FieldRotation)
+    )
+)]
+async fn field_rotation()->Response{
+    let field_rotation=alt_az_driver().field_rotation().await;
+   Json(&field_rotation).into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/sensors/orientation",
+    responses(
+        (status = 200, description = "Configuration of the orientation sensor or why its initialization failed", body = SensorStatus)
+    )
+)]
+async fn orientation_sensor_status()->Response{
+    let status=storage().get_sensor_status().await;
+   Json(&status).into_response()
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/mount/abort",
+    responses(
+        (status = 200, description = "Both axes decelerate to a controlled stop")
+    )
+)]
+async fn abort_slew()->Response{
+    alt_az_driver().abort().await;
+    StatusCode::OK.into_response()
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/mount/emergency-stop",
+    responses(
+        (status = 200, description = "Motor drivers disabled, motion is locked until released")
+    )
+)]
+async fn emergency_stop()->Response{
+    alt_az_driver().emergency_stop().await;
+    StatusCode::OK.into_response()
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/mount/emergency-stop",
+    responses(
+        (status = 200, description = "Emergency stop released, motor drivers enabled")
+    )
+)]
+async fn release_emergency_stop()->Response{
+    alt_az_driver().release_emergency_stop().await;
+    StatusCode::OK.into_response()
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/mount/fault",
+    responses(
+        (status = 200, description = "Pointing fault cleared, step counters follow the orientation sensor")
+    )
+)]
+async fn clear_fault()->Response{
+    alt_az_driver().clear_fault().await;
+    StatusCode::OK.into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/alignment/model",
+    responses(
+        (status = 200, description = "Fitted pointing model terms with their uncertainty, the residuals of all alignment stars and the IA/IE shift of the last sync", body = ModelStatistics)
+    )
+)]
+async fn pointing_model()->Response{
+    let statistics=storage().get_pointing_statistics().await;
+   Json(&statistics).into_response()
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/alignment/stars",
+    responses(
+        (status = 200, description = "All alignment stars, the correction and the pointing model removed"),
+        (status = 500, description = "Config could not be written")
+    )
+)]
+async fn clear_alignment()->Response{
+    match alt_az_driver().clear_alignment().await {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/alignment/stars/{index}",
+    params(("index" = usize, Path, description = "Position in the list of alignment stars")),
+    responses(
+        (status = 200, description = "Alignment star removed, correction and pointing model refitted"),
+        (status = 400, description = "No such alignment star or config could not be written")
+    )
+)]
+async fn remove_alignment_star(Path(index): Path<usize>)->Response{
+    match alt_az_driver().remove_alignment_star(index).await {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    }
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+struct CatalogQuery {
+    /// Name or designation, e.g. "M31", "NGC 224", "Vega" or "Jupiter"
+    q: String,
+    /// Maximum number of results, 20 if omitted
+    limit: Option<usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/catalog/search",
+    params(CatalogQuery),
+    responses(
+        (status = 200, description = "Matching catalogue objects with their current position, exact matches first", body = Vec<CatalogEntry>)
+    )
+)]
+async fn catalog_search(Query(query): Query<CatalogQuery>)->Response{
+    let site=storage().get_position().await;
+    let results=catalog::search(&query.q, query.limit.unwrap_or(20), &site, clock().now().await);
+   Json(&results).into_response()
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/goto/{name}",
+    params(("name" = String, Path, description = "Name or designation of a catalogue object")),
+    responses(
+        (status = 200, description = "Slewing to the object, it is tracked afterwards", body = CatalogEntry),
+        (status = 404, description = "No catalogue object with this name, NGC/IC objects beyond Messier and Caldwell need a [catalog] file"),
+        (status = 409, description = "Mount is parked, stopped or faulted, or the clock is not trusted"),
+        (status = 422, description = "Object is below the horizon, outside the mount limits or is the Sun")
+    )
+)]
+async fn goto(Path(name): Path<String>)->Response{
+    let site=storage().get_position().await;
+    let Some(entry)=catalog::resolve(&name, &site, clock().now().await) else {
+        return (StatusCode::NOT_FOUND, format!("Unknown object {name}")).into_response();
+    };
+    if entry.kind == ObjectKind::Sun {
+        return (StatusCode::UNPROCESSABLE_ENTITY, "Refusing to point at the Sun").into_response();
+    }
+    if !entry.above_horizon {
+        return (StatusCode::UNPROCESSABLE_ENTITY, format!("{} is below the horizon (altitude {:.1}°)", entry.name, entry.alt)).into_response();
+    }
+    // Der Mond läuft deutlich langsamer als die Sterne
+    let driver=alt_az_driver();
+    if let Err(e)=driver.limit_target(entry.position()).await {
+        return (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()).into_response();
+    }
+    let rate=match (entry.kind, driver.get_tracking_rate().await) {
+        (ObjectKind::Moon, _) => TrackingRate::Lunar,
+        (_, TrackingRate::Lunar) => TrackingRate::Sidereal,
+        (_, rate) => rate,
+    };
+    match driver.goto(entry.position(), rate).await {
+        Ok(()) => Json(&entry).into_response(),
+        Err(e) => (StatusCode::CONFLICT, e.to_string()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/limits",
+    responses(
+        (status = 200, description = "Altitude limits, horizon profile and cable wrap limits", body = LimitsConfig)
+    )
+)]
+async fn limits()->Response{
+    let limits=storage().get_limits().await;
+   Json(&limits).into_response()
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/limits/horizon",
+    request_body(content = String, description = "Horizon profile as CSV (az,alt) or Stellarium horizon file (az alt), degrees", content_type = "text/plain"),
+    responses(
+        (status = 200, description = "Horizon profile replaced and saved in [mount.limits]"),
+        (status = 400, description = "File could not be parsed"),
+        (status = 500, description = "Config could not be written")
+    )
+)]
+async fn set_horizon(body: String)->Response{
+    let horizon=match parse_horizon(&body) {
+        Ok(horizon) => horizon,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    match storage().set_horizon(horizon).await {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/limits/horizon",
+    responses(
+        (status = 200, description = "Horizon profile removed, only the altitude limits apply"),
+        (status = 500, description = "Config could not be written")
+    )
+)]
+async fn clear_horizon()->Response{
+    match storage().set_horizon(Vec::new()).await {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
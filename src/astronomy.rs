@@ -0,0 +1,352 @@
+//! Astronomische Grundlagen: Sternzeit, Präzession, Nutation, Aberration
+//! und die Umrechnung zwischen äquatorialen und horizontalen Koordinaten.
+//!
+//! Formeln nach J. Meeus, "Astronomical Algorithms" (2. Auflage).
+
+use std::sync::OnceLock;
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use nalgebra::{Matrix3, Rotation3, Vector3};
+use serde::Deserialize;
+
+use crate::storage::storage;
+
+/// Julianisches Datum der Epoche J2000.0
+pub const J2000: f64 = 2_451_545.0;
+
+/// Differenz TT - UTC in Sekunden (32.184 s + 37 Schaltsekunden)
+const TT_MINUS_UTC: f64 = 69.184;
+
+const ARCSEC: f64 = std::f64::consts::PI / (180.0 * 3600.0);
+
+/// Konstante der jährlichen Aberration (20.49552") in Radiant
+const ABERRATION_CONSTANT: f64 = 20.495_52 * ARCSEC;
+
+static CORRECTIONS: OnceLock<AstronomyConfig> = OnceLock::new();
+
+fn default_true() -> bool {
+    true
+}
+
+/// `[astronomy]`: welche Korrekturen zwischen mittlerem und scheinbarem Ort angewandt
+/// werden. Die Präzession gilt immer.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AstronomyConfig {
+    /// Nutation (bis ~17") samt Gleichung der Äquinoktien in der Sternzeit
+    #[serde(default = "default_true")]
+    pub nutation: bool,
+    /// Jährliche Aberration (bis ~20")
+    #[serde(default = "default_true")]
+    pub aberration: bool,
+}
+
+impl Default for AstronomyConfig {
+    fn default() -> Self {
+        AstronomyConfig {
+            nutation: true,
+            aberration: true,
+        }
+    }
+}
+
+fn corrections() -> &'static AstronomyConfig {
+    CORRECTIONS.get_or_init(AstronomyConfig::default)
+}
+
+/// Liest `[astronomy]`, muss vor der ersten Koordinatenumrechnung laufen
+pub(crate) async fn init_astronomy() -> Result<()> {
+    let config = storage().get_astronomy_config().await?;
+    match (config.nutation, config.aberration) {
+        (true, true) => {}
+        (false, true) => println!("Apparent places are computed without nutation"),
+        (true, false) => println!("Apparent places are computed without aberration"),
+        (false, false) => println!("Apparent places are computed without nutation and aberration"),
+    }
+    if CORRECTIONS.set(config).is_err() {
+        bail!("Astronomy settings are already initialized");
+    }
+    Ok(())
+}
+
+/// Julianisches Datum (UTC) für einen Zeitpunkt
+pub fn julian_date(time: DateTime<Utc>) -> f64 {
+    let seconds = time.timestamp() as f64 + f64::from(time.timestamp_subsec_nanos()) * 1e-9;
+    seconds / 86_400.0 + 2_440_587.5
+}
+
+/// Julianische Jahrhunderte seit J2000.0 in Terrestrischer Zeit
+fn julian_centuries_tt(time: DateTime<Utc>) -> f64 {
+    (julian_date(time) + TT_MINUS_UTC / 86_400.0 - J2000) / 36_525.0
+}
+
+/// Mittlere Sternzeit Greenwich in Grad (Meeus 12.4)
+pub fn greenwich_mean_sidereal_time(time: DateTime<Utc>) -> f64 {
+    let jd = julian_date(time);
+    let t = (jd - J2000) / 36_525.0;
+    let gmst = 280.460_618_37 + 360.985_647_366_29 * (jd - J2000) + 0.000_387_933 * t * t
+        - t * t * t / 38_710_000.0;
+    gmst.rem_euclid(360.0)
+}
+
+/// Wahre (scheinbare) Ortssternzeit in Grad, Länge positiv nach Osten
+pub fn local_sidereal_time(time: DateTime<Utc>, longitude: f64) -> f64 {
+    let nutation = Nutation::at(time);
+    let equation_of_equinoxes = (nutation.longitude * nutation.true_obliquity().cos()).to_degrees();
+    (greenwich_mean_sidereal_time(time) + equation_of_equinoxes + longitude).rem_euclid(360.0)
+}
+
+/// Mittlere Schiefe der Ekliptik in Radiant (Meeus 22.2)
+fn mean_obliquity(t: f64) -> f64 {
+    (84_381.448 - 46.815_0 * t - 0.000_59 * t * t + 0.001_813 * t * t * t) * ARCSEC
+}
+
+/// Nutation in Länge und Schiefe (Meeus Kap. 22, verkürzte Reihe, ~0.5")
+#[derive(Debug, Clone, Copy)]
+struct Nutation {
+    /// Δψ in Radiant
+    longitude: f64,
+    /// Δε in Radiant
+    obliquity: f64,
+    /// ε0 in Radiant
+    mean_obliquity: f64,
+}
+
+impl Nutation {
+    fn at(time: DateTime<Utc>) -> Self {
+        let t = julian_centuries_tt(time);
+        if !corrections().nutation {
+            return Nutation {
+                longitude: 0.0,
+                obliquity: 0.0,
+                mean_obliquity: mean_obliquity(t),
+            };
+        }
+        let omega = (125.044_52 - 1_934.136_261 * t).to_radians();
+        let sun = (280.466_5 + 36_000.769_8 * t).to_radians();
+        let moon = (218.316_5 + 481_267.881_3 * t).to_radians();
+
+        let longitude = -17.20 * omega.sin() - 1.32 * (2.0 * sun).sin() - 0.23 * (2.0 * moon).sin()
+            + 0.21 * (2.0 * omega).sin();
+        let obliquity = 9.20 * omega.cos() + 0.57 * (2.0 * sun).cos() + 0.10 * (2.0 * moon).cos()
+            - 0.09 * (2.0 * omega).cos();
+
+        Nutation {
+            longitude: longitude * ARCSEC,
+            obliquity: obliquity * ARCSEC,
+            mean_obliquity: mean_obliquity(t),
+        }
+    }
+
+    fn true_obliquity(&self) -> f64 {
+        self.mean_obliquity + self.obliquity
+    }
+
+    /// Rotation vom mittleren in das wahre Äquatorsystem des Datums
+    fn matrix(&self) -> Matrix3<f64> {
+        // Äquator -> Ekliptik (ε0), Länge um Δψ verschieben, Ekliptik -> Äquator (ε0 + Δε)
+        (Rotation3::from_axis_angle(&Vector3::x_axis(), self.true_obliquity())
+            * Rotation3::from_axis_angle(&Vector3::z_axis(), self.longitude)
+            * Rotation3::from_axis_angle(&Vector3::x_axis(), -self.mean_obliquity))
+        .into_inner()
+    }
+}
+
+/// Präzessionsmatrix von J2000.0 auf das mittlere Äquinoktium des Datums (IAU 1976, Meeus 21.2)
+fn precession_matrix(time: DateTime<Utc>) -> Matrix3<f64> {
+    let t = julian_centuries_tt(time);
+    let zeta = (2_306.218_1 * t + 0.301_88 * t * t + 0.017_998 * t * t * t) * ARCSEC;
+    let z = (2_306.218_1 * t + 1.094_68 * t * t + 0.018_203 * t * t * t) * ARCSEC;
+    let theta = (2_004.310_9 * t - 0.426_65 * t * t - 0.041_833 * t * t * t) * ARCSEC;
+
+    (Rotation3::from_axis_angle(&Vector3::z_axis(), z)
+        * Rotation3::from_axis_angle(&Vector3::y_axis(), -theta)
+        * Rotation3::from_axis_angle(&Vector3::z_axis(), zeta))
+    .into_inner()
+}
+
+/// Geschwindigkeit der Erde in Einheiten der Lichtgeschwindigkeit,
+/// im wahren Äquatorsystem des Datums (Meeus 23.3, ohne Exzentrizitätsterme)
+fn aberration_vector(time: DateTime<Utc>, nutation: &Nutation) -> Vector3<f64> {
+    if !corrections().aberration {
+        return Vector3::zeros();
+    }
+    let t = julian_centuries_tt(time);
+    let mean_longitude = 280.466_46 + 36_000.769_83 * t;
+    let mean_anomaly = (357.529_11 + 35_999.050_29 * t).to_radians();
+    let center = (1.914_602 - 0.004_817 * t) * mean_anomaly.sin()
+        + 0.019_993 * (2.0 * mean_anomaly).sin()
+        + 0.000_289 * (3.0 * mean_anomaly).sin();
+    let sun = (mean_longitude + center).to_radians();
+    let epsilon = nutation.true_obliquity();
+
+    ABERRATION_CONSTANT
+        * Vector3::new(
+            sun.sin(),
+            -sun.cos() * epsilon.cos(),
+            -sun.cos() * epsilon.sin(),
+        )
+}
+
+fn to_unit_vector(ra: f64, dec: f64) -> Vector3<f64> {
+    Vector3::new(dec.cos() * ra.cos(), dec.cos() * ra.sin(), dec.sin())
+}
+
+fn from_unit_vector(v: &Vector3<f64>) -> (f64, f64) {
+    let v = v.normalize();
+    let ra = v.y.atan2(v.x).rem_euclid(std::f64::consts::TAU);
+    let dec = v.z.clamp(-1.0, 1.0).asin();
+    (ra, dec)
+}
+
+/// Mittlerer Ort J2000.0 -> scheinbarer Ort des Datums
+/// (Präzession, Nutation und jährliche Aberration je nach `[astronomy]`). Winkel in Radiant.
+pub fn mean_to_apparent(ra: f64, dec: f64, time: DateTime<Utc>) -> (f64, f64) {
+    let nutation = Nutation::at(time);
+    let v = nutation.matrix() * precession_matrix(time) * to_unit_vector(ra, dec);
+    let v = v + aberration_vector(time, &nutation);
+    from_unit_vector(&v)
+}
+
+/// Scheinbarer Ort des Datums -> mittlerer Ort J2000.0. Winkel in Radiant.
+pub fn apparent_to_mean(ra: f64, dec: f64, time: DateTime<Utc>) -> (f64, f64) {
+    let nutation = Nutation::at(time);
+    let v = to_unit_vector(ra, dec) - aberration_vector(time, &nutation);
+    let v = (nutation.matrix() * precession_matrix(time)).transpose() * v.normalize();
+    from_unit_vector(&v)
+}
+
+/// Stundenwinkel/Deklination -> Höhe/Azimut (Nord = 0, Ost = 90°). Winkel in Radiant.
+pub fn equatorial_to_horizontal(hour_angle: f64, dec: f64, latitude: f64) -> (f64, f64) {
+    let alt = (latitude.sin() * dec.sin() + latitude.cos() * dec.cos() * hour_angle.cos())
+        .clamp(-1.0, 1.0)
+        .asin();
+    let az = (-dec.cos() * hour_angle.sin())
+        .atan2(dec.sin() * latitude.cos() - dec.cos() * hour_angle.cos() * latitude.sin());
+    (alt, az.rem_euclid(std::f64::consts::TAU))
+}
+
+/// Höhe/Azimut (Nord = 0, Ost = 90°) -> Stundenwinkel/Deklination. Winkel in Radiant.
+pub fn horizontal_to_equatorial(alt: f64, az: f64, latitude: f64) -> (f64, f64) {
+    let dec = (latitude.sin() * alt.sin() + latitude.cos() * alt.cos() * az.cos())
+        .clamp(-1.0, 1.0)
+        .asin();
+    let hour_angle = (-az.sin() * alt.cos())
+        .atan2(alt.sin() * latitude.cos() - alt.cos() * az.cos() * latitude.sin());
+    (hour_angle.rem_euclid(std::f64::consts::TAU), dec)
+}
+
+/// Parallaktischer Winkel (Meeus 14.1), positiv westlich des Meridians. Winkel in Radiant.
+pub fn parallactic_angle(hour_angle: f64, dec: f64, latitude: f64) -> f64 {
+    hour_angle
+        .sin()
+        .atan2(latitude.tan() * dec.cos() - dec.sin() * hour_angle.cos())
+}
+
+/// Änderung des parallaktischen Winkels pro Änderung des Stundenwinkels, aus Höhe/Azimut
+/// (Nord = 0). Mal der Nachführrate ergibt das die Bildfelddrehung. Winkel in Radiant.
+pub fn field_rotation_rate(alt: f64, az: f64, latitude: f64) -> f64 {
+    -latitude.cos() * az.cos() / alt.cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn hours(h: f64, m: f64, s: f64) -> f64 {
+        (h + m / 60.0 + s / 3600.0) * 15.0
+    }
+
+    fn degrees(d: f64, m: f64, s: f64) -> f64 {
+        d.signum() * (d.abs() + m / 60.0 + s / 3600.0)
+    }
+
+    /// Abstand zweier Winkel in Bogensekunden
+    fn arcsec(a: f64, b: f64) -> f64 {
+        ((a - b + 180.0).rem_euclid(360.0) - 180.0).abs() * 3600.0
+    }
+
+    #[test]
+    fn sidereal_time() {
+        // Meeus Beispiel 12.a: 1987-04-10 0h UT, mittlere Sternzeit 13h10m46.3668s,
+        // scheinbare 13h10m46.1351s
+        let time = Utc.with_ymd_and_hms(1987, 4, 10, 0, 0, 0).unwrap();
+        assert_eq!(julian_date(time), 2_446_895.5);
+        let mean = greenwich_mean_sidereal_time(time);
+        assert!(arcsec(mean, hours(13.0, 10.0, 46.3668)) < 0.01, "{mean}");
+        let apparent = local_sidereal_time(time, 0.0);
+        // Die verkürzte Nutationsreihe ist auf etwa 0.5" genau
+        assert!(arcsec(apparent, hours(13.0, 10.0, 46.1351)) < 0.5, "{apparent}");
+
+        // Meeus Beispiel 12.b: 19h21m00s UT, mittlere Sternzeit 128.7378734°
+        let time = Utc.with_ymd_and_hms(1987, 4, 10, 19, 21, 0).unwrap();
+        assert!(arcsec(greenwich_mean_sidereal_time(time), 128.737_873_4) < 0.01);
+        // Östliche Länge erhöht die Ortssternzeit
+        let local = local_sidereal_time(time, 10.0);
+        assert!(arcsec(local, local_sidereal_time(time, 0.0) + 10.0) < 1e-6);
+    }
+
+    #[test]
+    fn apparent_place() {
+        // Meeus Beispiel 23.a: θ Persei am 2028-11-13.19 TD. Die Eigenbewegung bis dahin
+        // (+0.03425 s/a, -0.0895"/a über 28.87 Jahre) ist im mittleren Ort schon enthalten.
+        let time = Utc.with_ymd_and_hms(2028, 11, 13, 4, 33, 36).unwrap()
+            - chrono::Duration::milliseconds((TT_MINUS_UTC * 1000.0) as i64);
+        let mean = (hours(2.0, 44.0, 11.986 + 0.989), degrees(49.0, 13.0, 42.48 - 2.58));
+        let (ra, dec) = mean_to_apparent(mean.0.to_radians(), mean.1.to_radians(), time);
+        assert!(
+            arcsec(ra.to_degrees(), hours(2.0, 46.0, 14.390)) < 1.5,
+            "RA off by {}\"",
+            arcsec(ra.to_degrees(), hours(2.0, 46.0, 14.390))
+        );
+        assert!(
+            arcsec(dec.to_degrees(), degrees(49.0, 21.0, 7.45)) < 1.5,
+            "Dec off by {}\"",
+            arcsec(dec.to_degrees(), degrees(49.0, 21.0, 7.45))
+        );
+
+        // Die Rückrechnung ergibt den mittleren Ort
+        let (mean_ra, mean_dec) = apparent_to_mean(ra, dec, time);
+        assert!(arcsec(mean_ra.to_degrees(), mean.0) < 0.01);
+        assert!(arcsec(mean_dec.to_degrees(), mean.1) < 0.01);
+    }
+
+    #[test]
+    fn horizontal_coordinates() {
+        // Meeus Beispiel 13.b: Venus von Washington aus, H = 64.352133°, δ = -6°43'11.61",
+        // φ = 38°55'17". Meeus zählt den Azimut von Süd: 68.0337° entspricht 248.0337°.
+        let latitude = degrees(38.0, 55.0, 17.0).to_radians();
+        let hour_angle = 64.352_133f64.to_radians();
+        let dec = degrees(-6.0, 43.0, 11.61).to_radians();
+        let (alt, az) = equatorial_to_horizontal(hour_angle, dec, latitude);
+        assert!((alt.to_degrees() - 15.1249).abs() < 1e-4, "{}", alt.to_degrees());
+        assert!((az.to_degrees() - 248.0337).abs() < 1e-4, "{}", az.to_degrees());
+
+        let (back_hour_angle, back_dec) = horizontal_to_equatorial(alt, az, latitude);
+        assert!((back_hour_angle - hour_angle).abs() < 1e-9);
+        assert!((back_dec - dec).abs() < 1e-9);
+    }
+
+    #[test]
+    fn parallactic_angle_and_field_rotation() {
+        let latitude = 48f64.to_radians();
+        let dec = 20f64.to_radians();
+        // Am Meridian südlich des Zenits ist der Winkel null, westlich positiv
+        assert!(parallactic_angle(0.0, dec, latitude).abs() < 1e-12);
+        assert!(parallactic_angle(0.3, dec, latitude) > 0.0);
+        assert!(parallactic_angle(-0.3, dec, latitude) < 0.0);
+
+        // Die Drehrate entspricht der Ableitung des parallaktischen Winkels
+        for hour_angle in [-2.0f64, -0.5, 0.4, 1.5] {
+            let step = 1e-6;
+            let numeric = (parallactic_angle(hour_angle + step, dec, latitude)
+                - parallactic_angle(hour_angle - step, dec, latitude))
+                / (2.0 * step);
+            let (alt, az) = equatorial_to_horizontal(hour_angle, dec, latitude);
+            let rate = field_rotation_rate(alt, az, latitude);
+            assert!((rate - numeric).abs() < 1e-6, "{hour_angle}: {rate} vs {numeric}");
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use bno055::{BNO055Calibration, BNO055OperationMode, Bno055};
+use linux_embedded_hal::Delay;
+use nalgebra::UnitQuaternion;
+use rppal::i2c::I2c;
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::vec_to_calib;
+use crate::sensor::{
+    CalibrationStatus, OrientationReading, OrientationSensor, SensorError, SensorKind, SensorStage,
+};
+
+/// Der BNO055 liefert Fusionsdaten mit höchstens 100 Hz
+const MAX_SAMPLE_RATE: f32 = 100.0;
+const BNO055_ADDRESSES: [u8; 2] = [0x28, 0x29];
+
+fn default_bus() -> u8 {
+    8
+}
+
+fn default_address() -> u8 {
+    0x29
+}
+
+fn default_sample_rate() -> f32 {
+    1.0
+}
+
+/// Betriebsart der Sensorfusion
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+pub enum Bno055Mode {
+    /// 9 Freiheitsgrade mit schneller Magnetometer-Kalibrierung
+    #[default]
+    #[serde(rename = "NDOF")]
+    Ndof,
+    /// Nur Beschleunigung und Drehrate, Azimut relativ zum Start
+    #[serde(rename = "IMU")]
+    Imu,
+    /// 9 Freiheitsgrade ohne schnelle Magnetometer-Kalibrierung
+    #[serde(rename = "NDOF_FMC_OFF")]
+    NdofFmcOff,
+}
+
+impl From<Bno055Mode> for BNO055OperationMode {
+    fn from(value: Bno055Mode) -> Self {
+        match value {
+            Bno055Mode::Ndof => BNO055OperationMode::NDOF,
+            Bno055Mode::Imu => BNO055OperationMode::IMU,
+            Bno055Mode::NdofFmcOff => BNO055OperationMode::NDOF_FMC_OFF,
+        }
+    }
+}
+
+/// `[sensors.bno055]`, die Kalibrierung im selben Abschnitt verwaltet `Storage`
+#[derive(Debug, Clone, Deserialize)]
+pub struct Bno055Config {
+    #[serde(default = "default_bus")]
+    pub bus: u8,
+    #[serde(default = "default_address")]
+    pub address: u8,
+    #[serde(default)]
+    pub mode: Bno055Mode,
+    /// in Hz
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: f32,
+}
+
+impl Default for Bno055Config {
+    fn default() -> Self {
+        Bno055Config {
+            bus: default_bus(),
+            address: default_address(),
+            mode: Bno055Mode::default(),
+            sample_rate: default_sample_rate(),
+        }
+    }
+}
+
+impl Bno055Config {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !BNO055_ADDRESSES.contains(&self.address) {
+            anyhow::bail!(
+                "sensors.bno055: address must be 0x28 or 0x29, got {:#04x}",
+                self.address
+            );
+        }
+        if !(self.sample_rate > 0.0 && self.sample_rate <= MAX_SAMPLE_RATE) {
+            anyhow::bail!(
+                "sensors.bno055: sample_rate must be in 0..={MAX_SAMPLE_RATE} Hz, got {}",
+                self.sample_rate
+            );
+        }
+        Ok(())
+    }
+}
+
+/// BNO055 mit eigener Sensorfusion
+pub struct Bno055Sensor {
+    imu: Bno055<I2c>,
+    delay: Delay,
+    sample_rate: f32,
+    /// Zuletzt gespeichertes Kalibrierprofil
+    saved_calib: Option<BNO055Calibration>,
+}
+
+impl Bno055Sensor {
+    /// Initialisiert den Sensor und lädt ein gespeichertes Kalibrierprofil
+    pub fn new(config: &Bno055Config, calibration: Option<Vec<u8>>) -> Result<Self, SensorError> {
+        let i2c = I2c::with_bus(config.bus).map_err(|e| {
+            SensorError::new(SensorStage::Bus, format!("/dev/i2c-{}: {e}", config.bus))
+        })?;
+
+        let imu = Bno055::new(i2c);
+        let mut imu = if config.address == default_address() {
+            imu
+        } else {
+            imu.with_alternative_address()
+        };
+        let mut delay = Delay;
+
+        imu.init(&mut delay).map_err(|e| {
+            SensorError::new(
+                SensorStage::Init,
+                format!("BNO055 at {:#04x}: {e:?}", config.address),
+            )
+        })?;
+
+        imu.set_mode(config.mode.into(), &mut delay)
+            .map_err(|e| SensorError::new(SensorStage::Mode, format!("{e:?}")))?;
+
+        let saved_calib = calibration.map(vec_to_calib);
+
+        if let Some(calib) = saved_calib {
+            imu.set_calibration_profile(calib, &mut delay)
+                .map_err(|e| SensorError::new(SensorStage::Calibration, format!("{e:?}")))?;
+        }
+
+        Ok(Bno055Sensor {
+            imu,
+            delay,
+            sample_rate: config.sample_rate,
+            saved_calib,
+        })
+    }
+}
+
+impl OrientationSensor for Bno055Sensor {
+    fn kind(&self) -> SensorKind {
+        SensorKind::Bno055
+    }
+
+    fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    fn read(&mut self) -> Result<OrientationReading, SensorError> {
+        let read_error = |e| SensorError::new(SensorStage::Read, format!("{e:?}"));
+        let quat = self.imu.quaternion().map_err(read_error)?;
+        let status = self.imu.get_calibration_status().map_err(read_error)?;
+
+        Ok(OrientationReading {
+            quaternion: UnitQuaternion::new_normalize(nalgebra::Quaternion::new(
+                quat.s, quat.v.x, quat.v.y, quat.v.z,
+            )),
+            calibration: CalibrationStatus {
+                system: status.sys,
+                gyroscope: status.gyr,
+                accelerometer: status.acc,
+                magnetometer: status.mag,
+            },
+        })
+    }
+
+    fn changed_calibration_profile(&mut self) -> Result<Option<Vec<u8>>, SensorError> {
+        let calib = self
+            .imu
+            .calibration_profile(&mut self.delay)
+            .map_err(|e| SensorError::new(SensorStage::Calibration, format!("{e:?}")))?;
+
+        // Nur bei Änderungen auf die SD-Karte schreiben
+        if self.saved_calib == Some(calib) {
+            return Ok(None);
+        }
+        self.saved_calib = Some(calib);
+        Ok(Some(calib.as_bytes().to_vec()))
+    }
+}
@@ -0,0 +1,307 @@
+//! Objektkatalog zum Anfahren nach Namen: Sonne, Mond und Planeten sowie eingebaut die
+//! rund 125 hellsten benannten Sterne mit ihrer Hipparcos-Nummer und die Messier- und
+//! Caldwell-Objekte mit ihren NGC/IC-Nummern. Die vollständigen Kataloge (Hipparcos bis
+//! 6 mag, NGC/IC) liegen noch nicht bei; bis dahin werden sie über `[catalog]` als Dateien
+//! im selben Format geladen.
+
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use open_pi_scope::gnss::Position;
+use serde::{Deserialize, Serialize};
+
+use crate::solar_system::Body;
+use crate::storage::storage;
+use crate::telescope_position::{EqPostion, TelescopePosition};
+
+const STARS: &str = include_str!("../catalog/stars.csv");
+const DEEP_SKY: &str = include_str!("../catalog/deep_sky.csv");
+
+static CATALOG: OnceLock<Vec<CatalogObject>> = OnceLock::new();
+
+/// `[catalog]`: zusätzliche Dateien im Format von `catalog/stars.csv` bzw.
+/// `catalog/deep_sky.csv`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CatalogConfig {
+    #[serde(default)]
+    pub stars: Vec<String>,
+    #[serde(default)]
+    pub deep_sky: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, utoipa::ToSchema)]
+pub enum ObjectKind {
+    Star,
+    DoubleStar,
+    Asterism,
+    OpenCluster,
+    GlobularCluster,
+    StarCloud,
+    EmissionNebula,
+    ReflectionNebula,
+    DarkNebula,
+    PlanetaryNebula,
+    SupernovaRemnant,
+    Galaxy,
+    Sun,
+    Moon,
+    Planet,
+}
+
+impl ObjectKind {
+    fn from_code(code: &str) -> Option<Self> {
+        Some(match code {
+            "G" => ObjectKind::Galaxy,
+            "GC" => ObjectKind::GlobularCluster,
+            "OC" => ObjectKind::OpenCluster,
+            "EN" => ObjectKind::EmissionNebula,
+            "RN" => ObjectKind::ReflectionNebula,
+            "PN" => ObjectKind::PlanetaryNebula,
+            "SNR" => ObjectKind::SupernovaRemnant,
+            "DS" => ObjectKind::DoubleStar,
+            "AST" => ObjectKind::Asterism,
+            "SC" => ObjectKind::StarCloud,
+            "DN" => ObjectKind::DarkNebula,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Location {
+    Fixed(EqPostion),
+    Body(Body),
+}
+
+#[derive(Debug, Clone)]
+struct CatalogObject {
+    /// Erster Name ist die Hauptbezeichnung
+    names: Vec<String>,
+    /// Normalisierte Namen für die Suche
+    keys: Vec<String>,
+    kind: ObjectKind,
+    magnitude: Option<f32>,
+    location: Location,
+}
+
+/// Katalogeintrag mit seinem Ort zum Zeitpunkt der Abfrage
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct CatalogEntry {
+    pub name: String,
+    /// Weitere Bezeichnungen (HIP, NGC/IC, Eigenname)
+    pub designations: Vec<String>,
+    pub kind: ObjectKind,
+    pub magnitude: Option<f32>,
+    /// Mittlerer Ort J2000.0, Rektaszension in Stunden
+    pub ra: f32,
+    pub dec: f32,
+    pub alt: f32,
+    pub az: f32,
+    pub above_horizon: bool,
+}
+
+impl CatalogEntry {
+    pub fn position(&self) -> TelescopePosition {
+        TelescopePosition::new_eq(self.ra, self.dec)
+    }
+}
+
+impl CatalogObject {
+    fn new(names: Vec<String>, kind: ObjectKind, magnitude: Option<f32>, location: Location) -> Self {
+        let keys = names.iter().map(|name| normalize(name)).collect();
+        CatalogObject {
+            names,
+            keys,
+            kind,
+            magnitude,
+            location,
+        }
+    }
+
+    fn entry(&self, site: &Position, time: DateTime<Utc>) -> CatalogEntry {
+        let eq = match self.location {
+            Location::Fixed(eq) => eq,
+            Location::Body(body) => body.position(site, time),
+        };
+        let alt_az = eq.to_alt_az(site, time);
+        CatalogEntry {
+            name: self.names[0].clone(),
+            designations: self.names[1..].to_vec(),
+            kind: self.kind,
+            magnitude: self.magnitude,
+            ra: eq.ra,
+            dec: eq.dec,
+            alt: alt_az.alt,
+            az: alt_az.az,
+            above_horizon: alt_az.alt >= 0.0,
+        }
+    }
+
+    /// 0: exakter Treffer, 1: Anfang eines Namens, 2: irgendwo im Namen
+    fn rank(&self, query: &str) -> Option<u8> {
+        self.keys
+            .iter()
+            .filter_map(|key| {
+                if key == query {
+                    Some(0)
+                } else if key.starts_with(query) {
+                    Some(1)
+                } else if key.contains(query) {
+                    Some(2)
+                } else {
+                    None
+                }
+            })
+            .min()
+    }
+}
+
+fn catalog() -> &'static [CatalogObject] {
+    CATALOG.get_or_init(builtin_catalog)
+}
+
+fn builtin_catalog() -> Vec<CatalogObject> {
+    let mut objects: Vec<CatalogObject> = Body::ALL
+        .iter()
+        .map(|body| {
+            let kind = match body {
+                Body::Sun => ObjectKind::Sun,
+                Body::Moon => ObjectKind::Moon,
+                _ => ObjectKind::Planet,
+            };
+            CatalogObject::new(vec![body.name().to_string()], kind, None, Location::Body(*body))
+        })
+        .collect();
+    objects.extend(csv_rows(STARS).filter_map(parse_star));
+    objects.extend(csv_rows(DEEP_SKY).filter_map(parse_deep_sky));
+    objects
+}
+
+/// Ergänzt den eingebauten Katalog um die Dateien aus `[catalog]`. Objekte, deren
+/// Hauptbezeichnung schon bekannt ist, werden übersprungen.
+pub(crate) async fn init_catalog() -> Result<()> {
+    let config = storage().get_catalog_config().await?;
+    let mut objects = builtin_catalog();
+    let mut known: HashSet<String> = objects.iter().flat_map(|object| object.keys.clone()).collect();
+    let files = config
+        .stars
+        .iter()
+        .map(|path| (path, parse_star as fn(Vec<&str>) -> Option<CatalogObject>))
+        .chain(config.deep_sky.iter().map(|path| (path, parse_deep_sky as _)));
+    for (path, parse) in files {
+        let data = tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("Could not read catalog file {path}"))?;
+        let before = objects.len();
+        for object in csv_rows(&data).filter_map(parse) {
+            if known.insert(object.keys[0].clone()) {
+                known.extend(object.keys[1..].iter().cloned());
+                objects.push(object);
+            }
+        }
+        println!("Loaded {} objects from {path}", objects.len() - before);
+    }
+    if CATALOG.set(objects).is_err() {
+        bail!("Catalog is already initialized");
+    }
+    Ok(())
+}
+
+/// Datenzeilen ohne Kommentare und Kopfzeile
+fn csv_rows(data: &str) -> impl Iterator<Item = Vec<&str>> {
+    data.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .skip(1)
+        .map(|line| line.split(',').map(str::trim).collect())
+}
+
+/// `hip,name,ra,dec,mag`
+fn parse_star(row: Vec<&str>) -> Option<CatalogObject> {
+    let [hip, name, ra, dec, mag] = row.as_slice() else {
+        println!("Skipping invalid star catalog row: {row:?}");
+        return None;
+    };
+    let location = Location::Fixed(EqPostion {
+        ra: parse_sexagesimal(ra)? as f32,
+        dec: parse_sexagesimal(dec)? as f32,
+    });
+    let names = vec![name.to_string(), format!("HIP {hip}")];
+    Some(CatalogObject::new(names, ObjectKind::Star, mag.parse().ok(), location))
+}
+
+/// `id,aliases,type,ra,dec,mag,name`
+fn parse_deep_sky(row: Vec<&str>) -> Option<CatalogObject> {
+    let [id, aliases, kind, ra, dec, mag, name] = row.as_slice() else {
+        println!("Skipping invalid deep sky catalog row: {row:?}");
+        return None;
+    };
+    let location = Location::Fixed(EqPostion {
+        ra: parse_sexagesimal(ra)? as f32,
+        dec: parse_sexagesimal(dec)? as f32,
+    });
+    let names = std::iter::once(*id)
+        .chain(aliases.split(';').map(str::trim))
+        .chain(std::iter::once(*name))
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect();
+    let kind = ObjectKind::from_code(kind)?;
+    Some(CatalogObject::new(names, kind, mag.parse().ok(), location))
+}
+
+/// "hh:mm[:ss.s]" bzw. "±dd:mm[:ss]"
+fn parse_sexagesimal(text: &str) -> Option<f64> {
+    let (sign, text) = match text.strip_prefix('-') {
+        Some(rest) => (-1.0, rest),
+        None => (1.0, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let mut value = 0.0;
+    for (i, part) in text.split(':').enumerate() {
+        value += part.parse::<f64>().ok()? / 60f64.powi(i as i32);
+    }
+    Some(sign * value)
+}
+
+/// Groß-/Kleinschreibung, Leer- und Satzzeichen spielen bei der Suche keine Rolle
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Sucht nach Namen und Bezeichnungen, exakte Treffer zuerst, sonst die hellsten
+pub fn search(query: &str, limit: usize, site: &Position, time: DateTime<Utc>) -> Vec<CatalogEntry> {
+    let query = normalize(query);
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let mut matches: Vec<(u8, &CatalogObject)> = catalog()
+        .iter()
+        .filter_map(|object| object.rank(&query).map(|rank| (rank, object)))
+        .collect();
+    matches.sort_by(|(rank_a, a), (rank_b, b)| {
+        let magnitude = |object: &CatalogObject| object.magnitude.unwrap_or(f32::MIN);
+        rank_a
+            .cmp(rank_b)
+            .then(magnitude(a).total_cmp(&magnitude(b)))
+    });
+    matches
+        .into_iter()
+        .take(limit)
+        .map(|(_, object)| object.entry(site, time))
+        .collect()
+}
+
+/// Findet ein Objekt über einen seiner Namen, z.B. "M31", "NGC 224" oder "Vega"
+pub fn resolve(name: &str, site: &Position, time: DateTime<Utc>) -> Option<CatalogEntry> {
+    let name = normalize(name);
+    catalog()
+        .iter()
+        .find(|object| object.keys.contains(&name))
+        .map(|object| object.entry(site, time))
+}
@@ -0,0 +1,286 @@
+use anyhow::{bail, Result};
+use atomic_struct_core::AtomicMember;
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+use crate::storage::storage;
+
+/// Zeit aus TPV, RMC oder NAV-PVT kommt erst nach der Übertragung an; so groß wird der
+/// Fehler ohne PPS angenommen, in s
+const SERIAL_TIME_ERROR: f64 = 0.5;
+/// Solange ist eine Messung die aktuelle Zeitquelle
+const SAMPLE_TIMEOUT: f64 = 10.0;
+
+fn default_max_offset() -> f64 {
+    1.0
+}
+
+fn default_holdover() -> f64 {
+    3600.0
+}
+
+/// `[time]`
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct TimeConfig {
+    /// Systemuhr stellen, wenn sie um mehr als `max_offset` abweicht (braucht CAP_SYS_TIME).
+    /// Die Feinregelung bleibt chrony bzw. ntpd überlassen.
+    #[serde(default)]
+    pub set_system_clock: bool,
+    /// Nachführung und GoTo erst, wenn die Uhr vertrauenswürdig ist
+    #[serde(default)]
+    pub require_trusted: bool,
+    /// Größte erlaubte Abweichung der Systemuhr in s
+    #[serde(default = "default_max_offset")]
+    pub max_offset: f64,
+    /// So lange bleibt die Uhr nach der letzten Messung vertrauenswürdig, in s
+    #[serde(default = "default_holdover")]
+    pub holdover: f64,
+}
+
+impl Default for TimeConfig {
+    fn default() -> Self {
+        TimeConfig {
+            set_system_clock: false,
+            require_trusted: false,
+            max_offset: default_max_offset(),
+            holdover: default_holdover(),
+        }
+    }
+}
+
+impl TimeConfig {
+    pub fn validate(&self) -> Result<()> {
+        for (name, value) in [("max_offset", self.max_offset), ("holdover", self.holdover)] {
+            if !value.is_finite() || value <= 0.0 {
+                bail!("time: {name} must be a positive number, got {value}");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeSource {
+    /// Keine aktuelle Messung
+    #[default]
+    None,
+    /// Zeit aus der Lösung des Empfängers
+    Gnss,
+    /// Sekundenimpuls über gpsd
+    Pps,
+}
+
+/// Vergleich einer Referenzzeit mit der Systemuhr
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    /// Referenz minus Systemzeit in s
+    offset: f64,
+    /// in s
+    error: f64,
+    /// Systemzeit der Messung
+    at: DateTime<Utc>,
+}
+
+impl Sample {
+    fn age(&self, now: DateTime<Utc>) -> f64 {
+        (now - self.at).as_seconds_f64()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct ClockState {
+    gnss: Option<Sample>,
+    pps: Option<Sample>,
+    clock_set: Option<DateTime<Utc>>,
+    /// Letzter Fehler beim Stellen der Systemuhr, wird nur einmal gemeldet
+    set_error: Option<String>,
+}
+
+impl ClockState {
+    /// PPS, solange es kommt, sonst die jüngste Messung
+    fn best(&self, now: DateTime<Utc>) -> Option<(TimeSource, Sample)> {
+        let pps = self.pps.map(|sample| (TimeSource::Pps, sample));
+        let gnss = self.gnss.map(|sample| (TimeSource::Gnss, sample));
+        match (pps, gnss) {
+            (Some(pps), _) if pps.1.age(now) <= SAMPLE_TIMEOUT => Some(pps),
+            (Some(pps), Some(gnss)) if pps.1.at > gnss.1.at => Some(pps),
+            (pps, gnss) => gnss.or(pps),
+        }
+    }
+}
+
+/// Antwort von GET /api/time
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct ClockStatus {
+    /// Zeitquelle der letzten Messung, `none` wenn sie älter als 10 s ist
+    pub source: TimeSource,
+    /// Referenzzeit minus Systemzeit in s
+    pub offset: Option<f64>,
+    /// Unsicherheit der Messung in s
+    pub estimated_error: Option<f64>,
+    /// Alter der Messung in s
+    pub age: Option<f64>,
+    /// Der Kernel meldet eine von NTP bzw. chrony geführte Uhr
+    pub kernel_synchronized: bool,
+    /// Abweichung höchstens `max_offset`, gemessen innerhalb von `holdover`; ohne Messung
+    /// entscheidet der Kernel
+    pub trusted: bool,
+    /// Wann die Systemuhr zuletzt gestellt wurde
+    pub clock_set: Option<DateTime<Utc>>,
+    /// Warum das Stellen der Systemuhr zuletzt fehlgeschlagen ist
+    pub set_error: Option<String>,
+    pub config: TimeConfig,
+}
+
+pub(crate) fn clock() -> &'static Clock {
+    static CLOCK: OnceLock<Clock> = OnceLock::new();
+    CLOCK.get_or_init(|| Clock {
+        config: AtomicMember::new(TimeConfig::default()),
+        state: AtomicMember::new(ClockState::default()),
+    })
+}
+
+/// Liest `[time]`
+pub(crate) async fn init_clock() -> Result<()> {
+    let config = storage().get_time_config().await?;
+    clock().config.set(config).await;
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct Clock {
+    config: AtomicMember<TimeConfig>,
+    state: AtomicMember<ClockState>,
+}
+
+impl Clock {
+    /// Zeit aus einer Lösung, empfangen zur Systemzeit `received`
+    pub async fn add_gnss_time(&self, time: DateTime<Utc>, received: DateTime<Utc>) {
+        let sample = Sample {
+            offset: (time - received).as_seconds_f64(),
+            error: SERIAL_TIME_ERROR,
+            at: received,
+        };
+        let mut state = self.state.get().await;
+        state.gnss = Some(sample);
+        self.update(state).await;
+    }
+
+    /// Sekundenimpuls: wahre Zeit und Systemzeit der Flanke, Genauigkeit in s
+    pub async fn add_pps(&self, real: DateTime<Utc>, system: DateTime<Utc>, error: f64) {
+        let sample = Sample {
+            offset: (real - system).as_seconds_f64(),
+            error,
+            at: system,
+        };
+        let mut state = self.state.get().await;
+        state.pps = Some(sample);
+        self.update(state).await;
+    }
+
+    /// Stellt bei Bedarf die Systemuhr nach der besten Messung und speichert den Zustand.
+    /// Abweichungen innerhalb der Messgenauigkeit bleiben stehen.
+    async fn update(&self, mut state: ClockState) {
+        let config = self.config.get().await;
+        let best = state.best(Utc::now()).filter(|(_, sample)| {
+            config.set_system_clock && sample.offset.abs() > config.max_offset.max(sample.error)
+        });
+        if let Some((source, sample)) = best {
+            let step = TimeDelta::nanoseconds((sample.offset * 1e9) as i64);
+            match set_system_time(Utc::now() + step) {
+                Ok(()) => {
+                    println!("System clock stepped by {:.3}s from {source:?} time", sample.offset);
+                    // Bisherige Messungen beziehen sich auf die alte Systemzeit
+                    for measured in [&mut state.gnss, &mut state.pps].into_iter().flatten() {
+                        measured.offset -= sample.offset;
+                        measured.at += step;
+                    }
+                    state.clock_set = Some(Utc::now());
+                    state.set_error = None;
+                }
+                Err(e) => {
+                    let error = e.to_string();
+                    if state.set_error.as_ref() != Some(&error) {
+                        println!("Could not set the system clock: {error}");
+                    }
+                    state.set_error = Some(error);
+                }
+            }
+        }
+        self.state.set(state).await;
+    }
+
+    pub async fn status(&self) -> ClockStatus {
+        let config = self.config.get().await;
+        let state = self.state.get().await;
+        let now = Utc::now();
+        let best = state.best(now);
+        let kernel_synchronized = kernel_synchronized();
+        let trusted = match best {
+            Some((_, sample)) if sample.age(now) <= config.holdover => {
+                sample.offset.abs() <= config.max_offset
+            }
+            _ => kernel_synchronized,
+        };
+        ClockStatus {
+            source: best
+                .filter(|(_, sample)| sample.age(now) <= SAMPLE_TIMEOUT)
+                .map_or(TimeSource::None, |(source, _)| source),
+            offset: best.map(|(_, sample)| sample.offset),
+            estimated_error: best.map(|(_, sample)| sample.error),
+            age: best.map(|(_, sample)| sample.age(now)),
+            kernel_synchronized,
+            trusted,
+            clock_set: state.clock_set,
+            set_error: state.set_error,
+            config,
+        }
+    }
+
+    /// Mit `require_trusted` nur bei vertrauenswürdiger Uhr
+    pub async fn ensure_trusted(&self) -> Result<()> {
+        if !self.config.get().await.require_trusted {
+            return Ok(());
+        }
+        let status = self.status().await;
+        if !status.trusted {
+            match status.offset {
+                Some(offset) => bail!("System clock is not trusted (offset {offset:.3}s)"),
+                None => bail!("System clock is not trusted yet, waiting for GNSS time"),
+            }
+        }
+        Ok(())
+    }
+
+    /// Systemzeit korrigiert um die letzte Messung
+    pub async fn now(&self) -> DateTime<Utc> {
+        let now = Utc::now();
+        match self.state.get().await.best(now) {
+            // Kleinere Abweichungen sind bei serieller Zeit nur die Übertragungsdauer
+            Some((_, sample)) if sample.offset.abs() > sample.error => {
+                now + TimeDelta::nanoseconds((sample.offset * 1e9) as i64)
+            }
+            _ => now,
+        }
+    }
+}
+
+fn set_system_time(time: DateTime<Utc>) -> std::io::Result<()> {
+    let spec = libc::timespec {
+        tv_sec: time.timestamp() as libc::time_t,
+        tv_nsec: time.timestamp_subsec_nanos() as libc::c_long,
+    };
+    if unsafe { libc::clock_settime(libc::CLOCK_REALTIME, &spec) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// `adjtimex` meldet `TIME_ERROR`, solange die Uhr nicht synchronisiert ist
+fn kernel_synchronized() -> bool {
+    let mut timex: libc::timex = unsafe { std::mem::zeroed() };
+    unsafe { libc::adjtimex(&mut timex) != libc::TIME_ERROR }
+}
@@ -0,0 +1,104 @@
+use anyhow::{bail, Result};
+use serde::Deserialize;
+
+use crate::telescope_position::AltAZPostion;
+
+fn default_deadband() -> f32 {
+    0.2
+}
+
+fn default_gain() -> f32 {
+    0.2
+}
+
+fn default_fault_threshold() -> f32 {
+    5.0
+}
+
+fn default_min_calibration() -> u8 {
+    2
+}
+
+/// `[mount.closed_loop]`: vergleicht die Schrittzähler laufend mit dem Lagesensor
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClosedLoopConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Kleinere Abweichungen in Grad gelten als Sensorrauschen
+    #[serde(default = "default_deadband")]
+    pub deadband: f32,
+    /// Anteil der Abweichung, der pro Zyklus korrigiert wird (0..=1)
+    #[serde(default = "default_gain")]
+    pub gain: f32,
+    /// Ab dieser Abweichung in Grad wird die Montierung angehalten
+    #[serde(default = "default_fault_threshold")]
+    pub fault_threshold: f32,
+    /// Mindestwert der Systemkalibrierung des Sensors (0..=3)
+    #[serde(default = "default_min_calibration")]
+    pub min_calibration: u8,
+}
+
+impl Default for ClosedLoopConfig {
+    fn default() -> Self {
+        ClosedLoopConfig {
+            enabled: false,
+            deadband: default_deadband(),
+            gain: default_gain(),
+            fault_threshold: default_fault_threshold(),
+            min_calibration: default_min_calibration(),
+        }
+    }
+}
+
+impl ClosedLoopConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !self.deadband.is_finite() || self.deadband < 0.0 {
+            bail!("mount.closed_loop: deadband must not be negative, got {}", self.deadband);
+        }
+        if !self.gain.is_finite() || self.gain <= 0.0 || self.gain > 1.0 {
+            bail!("mount.closed_loop: gain must be in 0..=1, got {}", self.gain);
+        }
+        if !self.fault_threshold.is_finite() || self.fault_threshold <= self.deadband {
+            bail!("mount.closed_loop: fault_threshold must be larger than deadband");
+        }
+        if self.min_calibration > 3 {
+            bail!("mount.closed_loop: min_calibration must be in 0..=3");
+        }
+        Ok(())
+    }
+
+    /// Bewertet die Abweichung Sensor − Schrittzähler
+    pub fn evaluate(&self, error: AltAZPostion) -> ClosedLoopAction {
+        if error.alt.abs() > self.fault_threshold || error.az.abs() > self.fault_threshold {
+            return ClosedLoopAction::Fault;
+        }
+        let correction = |error: f32| {
+            if error.abs() > self.deadband {
+                error * self.gain
+            } else {
+                0.0
+            }
+        };
+        ClosedLoopAction::Correct(AltAZPostion {
+            alt: correction(error.alt),
+            az: correction(error.az),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ClosedLoopAction {
+    /// Um diesen Betrag in Grad werden die Schrittzähler verschoben
+    Correct(AltAZPostion),
+    /// Schrittverlust oder rutschende Kupplung, die sich nicht mehr nachregeln lässt
+    Fault,
+}
+
+/// Abweichung Sensor − Schrittzähler, Azimut auf ±180° gefaltet
+pub fn pointing_error(sensor: AltAZPostion, stepper: AltAZPostion) -> AltAZPostion {
+    AltAZPostion {
+        alt: sensor.alt - stepper.alt,
+        az: (sensor.az - stepper.az + 180.0).rem_euclid(360.0) - 180.0,
+    }
+}
@@ -0,0 +1,30 @@
+use open_pi_scope::gnss::Position;
+use serde::Serialize;
+
+use crate::astronomy::{field_rotation_rate, horizontal_to_equatorial, parallactic_angle};
+use crate::telescope_position::AltAZPostion;
+use crate::tracking::TrackingRate;
+
+/// Drehung des Bildfelds einer azimutalen Montierung
+#[derive(Debug, Clone, Copy, Serialize, utoipa::ToSchema)]
+pub struct FieldRotation {
+    /// Parallaktischer Winkel in Grad, positiv westlich des Meridians
+    pub parallactic_angle: f32,
+    /// Änderung des parallaktischen Winkels in Grad/s bei der eingestellten Nachführrate
+    pub rate: f32,
+}
+
+impl FieldRotation {
+    /// Bildfelddrehung für einen Ort am Himmel
+    pub fn at(position: AltAZPostion, site: &Position, tracking_rate: TrackingRate) -> Self {
+        let (alt, az) = ((position.alt as f64).to_radians(), (position.az as f64).to_radians());
+        let latitude = site.latitude.to_radians();
+        let (hour_angle, dec) = horizontal_to_equatorial(alt, az, latitude);
+        // Bogensekunden/s -> Grad/s
+        let hour_angle_rate = tracking_rate.arcsec_per_second() / 3600.0;
+        FieldRotation {
+            parallactic_angle: parallactic_angle(hour_angle, dec, latitude).to_degrees() as f32,
+            rate: (field_rotation_rate(alt, az, latitude) * hour_angle_rate) as f32,
+        }
+    }
+}
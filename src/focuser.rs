@@ -0,0 +1,279 @@
+use anyhow::{bail, Context, Result};
+use atomic_struct_core::AtomicMember;
+use serde::Deserialize;
+use std::{fs, sync::OnceLock, time::Duration};
+
+use crate::motion_profile::MotionProfile;
+use crate::mount_backend::{stepper_drive, StepperDrive};
+use crate::mount_config::{check_pins, StepperConfig};
+use crate::stepper_motor::Polarity;
+use crate::storage::storage;
+
+/// So oft wird die Temperatur gelesen und die Kompensation nachgezogen
+const TEMPERATURE_INTERVAL: Duration = Duration::from_secs(10);
+
+fn default_max_speed() -> f32 {
+    500.0
+}
+
+fn default_acceleration() -> f32 {
+    1000.0
+}
+
+/// `[focuser]`: Schrittmotor am Okularauszug, alle Positionen in (Mikro-)Schritten
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FocuserConfig {
+    /// BCM-Pins des Treibers
+    pub step_pin: u8,
+    pub dir_pin: u8,
+    pub enable_pin: Option<u8>,
+    #[serde(default)]
+    pub invert_direction: bool,
+    #[serde(default)]
+    pub enable_active_high: bool,
+    /// Größte Position, 0 ist ganz eingefahren
+    pub max_step: u32,
+    /// Größter Verfahrweg pro Auftrag, ohne Angabe `max_step`
+    pub max_increment: Option<u32>,
+    /// Weg pro Schritt in µm
+    pub step_size: Option<f64>,
+    /// in Schritten/s
+    #[serde(default = "default_max_speed")]
+    pub max_speed: f32,
+    /// in Schritten/s²
+    #[serde(default = "default_acceleration")]
+    pub acceleration: f32,
+    /// Temperaturfühler: `w1_slave` eines DS18B20 oder eine sysfs-Datei in m°C
+    pub temperature_file: Option<String>,
+    /// Korrektur in Schritten pro °C, positiv fährt bei Erwärmung nach außen
+    #[serde(default)]
+    pub temperature_coefficient: f32,
+    /// Temperaturkompensation beim Start einschalten
+    #[serde(default)]
+    pub temp_comp: bool,
+    /// Letzte Position, wird nach jeder Bewegung gespeichert
+    #[serde(default)]
+    pub position: u32,
+}
+
+impl FocuserConfig {
+    pub fn pins(&self) -> Vec<u8> {
+        let mut pins = vec![self.step_pin, self.dir_pin];
+        pins.extend(self.enable_pin);
+        pins
+    }
+
+    pub fn stepper(&self) -> StepperConfig {
+        StepperConfig {
+            step_pin: self.step_pin,
+            dir_pin: self.dir_pin,
+            enable_pin: self.enable_pin,
+            polarity: Polarity {
+                invert_direction: self.invert_direction,
+                enable_active_high: self.enable_active_high,
+            },
+            steps_per_unit: 1.0,
+            max_speed: self.max_speed,
+            acceleration: self.acceleration,
+            profile: MotionProfile::Trapezoidal,
+        }
+    }
+
+    pub fn max_increment(&self) -> u32 {
+        self.max_increment.unwrap_or(self.max_step)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        check_pins("focuser", &self.pins())?;
+        if self.max_step == 0 || self.max_step > i32::MAX as u32 {
+            bail!("focuser: max_step must be in 1..={}", i32::MAX);
+        }
+        if self.max_increment() == 0 || self.max_increment() > self.max_step {
+            bail!("focuser: max_increment must be in 1..=max_step");
+        }
+        if self.position > self.max_step {
+            bail!("focuser: position {} is beyond max_step", self.position);
+        }
+        for (name, value) in [("max_speed", self.max_speed), ("acceleration", self.acceleration)] {
+            if !value.is_finite() || value <= 0.0 {
+                bail!("focuser: {name} must be a positive number, got {value}");
+            }
+        }
+        if self.step_size.is_some_and(|size| !size.is_finite() || size <= 0.0) {
+            bail!("focuser: step_size must be a positive number");
+        }
+        if !self.temperature_coefficient.is_finite() {
+            bail!("focuser: temperature_coefficient must be a number");
+        }
+        if self.temp_comp && self.temperature_file.is_none() {
+            bail!("focuser: temp_comp needs a temperature_file");
+        }
+        Ok(())
+    }
+}
+
+static FOCUSER: OnceLock<Option<Focuser>> = OnceLock::new();
+
+/// Fokussierer aus `[focuser]`, `None` wenn keiner konfiguriert ist
+pub fn focuser() -> Option<&'static Focuser> {
+    FOCUSER.get()?.as_ref()
+}
+
+/// Liest `[focuser]` und richtet den Motor auf dem Backend der Montierung ein
+pub(crate) async fn init_focuser() -> Result<()> {
+    let focuser = match storage().get_focuser_config().await? {
+        Some(config) => {
+            let kind = storage().get_backend_kind().await?;
+            let motor = stepper_drive(kind, "focuser", &config.stepper())?;
+            motor.set_position(config.position as f32);
+            Some(Focuser {
+                temp_comp: AtomicMember::new(config.temp_comp),
+                reference: AtomicMember::new(None),
+                temperature: AtomicMember::new(None),
+                config,
+                motor,
+            })
+        }
+        None => None,
+    };
+    if FOCUSER.set(focuser).is_err() {
+        bail!("Focuser is already initialized");
+    }
+    Ok(())
+}
+
+pub struct Focuser {
+    config: FocuserConfig,
+    motor: Box<dyn StepperDrive>,
+    temp_comp: AtomicMember<bool>,
+    /// Position und Temperatur, von denen die Kompensation ausgeht
+    reference: AtomicMember<Option<(i32, f32)>>,
+    /// in °C
+    temperature: AtomicMember<Option<f32>>,
+}
+
+impl Focuser {
+    pub fn config(&self) -> &FocuserConfig {
+        &self.config
+    }
+
+    pub fn position(&self) -> i32 {
+        self.motor.position().round() as i32
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.motor.is_moving()
+    }
+
+    pub async fn temperature(&self) -> Option<f32> {
+        self.temperature.get().await
+    }
+
+    pub async fn temp_comp(&self) -> bool {
+        self.temp_comp.get().await
+    }
+
+    /// Die Kompensation geht von der aktuellen Position und Temperatur aus
+    pub async fn set_temp_comp(&self, enabled: bool) -> Result<()> {
+        if enabled && self.config.temperature_file.is_none() {
+            bail!("No temperature_file configured in [focuser]");
+        }
+        self.reference.set(None).await;
+        self.temp_comp.set(enabled).await;
+        Ok(())
+    }
+
+    /// Fährt auf eine absolute Position; bei aktiver Kompensation gilt sie für die aktuelle
+    /// Temperatur
+    pub async fn move_to(&self, position: i32) -> Result<()> {
+        if position < 0 || position > self.config.max_step as i32 {
+            bail!("Focuser position {position} is outside 0..={}", self.config.max_step);
+        }
+        let increment = (position - self.position()).unsigned_abs();
+        if increment > self.config.max_increment() {
+            bail!(
+                "Focuser move of {increment} steps exceeds max_increment {}",
+                self.config.max_increment()
+            );
+        }
+        // Ohne Temperatur entfällt der alte Bezug, sonst zöge die Kompensation zurück
+        let temperature = self.temperature.get().await;
+        self.reference.set(temperature.map(|temperature| (position, temperature))).await;
+        self.motor.move_to(position as f32);
+        Ok(())
+    }
+
+    pub fn halt(&self) {
+        self.motor.stop();
+    }
+
+    /// Zieht die Position der Temperaturänderung seit dem letzten Auftrag nach
+    async fn compensate(&self, temperature: f32) {
+        if !self.temp_comp.get().await || self.is_moving() {
+            return;
+        }
+        let Some((position, reference)) = self.reference.get().await else {
+            self.reference.set(Some((self.position(), temperature))).await;
+            return;
+        };
+        let offset = (self.config.temperature_coefficient * (temperature - reference)).round();
+        let target = (position + offset as i32).clamp(0, self.config.max_step as i32);
+        if target != self.position() {
+            self.motor.move_to(target as f32);
+        }
+    }
+}
+
+/// Liest die Temperatur, führt die Kompensation nach und speichert die Position nach
+/// jeder Bewegung
+pub(crate) async fn run_focuser() -> Result<()> {
+    let Some(focuser) = focuser() else {
+        return Ok(());
+    };
+    let mut saved_position = focuser.position();
+    loop {
+        tokio::time::sleep(TEMPERATURE_INTERVAL).await;
+
+        if let Some(path) = focuser.config.temperature_file.clone() {
+            // Ein DS18B20 braucht für die Wandlung fast eine Sekunde
+            match tokio::task::spawn_blocking(move || read_temperature(&path)).await? {
+                Ok(temperature) => {
+                    focuser.temperature.set(Some(temperature)).await;
+                    focuser.compensate(temperature).await;
+                }
+                Err(e) => {
+                    println!("Could not read focuser temperature: {e:#}");
+                    focuser.temperature.set(None).await;
+                }
+            }
+        }
+
+        let position = focuser.position();
+        if !focuser.is_moving() && position != saved_position {
+            if let Err(e) = storage().set_focuser_position(position as u32).await {
+                println!("Could not save focuser position: {e}");
+            }
+            saved_position = position;
+        }
+    }
+}
+
+/// Temperatur in °C aus `w1_slave` ("… YES" / "… t=21562") oder einer Datei in m°C
+fn read_temperature(path: &str) -> Result<f32> {
+    let text = fs::read_to_string(path).with_context(|| format!("Could not read {path}"))?;
+    let millidegrees = match text.rsplit_once("t=") {
+        Some((head, value)) => {
+            if !head.contains("YES") {
+                bail!("CRC check of {path} failed");
+            }
+            value
+        }
+        None => text.as_str(),
+    };
+    let millidegrees: f32 = millidegrees
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid temperature in {path}"))?;
+    Ok(millidegrees / 1000.0)
+}
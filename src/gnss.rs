@@ -1,41 +1,68 @@
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
-#[atomic_struct::atomic_struct]
-#[derive(Deserialize, Debug, Default, Serialize, Clone, utoipa::ToSchema)]
-pub struct GnssData {
-    #[schema(value_type = f64)]
-    pub lat: f64,
-    #[schema(value_type = f64)]
-    pub lon: f64,
-    #[schema(value_type = f32)]
-    pub alt: f32,
-    #[schema(value_type = i32)]
-    pub leap_seconds: i32,
-    #[schema(value_type = f32)]
-    pub estimated_error_longitude: f32,
-    #[schema(value_type = f32)]
-    pub estimated_error_latitude: f32,
-    #[schema(value_type = f32)]
-    pub estimated_error_plane: f32,
-    #[schema(value_type = f32)]
-    pub estimated_error_altitude: f32,
-    #[schema(value_type = f32)]
-    pub track: f32,
-    #[schema(value_type = f32)]
-    pub speed: f32,
-    #[schema(value_type = f32)]
-    pub climb: f32,
-    #[schema(value_type = Mode)]
-    pub mode: Mode,
-    #[schema(value_type = f32)]
-    pub estimated_error_track: f32,
-    #[schema(value_type = f32)]
-    pub estimated_error_speed: f32,
-    #[schema(value_type = f32)]
-    pub estimated_error_climb: f32,
-    #[schema(value_type = Vec<Satellite>)]
-    pub satellites: Vec<Satellite>,
+pub use data::GnssData;
+
+/// Der von atomic_struct erzeugte Konstruktor hat einen Parameter pro Feld
+#[allow(clippy::too_many_arguments)]
+mod data {
+    use super::{ConnectionState, Mode, Satellite};
+    use serde::{Deserialize, Serialize};
+
+    #[atomic_struct::atomic_struct]
+    #[derive(Deserialize, Debug, Default, Serialize, Clone, utoipa::ToSchema)]
+    pub struct GnssData {
+        #[schema(value_type = f64)]
+        pub lat: f64,
+        #[schema(value_type = f64)]
+        pub lon: f64,
+        #[schema(value_type = f32)]
+        pub alt: f32,
+        #[schema(value_type = i32)]
+        pub leap_seconds: i32,
+        #[schema(value_type = f32)]
+        pub estimated_error_longitude: f32,
+        #[schema(value_type = f32)]
+        pub estimated_error_latitude: f32,
+        #[schema(value_type = f32)]
+        pub estimated_error_plane: f32,
+        #[schema(value_type = f32)]
+        pub estimated_error_altitude: f32,
+        #[schema(value_type = f32)]
+        pub track: f32,
+        #[schema(value_type = f32)]
+        pub speed: f32,
+        #[schema(value_type = f32)]
+        pub climb: f32,
+        #[schema(value_type = Mode)]
+        pub mode: Mode,
+        #[schema(value_type = f32)]
+        pub estimated_error_track: f32,
+        #[schema(value_type = f32)]
+        pub estimated_error_speed: f32,
+        #[schema(value_type = f32)]
+        pub estimated_error_climb: f32,
+        #[schema(value_type = Vec<Satellite>)]
+        pub satellites: Vec<Satellite>,
+        #[schema(value_type = ConnectionState)]
+        pub connection: ConnectionState,
+    }
+}
+
+/// Verbindung zur GNSS-Quelle
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "state")]
+pub enum ConnectionState {
+    #[default]
+    Connecting,
+    Connected,
+    /// Letzter Fehler, der nächste Versuch folgt nach `retry_in` Sekunden
+    Disconnected {
+        error: String,
+        /// Fehlgeschlagene Versuche seit der letzten Verbindung
+        attempts: u32,
+        retry_in: f32,
+    },
 }
 
 #[derive(
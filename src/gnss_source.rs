@@ -0,0 +1,240 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use open_pi_scope::gnss::{ConnectionState, Mode, Satellite};
+use rppal::uart::{Parity, Uart};
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+use crate::gpsd::read_gpsd;
+use crate::nmea::NmeaParser;
+use crate::storage::Storage;
+use crate::ubx::UbxParser;
+
+/// Wartezeit nach dem ersten Fehlschlag, verdoppelt sich bis `MAX_BACKOFF`
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// Ein `read` auf der seriellen Schnittstelle kehrt spätestens nach dieser Zeit zurück
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+/// Kommt so lange nichts vom Empfänger, wird die Schnittstelle neu geöffnet
+const DATA_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn default_host() -> String {
+    "127.0.0.1".to_owned()
+}
+
+fn default_port() -> u16 {
+    2947
+}
+
+fn default_device() -> String {
+    "/dev/serial0".to_owned()
+}
+
+fn default_baud_rate() -> u32 {
+    9600
+}
+
+/// Woher Position und Satelliten kommen, `[gnss] source`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GnssSource {
+    #[default]
+    Gpsd,
+    /// NMEA 0183 direkt von der seriellen Schnittstelle
+    Nmea,
+    /// u-blox UBX direkt von der seriellen Schnittstelle
+    Ubx,
+}
+
+/// `[gnss]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GnssConfig {
+    #[serde(default)]
+    pub source: GnssSource,
+    /// Adresse von gpsd
+    #[serde(default = "default_host")]
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    /// Serielle Schnittstelle des Empfängers für `nmea` und `ubx`
+    #[serde(default = "default_device")]
+    pub device: String,
+    #[serde(default = "default_baud_rate")]
+    pub baud_rate: u32,
+}
+
+impl Default for GnssConfig {
+    fn default() -> Self {
+        GnssConfig {
+            source: GnssSource::default(),
+            host: default_host(),
+            port: default_port(),
+            device: default_device(),
+            baud_rate: default_baud_rate(),
+        }
+    }
+}
+
+impl GnssConfig {
+    /// Für Log-Meldungen
+    fn describe(&self) -> String {
+        match self.source {
+            GnssSource::Gpsd => format!("gpsd at {}:{}", self.host, self.port),
+            GnssSource::Nmea => format!("NMEA receiver at {}", self.device),
+            GnssSource::Ubx => format!("u-blox receiver at {}", self.device),
+        }
+    }
+}
+
+/// Lösung eines Empfängers unabhängig vom Protokoll. `None` lässt den bisherigen Wert
+/// in `GnssData` stehen.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GnssFix {
+    pub mode: Option<Mode>,
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
+    /// über dem Meeresspiegel, in m
+    pub alt: Option<f32>,
+    pub leap_seconds: Option<i32>,
+    /// Geschätzte Fehler in m, m/s bzw. Grad
+    pub estimated_error_latitude: Option<f32>,
+    pub estimated_error_longitude: Option<f32>,
+    pub estimated_error_plane: Option<f32>,
+    pub estimated_error_altitude: Option<f32>,
+    pub estimated_error_speed: Option<f32>,
+    pub estimated_error_track: Option<f32>,
+    pub estimated_error_climb: Option<f32>,
+    /// in m/s
+    pub speed: Option<f32>,
+    /// Kurs über Grund in Grad
+    pub track: Option<f32>,
+    /// in m/s
+    pub climb: Option<f32>,
+    /// UTC-Zeit der Lösung
+    pub time: Option<DateTime<Utc>>,
+    /// Vollständige Lösung einer Epoche mit deren eigenen Fehlern (GGA, TPV, NAV-PVT).
+    /// Nur sie geht in den gemittelten Standort ein.
+    pub epoch: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GnssUpdate {
+    Fix(GnssFix),
+    /// Alle Satelliten in Sicht
+    Satellites(Vec<Satellite>),
+}
+
+/// Zerlegt den Datenstrom eines direkt angeschlossenen Empfängers
+pub trait GnssParser: Send {
+    fn feed(&mut self, data: &[u8], updates: &mut Vec<GnssUpdate>);
+    /// Wird nach dem Öffnen der Schnittstelle an den Empfänger geschickt
+    fn init_commands(&self) -> Vec<Vec<u8>> {
+        Vec::new()
+    }
+}
+
+/// Hält die Verbindung zur GNSS-Quelle. Ist sie beim Start noch nicht da oder bricht ab,
+/// wird mit wachsendem Abstand erneut verbunden.
+pub(crate) async fn handle_gnss(storage: &Storage) -> Result<()> {
+    let config = storage.get_gnss_config().await?;
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempts = 0;
+    loop {
+        storage
+            .gnss_data
+            .set_connection(ConnectionState::Connecting)
+            .await;
+        let result = match config.source {
+            GnssSource::Gpsd => read_gpsd(storage, &config).await,
+            GnssSource::Nmea => read_serial(storage, &config, Box::new(NmeaParser::default())).await,
+            GnssSource::Ubx => read_serial(storage, &config, Box::new(UbxParser::default())).await,
+        };
+        let error = match result {
+            Ok(()) => "connection closed".to_owned(),
+            Err(e) => format!("{e:#}"),
+        };
+        // Nach einer zustande gekommenen Verbindung wieder von vorn
+        if storage.gnss_data.get_connection().await == ConnectionState::Connected {
+            backoff = INITIAL_BACKOFF;
+            attempts = 0;
+        }
+        attempts += 1;
+        println!(
+            "{}: {error}, retrying in {}s",
+            config.describe(),
+            backoff.as_secs()
+        );
+        storage.gnss_data.set_mode(Mode::NoFix).await;
+        storage
+            .gnss_data
+            .set_connection(ConnectionState::Disconnected {
+                error,
+                attempts,
+                retry_in: backoff.as_secs_f32(),
+            })
+            .await;
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+/// Liest den Empfänger in einem eigenen Thread; verbunden ist er ab der ersten Lösung
+async fn read_serial(
+    storage: &Storage,
+    config: &GnssConfig,
+    parser: Box<dyn GnssParser>,
+) -> Result<()> {
+    let (sender, mut receiver) = unbounded_channel();
+    let (device, baud_rate) = (config.device.clone(), config.baud_rate);
+    let reader = tokio::task::spawn_blocking(move || read_port(&device, baud_rate, parser, sender));
+    while let Some(update) = receiver.recv().await {
+        if storage.gnss_data.get_connection().await != ConnectionState::Connected {
+            println!("Receiving from {}", config.describe());
+            storage
+                .gnss_data
+                .set_connection(ConnectionState::Connected)
+                .await;
+        }
+        storage.update_gnss(update).await;
+    }
+    reader.await?
+}
+
+/// Blockiert, bis die Schnittstelle einen Fehler meldet oder keine Daten mehr kommen
+fn read_port(
+    device: &str,
+    baud_rate: u32,
+    mut parser: Box<dyn GnssParser>,
+    sender: UnboundedSender<GnssUpdate>,
+) -> Result<()> {
+    let mut uart = Uart::with_path(device, baud_rate, Parity::None, 8, 1)
+        .with_context(|| format!("Could not open {device}"))?;
+    uart.set_read_mode(0, READ_TIMEOUT)?;
+    uart.set_write_mode(true)?;
+    for command in parser.init_commands() {
+        uart.write(&command)?;
+    }
+
+    let mut buffer = [0u8; 512];
+    let mut updates = Vec::new();
+    let mut last_data = Instant::now();
+    loop {
+        let count = uart.read(&mut buffer)?;
+        if count == 0 {
+            if last_data.elapsed() >= DATA_TIMEOUT {
+                bail!("No data for {}s", DATA_TIMEOUT.as_secs());
+            }
+            continue;
+        }
+        last_data = Instant::now();
+        parser.feed(&buffer[..count], &mut updates);
+        for update in updates.drain(..) {
+            // Der Empfänger auf der async-Seite ist weg
+            if sender.send(update).is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use futures::prelude::*;
+use open_pi_scope::gnss::ConnectionState;
+use tokio::net::TcpStream;
+use tokio_util::codec::{Framed, LinesCodec};
+
+use crate::gnss_source::GnssConfig;
+use crate::storage::Storage;
+
+/// Wie `gpsd_proto::ENABLE_WATCH_CMD`, zusätzlich mit PPS-Meldungen
+const WATCH_CMD: &str = "?WATCH={\"enable\":true,\"json\":true,\"pps\":true};\r\n";
+
+/// Verbindet, meldet sich für Positionsdaten an und liest bis zum Verbindungsende
+pub(crate) async fn read_gpsd(storage: &Storage, config: &GnssConfig) -> anyhow::Result<()> {
+    let stream = TcpStream::connect((config.host.as_str(), config.port)).await?;
+    let mut framed = Framed::new(stream, LinesCodec::new());
+    framed.send(WATCH_CMD).await?;
+    println!("Connected to gpsd at {}:{}", config.host, config.port);
+    storage
+        .gnss_data
+        .set_connection(ConnectionState::Connected)
+        .await;
+    framed.try_for_each(|line| storage.update_gpsd(line)).await?;
+    Ok(())
+}
@@ -0,0 +1,306 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::telescope_position::AltAZPostion;
+
+fn default_min_altitude() -> f32 {
+    0.0
+}
+
+fn default_max_altitude() -> f32 {
+    90.0
+}
+
+/// Verhalten bei einem Ziel außerhalb der Grenzen
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LimitAction {
+    /// Ziel ablehnen bzw. die Nachführung beenden
+    #[default]
+    Reject,
+    /// Höhe auf die Grenze begrenzen
+    Clip,
+}
+
+/// `[mount.limits]`: erlaubter Bereich, damit der Tubus nicht gegen das Stativ fährt
+/// und sich die Kabel nicht um die Azimutachse wickeln
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct LimitsConfig {
+    /// in Grad
+    #[serde(default = "default_min_altitude")]
+    pub min_altitude: f32,
+    #[serde(default = "default_max_altitude")]
+    pub max_altitude: f32,
+    /// Horizontprofil als Punkte [Azimut, Höhe] in Grad
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub horizon: Vec<[f32; 2]>,
+    /// CSV- oder Stellarium-Horizontdatei, wird beim Start statt `horizon` eingelesen
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub horizon_file: Option<String>,
+    /// Grenzen der aufsummierten Azimut-Achsstellung in Grad, ohne Angabe unbegrenzt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub az_min: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub az_max: Option<f32>,
+    #[serde(default)]
+    pub action: LimitAction,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        LimitsConfig {
+            min_altitude: default_min_altitude(),
+            max_altitude: default_max_altitude(),
+            horizon: Vec::new(),
+            horizon_file: None,
+            az_min: None,
+            az_max: None,
+            action: LimitAction::default(),
+        }
+    }
+}
+
+impl LimitsConfig {
+    pub fn validate(&self) -> Result<()> {
+        for (name, value) in [("min_altitude", self.min_altitude), ("max_altitude", self.max_altitude)] {
+            if !value.is_finite() || !(-90.0..=90.0).contains(&value) {
+                bail!("mount.limits: {name} must be in -90..=90, got {value}");
+            }
+        }
+        if self.min_altitude >= self.max_altitude {
+            bail!("mount.limits: min_altitude must be smaller than max_altitude");
+        }
+        if self.horizon_file.is_some() && !self.horizon.is_empty() {
+            bail!("mount.limits: set either horizon or horizon_file, not both");
+        }
+        for [az, alt] in &self.horizon {
+            if !az.is_finite() || !alt.is_finite() || !(-90.0..=90.0).contains(alt) {
+                bail!("mount.limits: invalid horizon point [{az}, {alt}]");
+            }
+        }
+        match (self.az_min, self.az_max) {
+            (None, None) => {}
+            (Some(min), Some(max)) => {
+                if !min.is_finite() || !max.is_finite() || max - min < 360.0 {
+                    bail!("mount.limits: az_max - az_min must cover at least 360°");
+                }
+            }
+            _ => bail!("mount.limits: az_min and az_max must be set together"),
+        }
+        Ok(())
+    }
+
+    /// Niedrigste erlaubte Höhe in Richtung `az`, aus Mindesthöhe und Horizontprofil
+    pub fn min_altitude_at(&self, az: f32) -> f32 {
+        self.min_altitude.max(horizon_altitude(&self.horizon, az))
+    }
+
+    /// Prüft ein Ziel am Himmel, bei `LimitAction::Clip` wird die Höhe auf die Grenze gesetzt
+    pub fn apply(&self, target: AltAZPostion) -> Result<AltAZPostion> {
+        let min = self.min_altitude_at(target.az);
+        let alt = target.alt.clamp(min, self.max_altitude);
+        if alt == target.alt {
+            return Ok(target);
+        }
+        match self.action {
+            LimitAction::Clip => Ok(AltAZPostion { alt, az: target.az }),
+            LimitAction::Reject if target.alt < min => bail!(
+                "Target altitude {:.1}° is below the limit of {min:.1}° at azimuth {:.1}°",
+                target.alt,
+                target.az
+            ),
+            LimitAction::Reject => bail!(
+                "Target altitude {:.1}° is above the limit of {:.1}°",
+                target.alt,
+                self.max_altitude
+            ),
+        }
+    }
+
+    /// Prüft Park- und Home-Position: Sie sind feste Achsstellungen und dürfen unter dem
+    /// Horizontprofil liegen, aber nicht außerhalb des mechanischen Höhenbereichs
+    pub fn check_altitude_range(&self, target: AltAZPostion) -> Result<()> {
+        if !(self.min_altitude..=self.max_altitude).contains(&target.alt) {
+            bail!(
+                "Altitude {:.1}° is outside the mount range of {:.1}°..{:.1}°",
+                target.alt,
+                self.min_altitude,
+                self.max_altitude
+            );
+        }
+        Ok(())
+    }
+
+    /// Azimut-Achsstellung für `az`, die von `current` aus auf dem kürzesten erlaubten
+    /// Weg erreicht wird
+    pub fn wrap_azimuth(&self, current: f32, az: f32) -> Result<f32> {
+        let nearest = current + (az - current + 180.0).rem_euclid(360.0) - 180.0;
+        let (Some(min), Some(max)) = (self.az_min, self.az_max) else {
+            return Ok(nearest);
+        };
+        // Der andere Weg herum ist höchstens eine Umdrehung weiter
+        [nearest, nearest - 360.0, nearest + 360.0]
+            .into_iter()
+            .filter(|candidate| (min..=max).contains(candidate))
+            .min_by(|a, b| (a - current).abs().total_cmp(&(b - current).abs()))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Azimuth {az:.1}° cannot be reached within the cable wrap limits {min}°..{max}°"
+                )
+            })
+    }
+
+    /// Wie `wrap_azimuth`, aber in Drehrichtung `direction` (±1), solange die Kabelgrenzen es
+    /// erlauben. Bei einem Zenitdurchgang eilt das Ziel der Achse um bis zu 180° voraus.
+    pub fn wrap_azimuth_towards(&self, current: f32, az: f32, direction: f32) -> Result<f32> {
+        let ahead = ((az - current) * direction).rem_euclid(360.0);
+        // Knapp hinter der Achse nicht eine ganze Umdrehung nachlaufen
+        let ahead = if ahead > 270.0 { ahead - 360.0 } else { ahead };
+        let candidate = current + ahead * direction;
+        match (self.az_min, self.az_max) {
+            (Some(min), Some(max)) if !(min..=max).contains(&candidate) => {
+                self.wrap_azimuth(current, az)
+            }
+            _ => Ok(candidate),
+        }
+    }
+}
+
+/// Höhe des Horizontprofils, zwischen den Punkten linear interpoliert
+fn horizon_altitude(horizon: &[[f32; 2]], az: f32) -> f32 {
+    let mut points: Vec<[f32; 2]> = horizon
+        .iter()
+        .map(|[az, alt]| [az.rem_euclid(360.0), *alt])
+        .collect();
+    points.sort_by(|a, b| a[0].total_cmp(&b[0]));
+    let (Some(first), Some(last)) = (points.first().copied(), points.last().copied()) else {
+        return f32::NEG_INFINITY;
+    };
+    let az = az.rem_euclid(360.0);
+    // Über Nord hinweg vom letzten zum ersten Punkt
+    let (lower, upper) = match points.iter().position(|point| point[0] > az) {
+        Some(0) | None => (
+            [last[0] - if az < first[0] { 360.0 } else { 0.0 }, last[1]],
+            [first[0] + if az >= last[0] { 360.0 } else { 0.0 }, first[1]],
+        ),
+        Some(i) => (points[i - 1], points[i]),
+    };
+    let span = upper[0] - lower[0];
+    if span <= f32::EPSILON {
+        return lower[1].max(upper[1]);
+    }
+    lower[1] + (upper[1] - lower[1]) * (az - lower[0]) / span
+}
+
+/// Liest ein Horizontprofil aus einer CSV-Datei ("az,alt") oder einer Stellarium-
+/// Horizontdatei ("az alt"). Kommentare beginnen mit '#' oder ';', vor dem ersten Punkt
+/// darf ein Spaltenkopf ohne Zahlen stehen. Jede andere Zeile muss zwei Zahlen enthalten.
+pub fn parse_horizon(text: &str) -> Result<Vec<[f32; 2]>> {
+    let mut points = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split(['#', ';']).next().unwrap_or_default().trim();
+        let fields: Vec<&str> = line
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|field| !field.is_empty())
+            .collect();
+        if fields.is_empty() {
+            continue;
+        }
+        let numbers: Vec<Option<f32>> = fields.iter().map(|field| field.parse().ok()).collect();
+        if points.is_empty() && numbers.iter().all(Option::is_none) {
+            continue;
+        }
+        let [Some(az), Some(alt), ..] = numbers[..] else {
+            bail!(
+                "Horizon line {}: expected azimuth and altitude, got \"{line}\"",
+                number + 1
+            );
+        };
+        points.push([az, alt]);
+    }
+    if points.is_empty() {
+        bail!("Horizon file contains no points");
+    }
+    Ok(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wrapped(az_min: f32, az_max: f32) -> LimitsConfig {
+        LimitsConfig {
+            az_min: Some(az_min),
+            az_max: Some(az_max),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parse_csv_horizon() {
+        let text = "azimuth,altitude\n# Dach im Süden\n0,5\n90, 10.5 ; Baum\n\n180,25\n";
+        let horizon = parse_horizon(text).unwrap();
+        assert_eq!(horizon, vec![[0.0, 5.0], [90.0, 10.5], [180.0, 25.0]]);
+    }
+
+    #[test]
+    fn parse_stellarium_horizon() {
+        let text = "; Stellarium polygonal horizon\n0 3.2\n120 8\t\n240 4.5\n";
+        let horizon = parse_horizon(text).unwrap();
+        assert_eq!(horizon, vec![[0.0, 3.2], [120.0, 8.0], [240.0, 4.5]]);
+    }
+
+    #[test]
+    fn parse_horizon_rejects_bad_lines() {
+        let error = parse_horizon("0,5\n90,ten\n180,25\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Horizon line 2: expected azimuth and altitude, got \"90,ten\""
+        );
+        // Eine Kopfzeile ist nur vor dem ersten Punkt erlaubt
+        let error = parse_horizon("az,alt\n0,5\naz,alt\n").unwrap_err();
+        assert!(error.to_string().starts_with("Horizon line 3:"), "{error}");
+        assert!(parse_horizon("0\n").is_err());
+        assert!(parse_horizon("# nur Kommentare\n\n").is_err());
+    }
+
+    #[test]
+    fn horizon_altitude_interpolates_across_north() {
+        let limits = LimitsConfig {
+            horizon: vec![[90.0, 10.0], [270.0, 30.0], [350.0, 20.0], [10.0, 0.0]],
+            ..Default::default()
+        };
+        assert_eq!(limits.min_altitude_at(180.0), 20.0);
+        assert_eq!(limits.min_altitude_at(0.0), 10.0);
+        assert_eq!(limits.min_altitude_at(-10.0), 20.0);
+        assert_eq!(limits.min_altitude_at(50.0), 5.0);
+    }
+
+    #[test]
+    fn wrap_azimuth_takes_the_short_way() {
+        let limits = LimitsConfig::default();
+        assert_eq!(limits.wrap_azimuth(350.0, 10.0).unwrap(), 370.0);
+        assert_eq!(limits.wrap_azimuth(10.0, 350.0).unwrap(), -10.0);
+
+        // Die Kabelgrenze erzwingt den langen Weg
+        let limits = wrapped(-180.0, 270.0);
+        assert_eq!(limits.wrap_azimuth(260.0, 300.0).unwrap(), -60.0);
+        assert!(wrapped(0.0, 360.0).wrap_azimuth(0.0, 360.0).is_ok());
+    }
+
+    #[test]
+    fn wrap_azimuth_towards_follows_the_direction() {
+        let limits = LimitsConfig::default();
+        // Bei einem Zenitdurchgang liegt das Ziel 170° voraus, entgegen dem kürzeren Weg
+        assert_eq!(limits.wrap_azimuth_towards(100.0, 270.0, 1.0).unwrap(), 270.0);
+        assert_eq!(limits.wrap_azimuth_towards(100.0, 270.0, -1.0).unwrap(), -90.0);
+        // Knapp hinter der Achse nicht eine ganze Umdrehung nachlaufen
+        assert_eq!(limits.wrap_azimuth_towards(100.0, 95.0, 1.0).unwrap(), 95.0);
+
+        // Geht die Drehrichtung über die Kabelgrenze, gilt der kürzeste erlaubte Weg
+        let limits = wrapped(-90.0, 270.0);
+        assert_eq!(limits.wrap_azimuth_towards(200.0, 300.0, 1.0).unwrap(), -60.0);
+        assert_eq!(limits.wrap_azimuth_towards(0.0, 190.0, 1.0).unwrap(), 190.0);
+    }
+}
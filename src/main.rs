@@ -1,12 +1,9 @@
-use futures::{join, prelude::*};
-
-use nalgebra::UnitQuaternion;
+use futures::join;
 
 use open_pi_scope::{Broadcast, MAGIC_NUMBER};
-use rppal::i2c::I2c;
-use std::{error::Error, net::SocketAddr, time::Duration};
-use tokio::net::{TcpStream, UdpSocket};
-use tokio_util::codec::{Framed, LinesCodec};
+use std::{error::Error, time::Duration};
+use tokio::net::UdpSocket;
+use tokio::signal::unix::{signal, SignalKind};
 
 pub(crate) mod helpers;
 
@@ -18,68 +15,46 @@ async fn main() -> Result<(), Box<dyn Error>> {
     println!("Starting");
     let store = storage::storage();
     store.load_config().await?;
-
-    let _res = join!(
-        handle_gnss(),
-        api::handle_web(),
-        handle_broadcasting(store),
-        handle_i2c(store),
-        alpaca::handle_alpaca(store),
-        alt_az_driver::run_alt_az_driver()
-    );
-    Ok(())
-}
-
-mod api;
-
-async fn handle_gnss() -> anyhow::Result<()> {
-    let addr: SocketAddr = "127.0.0.1:2947".parse().unwrap();
-    let stream = TcpStream::connect(&addr).await?;
-    let mut framed: Framed<TcpStream, LinesCodec> = Framed::new(stream, LinesCodec::new());
-    framed.send(gpsd_proto::ENABLE_WATCH_CMD).await?;
-    framed.try_for_each(|line| storage::storage().update_gpsd(line)).await?;
+    astronomy::init_astronomy().await?;
+    clock::init_clock().await?;
+    alt_az_driver::init_backend().await?;
+    catalog::init_catalog().await?;
+    focuser::init_focuser().await?;
+    switch::init_switches().await?;
+
+    tokio::select! {
+        _res = async {
+            join!(
+                gnss_source::handle_gnss(store),
+                api::handle_web(),
+                handle_broadcasting(store),
+                sensor::handle_orientation_sensor(store),
+                alpaca::handle_alpaca(store),
+                alt_az_driver::run_alt_az_driver(),
+                alt_az_driver::handle_estop_button(),
+                focuser::run_focuser()
+            )
+        } => {}
+        result = shutdown_signal() => {
+            result?;
+            println!("Shutting down");
+        }
+    }
+    alt_az_driver::shutdown().await;
     Ok(())
 }
 
-async fn handle_i2c(storage: &storage::Storage) -> anyhow::Result<()> {
-    let i2c = I2c::with_bus(8).unwrap();
-
-    let mut imu = bno055::Bno055::new(i2c);
-    let mut delay = linux_embedded_hal::Delay;
-
-    imu.init(&mut delay)?;
-
-    // Enable 9-degrees-of-freedom sensor fusion mode with fast magnetometer calibration
-    imu.set_mode(bno055::BNO055OperationMode::NDOF, &mut delay)?;
-
-    let calib = storage.get_bno055_calib().await;
-
-    if let Some(calib) = calib {
-        imu.set_calibration_profile(calib, &mut delay)?;
-    }
-
-    loop {
-        let quat = imu.quaternion()?;
-        let dec = storage.magnetic_data.get_declination().await.to_radians();
-        // Rotation um Z-Achse
-        let declination_rotation =
-            UnitQuaternion::from_axis_angle(&nalgebra::Vector3::z_axis(), dec);
-
-        let quat = UnitQuaternion::new_normalize(nalgebra::Quaternion::new(
-            quat.s, quat.v.x, quat.v.y, quat.v.z,
-        ));
-        let quat = quat * declination_rotation;
-        dbg!(&quat);
-        storage.update_orientation(quat).await;
-
-        let calib = imu.calibration_profile(&mut delay)?;
-
-        storage.set_bno055_calib(calib).await?;
-        
-        tokio::time::sleep(Duration::from_secs(1)).await;
+/// SIGTERM von systemd oder Strg+C
+async fn shutdown_signal() -> std::io::Result<()> {
+    let mut terminate = signal(SignalKind::terminate())?;
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result,
+        _ = terminate.recv() => Ok(()),
     }
 }
 
+mod api;
+
 async fn handle_broadcasting(_storage: &storage::Storage) -> anyhow::Result<()> {
     let socket = UdpSocket::bind("0.0.0.0:0").await?; // ausgehend, beliebiger Port
     socket.set_broadcast(true)?;
@@ -132,8 +107,33 @@ impl OpenPiScopeServer for Rpc {
     }
 }
  */
+mod ahrs;
 mod alpaca;
 mod alt_az_driver;
+mod astronomy;
+mod bno055_sensor;
+mod catalog;
+mod clock;
+mod closed_loop;
+mod field_rotation;
+mod focuser;
+mod gnss_source;
+mod gpsd;
+mod limits;
+mod motion_profile;
+mod mount_backend;
+mod mount_config;
+mod mpu9250;
+mod nmea;
+mod pointing_model;
+mod sensor;
+mod site;
+mod site_estimator;
+mod solar_system;
 mod stepper_axis;
 mod stepper_motor;
+mod switch;
 pub(crate) mod telescope_position;
+mod tracking;
+mod ubx;
+mod zenith_pass;
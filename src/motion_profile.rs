@@ -0,0 +1,437 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Die schrittweise Integration der S-Kurve bremst etwas später als gerechnet,
+/// daher beginnt das Bremsen um so viele Schritte früher
+const S_CURVE_BRAKING_MARGIN: f32 = 2.0;
+
+/// Form der Beschleunigungsrampe
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum MotionProfile {
+    /// Konstante Beschleunigung bis zur Maximalgeschwindigkeit
+    #[default]
+    Trapezoidal,
+    /// Beschleunigung steigt mit begrenztem Ruck (Schritte/s³) an und ab
+    SCurve { jerk: f32 },
+}
+
+/// Bewegungsphase eines Motors
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, utoipa::ToSchema,
+)]
+#[repr(u8)]
+pub enum MotionPhase {
+    #[default]
+    Idle = 0,
+    Accelerating = 1,
+    Cruising = 2,
+    Decelerating = 3,
+}
+
+impl From<u8> for MotionPhase {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => MotionPhase::Accelerating,
+            2 => MotionPhase::Cruising,
+            3 => MotionPhase::Decelerating,
+            _ => MotionPhase::Idle,
+        }
+    }
+}
+
+/// Ziel des Planers für den nächsten Schritt
+#[derive(Debug, Clone, Copy)]
+pub enum Goal {
+    /// Verbleibende Schritte bis zum Ziel (mit Vorzeichen)
+    Position(i32),
+    /// Zielgeschwindigkeit in Schritten/s (mit Vorzeichen)
+    Velocity(f32),
+}
+
+/// Plant Schritt für Schritt den Abstand zum nächsten Puls aus Maximalgeschwindigkeit
+/// und Beschleunigung. Da der Zustand nur aus der aktuellen Geschwindigkeit besteht,
+/// kann sich das Ziel jederzeit ändern; der Planer bremst bei Bedarf ab und kehrt um.
+#[derive(Debug, Clone, Default)]
+pub struct MotionPlanner {
+    /// Schritte/s mit Vorzeichen
+    velocity: f32,
+    /// Schritte/s² in Bewegungsrichtung (nur S-Kurve)
+    acceleration: f32,
+    phase: MotionPhase,
+}
+
+impl MotionPlanner {
+    pub fn velocity(&self) -> f32 {
+        self.velocity
+    }
+
+    pub fn phase(&self) -> MotionPhase {
+        self.phase
+    }
+
+    /// Sofortiger Stillstand, z. B. nach dem Abschalten des Treibers
+    pub fn reset(&mut self) {
+        self.velocity = 0.0;
+        self.acceleration = 0.0;
+        self.phase = MotionPhase::Idle;
+    }
+
+    /// Richtung und Abstand zum vorherigen Schritt für den nächsten Schritt,
+    /// `None` wenn der Motor steht und nichts zu tun ist
+    pub fn next_step(
+        &mut self,
+        goal: Goal,
+        profile: MotionProfile,
+        max_speed: f32,
+        acceleration: f32,
+    ) -> Option<(i32, Duration)> {
+        let max_speed = max_speed.max(f32::MIN_POSITIVE);
+        let acceleration = acceleration.max(f32::MIN_POSITIVE);
+        // Geschwindigkeit nach dem ersten Schritt aus dem Stand
+        let min_speed = (2.0 * acceleration).sqrt().min(max_speed);
+        let speed = self.velocity.abs();
+        let direction = signum(self.velocity);
+
+        let desired = match goal {
+            Goal::Position(distance) => {
+                let moving_away = direction != 0 && distance.signum() != direction;
+                if distance == 0 && speed <= min_speed {
+                    self.reset();
+                    return None;
+                }
+                if moving_away
+                    || distance.unsigned_abs() as f32
+                        <= self.stopping_distance(profile, acceleration)
+                {
+                    0.0
+                } else {
+                    distance.signum() as f32 * max_speed
+                }
+            }
+            Goal::Velocity(velocity) => {
+                let velocity = velocity.clamp(-max_speed, max_speed);
+                if velocity == 0.0 && speed <= min_speed {
+                    self.reset();
+                    return None;
+                }
+                velocity
+            }
+        };
+
+        if direction == 0 {
+            // Anfahren aus dem Stand
+            let direction = signum(desired);
+            if direction == 0 {
+                return None;
+            }
+            self.velocity = direction as f32 * min_speed;
+            self.acceleration = 0.0;
+            self.phase = MotionPhase::Accelerating;
+            let interval = (2.0 / acceleration).sqrt().max(1.0 / max_speed);
+            return Some((direction, Duration::from_secs_f32(interval)));
+        }
+
+        let target_speed = if signum(desired) == direction {
+            desired.abs()
+        } else {
+            0.0
+        };
+
+        if target_speed == 0.0 && speed <= min_speed {
+            let keep_going = matches!(goal, Goal::Position(d) if d.signum() == direction);
+            if !keep_going {
+                // Stillstand erreicht, im nächsten Aufruf ggf. in Gegenrichtung anfahren
+                self.reset();
+                return self.next_step(goal, profile, max_speed, acceleration);
+            }
+            // Die letzten Schritte vor dem Ziel mit Mindestgeschwindigkeit
+            self.phase = MotionPhase::Decelerating;
+            return Some((direction, Duration::from_secs_f32(1.0 / min_speed)));
+        }
+
+        let new_speed = match profile {
+            MotionProfile::Trapezoidal => {
+                if target_speed > speed {
+                    (speed * speed + 2.0 * acceleration)
+                        .sqrt()
+                        .min(target_speed)
+                } else {
+                    (speed * speed - 2.0 * acceleration)
+                        .max(0.0)
+                        .sqrt()
+                        .max(target_speed)
+                }
+            }
+            MotionProfile::SCurve { jerk } => {
+                let new_speed = self.s_curve_speed(
+                    speed,
+                    target_speed,
+                    acceleration,
+                    jerk.max(f32::MIN_POSITIVE),
+                );
+                match goal {
+                    // Vor dem Ziel höchstens so schnell, dass die volle Verzögerung auf den
+                    // restlichen Schritten noch genügt, sonst schösse die Achse darüber hinaus
+                    Goal::Position(distance) if target_speed == 0.0 && distance != 0 => {
+                        let remaining = (distance.unsigned_abs() - 1) as f32;
+                        let limit = (2.0 * acceleration * remaining).sqrt().max(min_speed);
+                        if new_speed > limit {
+                            self.acceleration = (limit - speed) * speed;
+                            limit
+                        } else {
+                            new_speed
+                        }
+                    }
+                    _ => new_speed,
+                }
+            }
+        }
+        .max(min_speed);
+
+        self.velocity = direction as f32 * new_speed;
+        self.phase = if new_speed > speed {
+            MotionPhase::Accelerating
+        } else if new_speed < speed {
+            MotionPhase::Decelerating
+        } else {
+            MotionPhase::Cruising
+        };
+        Some((
+            direction,
+            Duration::from_secs_f32(2.0 / (speed + new_speed)),
+        ))
+    }
+
+    /// Weg bis zum Stillstand in Schritten
+    fn stopping_distance(&self, profile: MotionProfile, acceleration: f32) -> f32 {
+        let speed = self.velocity.abs();
+        let distance = speed * speed / (2.0 * acceleration);
+        let MotionProfile::SCurve { jerk } = profile else {
+            return distance;
+        };
+        if speed == 0.0 {
+            return 0.0;
+        }
+        let jerk = jerk.max(f32::MIN_POSITIVE);
+        // Die aktuelle Beschleunigung entsteht mit `jerk` aus einem Zustand ohne Beschleunigung
+        // (bzw. geht in ihn über). Von dort aus wird symmetrisch gebremst.
+        let ramp = -self.acceleration / jerk;
+        let start_speed = speed + self.acceleration * self.acceleration / (2.0 * jerk);
+        let braking = if start_speed >= acceleration * acceleration / jerk {
+            start_speed / 2.0 * (start_speed / acceleration + acceleration / jerk)
+        } else {
+            // Die Höchstverzögerung wird nicht erreicht
+            start_speed * (start_speed / jerk).sqrt()
+        };
+        braking - (start_speed * ramp - jerk * ramp.powi(3) / 6.0) + S_CURVE_BRAKING_MARGIN
+    }
+
+    /// Integriert Beschleunigung und Geschwindigkeit über einen Schritt mit begrenztem Ruck
+    fn s_curve_speed(
+        &mut self,
+        speed: f32,
+        target_speed: f32,
+        acceleration: f32,
+        jerk: f32,
+    ) -> f32 {
+        let dt = 1.0 / speed;
+        let difference = target_speed - speed;
+        // Ab hier muss die Beschleunigung abgebaut werden, um nicht überzuschwingen
+        let release = self.acceleration * self.acceleration / (2.0 * jerk);
+        let wanted = if difference > 0.0 && (self.acceleration <= 0.0 || difference > release) {
+            acceleration
+        } else if difference < 0.0 && (self.acceleration >= 0.0 || -difference > release) {
+            -acceleration
+        } else {
+            0.0
+        };
+        let max_change = jerk * dt;
+        self.acceleration += (wanted - self.acceleration).clamp(-max_change, max_change);
+
+        let new_speed = (speed + self.acceleration * dt).max(0.0);
+        if (difference > 0.0 && new_speed > target_speed)
+            || (difference < 0.0 && new_speed < target_speed)
+        {
+            self.acceleration = 0.0;
+            target_speed
+        } else {
+            new_speed
+        }
+    }
+}
+
+fn signum(value: f32) -> i32 {
+    if value > 0.0 {
+        1
+    } else if value < 0.0 {
+        -1
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_SPEED: f32 = 1000.0;
+    const ACCELERATION: f32 = 2000.0;
+
+    /// Fährt bis zum Stillstand auf `target`, Ergebnis: Endposition, Dauer in s,
+    /// größte Geschwindigkeit und ob eine Konstantfahrt dabei war
+    fn run(
+        planner: &mut MotionPlanner,
+        position: &mut i32,
+        target: i32,
+        profile: MotionProfile,
+    ) -> (f32, f32, bool) {
+        let (mut time, mut top_speed, mut cruised) = (0.0, 0.0f32, false);
+        for _ in 0..1_000_000 {
+            let Some((direction, interval)) =
+                planner.next_step(Goal::Position(target - *position), profile, MAX_SPEED, ACCELERATION)
+            else {
+                return (time, top_speed, cruised);
+            };
+            *position += direction;
+            time += interval.as_secs_f32();
+            top_speed = top_speed.max(planner.velocity().abs());
+            cruised |= planner.phase() == MotionPhase::Cruising;
+        }
+        panic!("planner did not stop");
+    }
+
+    #[test]
+    fn trapezoid_reaches_target_with_cruise() {
+        let mut planner = MotionPlanner::default();
+        let mut position = 0;
+        let (time, top_speed, cruised) =
+            run(&mut planner, &mut position, 5000, MotionProfile::Trapezoidal);
+        assert_eq!(position, 5000);
+        assert_eq!(planner.phase(), MotionPhase::Idle);
+        assert!(cruised);
+        assert!((top_speed - MAX_SPEED).abs() < 1.0, "top speed {top_speed}");
+        // Zwei Rampen von je v/a = 0.5 s plus (s - v²/a)/v = 4.5 s Konstantfahrt
+        assert!((time - 5.5).abs() < 0.05, "took {time} s");
+    }
+
+    #[test]
+    fn trapezoid_short_move_is_a_triangle() {
+        let mut planner = MotionPlanner::default();
+        let mut position = 0;
+        let (time, top_speed, cruised) =
+            run(&mut planner, &mut position, -200, MotionProfile::Trapezoidal);
+        assert_eq!(position, -200);
+        assert!(!cruised);
+        // Spitze bei sqrt(a·s) = 632 Schritte/s, Dauer 2·sqrt(s/a) = 0.63 s
+        assert!((top_speed - 632.5).abs() < 15.0, "top speed {top_speed}");
+        assert!((time - 0.632).abs() < 0.03, "took {time} s");
+    }
+
+    #[test]
+    fn s_curve_reaches_target_with_limited_jerk() {
+        let profile = MotionProfile::SCurve { jerk: 20_000.0 };
+        let mut planner = MotionPlanner::default();
+        let mut position = 0;
+        let (mut time, mut previous_acceleration) = (0.0f32, 0.0f32);
+        let mut jumps = 0;
+        while let Some((direction, interval)) =
+            planner.next_step(Goal::Position(5000 - position), profile, MAX_SPEED, ACCELERATION)
+        {
+            position += direction;
+            let dt = interval.as_secs_f32();
+            time += dt;
+            assert!(
+                planner.acceleration.abs() <= ACCELERATION + 1.0,
+                "acceleration {} at {position}",
+                planner.acceleration
+            );
+            // Die Beschleunigung ändert sich höchstens mit `jerk`, nur beim Einrasten auf die
+            // Höchstgeschwindigkeit fällt ein kleiner Rest auf einmal weg
+            let change = (planner.acceleration - previous_acceleration).abs();
+            if change > 20_000.0 * dt * 1.1 {
+                assert!(change < 0.2 * ACCELERATION, "acceleration jumps by {change} at {position}");
+                jumps += 1;
+            }
+            previous_acceleration = planner.acceleration;
+            assert!(position <= 5000);
+        }
+        assert_eq!(position, 5000);
+        // Die Rampen dauern je a/j = 0.1 s länger und brauchen je 50 Schritte mehr als beim
+        // Trapez: 2 · 0.6 s + 4.4 s
+        assert!((time - 5.6).abs() < 0.1, "took {time} s");
+        assert_eq!(jumps, 1);
+    }
+
+    #[test]
+    fn stopping_distance() {
+        let planner = MotionPlanner {
+            velocity: 1000.0,
+            ..MotionPlanner::default()
+        };
+        // v²/2a
+        assert_eq!(planner.stopping_distance(MotionProfile::Trapezoidal, 2000.0), 250.0);
+        // Bei erreichter Höchstverzögerung v/2·(v/a + a/j) = 500·(0.5 + 0.1), dazu die Reserve
+        let distance = planner.stopping_distance(MotionProfile::SCurve { jerk: 20_000.0 }, 2000.0);
+        assert!((distance - 300.0 - S_CURVE_BRAKING_MARGIN).abs() < 1e-3, "{distance}");
+        // Ohne sie v·sqrt(v/j)
+        let slow = MotionPlanner {
+            velocity: 100.0,
+            ..MotionPlanner::default()
+        };
+        let distance = slow.stopping_distance(MotionProfile::SCurve { jerk: 20_000.0 }, 2000.0);
+        let expected = 100.0 * (100.0f32 / 20_000.0).sqrt() + S_CURVE_BRAKING_MARGIN;
+        assert!((distance - expected).abs() < 1e-3, "{distance}");
+        // Noch beschleunigend braucht es mehr Weg als ohne Beschleunigung
+        let accelerating = MotionPlanner {
+            velocity: 1000.0,
+            acceleration: 2000.0,
+            ..MotionPlanner::default()
+        };
+        assert!(
+            accelerating.stopping_distance(MotionProfile::SCurve { jerk: 20_000.0 }, 2000.0)
+                > 300.0 + S_CURVE_BRAKING_MARGIN
+        );
+    }
+
+    #[test]
+    fn retarget_mid_move_reverses_and_arrives() {
+        for profile in [
+            MotionProfile::Trapezoidal,
+            MotionProfile::SCurve { jerk: 20_000.0 },
+        ] {
+            let mut planner = MotionPlanner::default();
+            let mut position = 0;
+            for _ in 0..1000 {
+                let (direction, _) = planner
+                    .next_step(Goal::Position(5000 - position), profile, MAX_SPEED, ACCELERATION)
+                    .unwrap();
+                position += direction;
+            }
+            assert!(planner.velocity() > 0.0);
+            // Neues Ziel hinter der aktuellen Position: bremsen, umkehren, ankommen
+            let (_, top_speed, _) = run(&mut planner, &mut position, 500, profile);
+            assert_eq!(position, 500, "{profile:?}");
+            assert!(top_speed <= MAX_SPEED + 1.0);
+        }
+    }
+
+    #[test]
+    fn velocity_goal_ramps_and_stops() {
+        let mut planner = MotionPlanner::default();
+        let mut steps = 0;
+        for _ in 0..2000 {
+            let (direction, _) = planner
+                .next_step(Goal::Velocity(-500.0), MotionProfile::Trapezoidal, MAX_SPEED, ACCELERATION)
+                .unwrap();
+            steps += direction;
+        }
+        assert_eq!(planner.velocity(), -500.0);
+        assert_eq!(planner.phase(), MotionPhase::Cruising);
+        assert!(steps < 0);
+        while planner
+            .next_step(Goal::Velocity(0.0), MotionProfile::Trapezoidal, MAX_SPEED, ACCELERATION)
+            .is_some()
+        {}
+        assert_eq!(planner.velocity(), 0.0);
+    }
+}
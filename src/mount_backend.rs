@@ -0,0 +1,292 @@
+use anyhow::{bail, Context, Result};
+use embedded_hal::digital::{ErrorType, OutputPin};
+use rppal::gpio::{Gpio, OutputPin as RppalOutputPin};
+use std::convert::Infallible;
+
+use crate::motion_profile::MotionPhase;
+use crate::mount_config::{AxisConfig, StepperConfig};
+use crate::stepper_axis::StepperAxis;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    Alt,
+    Az,
+    /// Optionaler Bildfeldrotator, `[mount.derotator]`
+    Derotator,
+}
+
+/// Welche Hardware die Achsen bewegt, `[mount] backend`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BackendKind {
+    /// Schrittmotortreiber an den GPIO-Pins des Raspberry Pi
+    #[default]
+    Gpio,
+    /// Keine Hardware, Schrittfolge und Rampen werden nur berechnet
+    Simulated,
+}
+
+/// Momentaufnahme einer Achse, alle Werte in Grad bzw. Grad/s
+#[derive(Debug, Clone, Copy)]
+pub struct AxisMotion {
+    pub position: f32,
+    /// Ziel des letzten Positionsauftrags
+    pub target: f32,
+    pub velocity: f32,
+    pub phase: MotionPhase,
+    /// `true` solange ein Auftrag läuft oder die Achse noch abbremst
+    pub moving: bool,
+}
+
+/// Hardware, die die Achsen der Montierung bewegt. Alle Methoden kehren
+/// sofort zurück und dürfen auch außerhalb des Tokio-Runtimes aufgerufen werden.
+pub trait MountBackend: Send + Sync {
+    /// `false` für den Derotator, wenn keiner konfiguriert ist. Aufträge an eine
+    /// fehlende Achse werden ignoriert.
+    fn has_axis(&self, axis: Axis) -> bool;
+    /// Fährt die Achse auf die Position in Grad, ein laufender Auftrag wird ersetzt
+    fn move_to(&self, axis: Axis, position: f32);
+    /// Dreht die Achse dauerhaft mit Grad/s, 0 bremst ab
+    fn set_velocity(&self, axis: Axis, velocity: f32);
+    /// Bremst die Achse kontrolliert bis zum Stillstand ab
+    fn stop(&self, axis: Axis);
+    fn position(&self, axis: Axis) -> f32;
+    /// Setzt die aktuelle Position, ohne die Achse zu bewegen
+    fn set_position(&self, axis: Axis, position: f32);
+    /// Schaltet die Treiber aller Achsen ein bzw. aus
+    fn enable(&self);
+    fn disable(&self);
+    fn status(&self, axis: Axis) -> AxisMotion;
+}
+
+/// Einzelner Schrittmotor außerhalb der Montierung, z. B. der Fokussierer. Positionen in
+/// der Einheit aus `StepperConfig`.
+pub trait StepperDrive: Send + Sync {
+    fn move_to(&self, position: f32);
+    fn stop(&self);
+    fn position(&self) -> f32;
+    /// Setzt die aktuelle Position, ohne den Motor zu bewegen
+    fn set_position(&self, position: f32);
+    fn is_moving(&self) -> bool;
+}
+
+impl<STEP, DIR, EN> StepperDrive for StepperAxis<STEP, DIR, EN>
+where
+    StepperAxis<STEP, DIR, EN>: Send + Sync,
+{
+    fn move_to(&self, position: f32) {
+        StepperAxis::move_to(self, position);
+    }
+
+    fn stop(&self) {
+        StepperAxis::stop(self);
+    }
+
+    fn position(&self) -> f32 {
+        StepperAxis::position(self)
+    }
+
+    fn set_position(&self, position: f32) {
+        StepperAxis::set_position(self, position);
+    }
+
+    fn is_moving(&self) -> bool {
+        StepperAxis::is_moving(self)
+    }
+}
+
+/// Richtet einen einzelnen Schrittmotor auf derselben Hardware wie die Montierung ein.
+/// `section` erscheint in den Fehlermeldungen.
+pub fn stepper_drive(
+    kind: BackendKind,
+    section: &str,
+    config: &StepperConfig,
+) -> Result<Box<dyn StepperDrive>> {
+    Ok(match kind {
+        BackendKind::Gpio => {
+            let gpio = Gpio::new().context("Failed to initialize GPIO")?;
+            Box::new(gpio_axis(&gpio, section, config)?)
+        }
+        BackendKind::Simulated => Box::new(simulated_axis(config)),
+    })
+}
+
+/// Zwei Schrittmotorachsen und ggf. der Derotator; mit GPIO-Pins die echte Montierung, mit
+/// `SimulatedPin` eine Simulation mit demselben Timing und denselben Rampen
+#[derive(Debug)]
+pub struct StepperBackend<STEP, DIR, EN> {
+    alt: StepperAxis<STEP, DIR, EN>,
+    az: StepperAxis<STEP, DIR, EN>,
+    derotator: Option<StepperAxis<STEP, DIR, EN>>,
+}
+
+pub type GpioBackend = StepperBackend<RppalOutputPin, RppalOutputPin, RppalOutputPin>;
+pub type SimulatedBackend = StepperBackend<SimulatedPin, SimulatedPin, SimulatedPin>;
+
+impl<STEP, DIR, EN> StepperBackend<STEP, DIR, EN> {
+    fn axis(&self, axis: Axis) -> Option<&StepperAxis<STEP, DIR, EN>> {
+        match axis {
+            Axis::Alt => Some(&self.alt),
+            Axis::Az => Some(&self.az),
+            Axis::Derotator => self.derotator.as_ref(),
+        }
+    }
+
+    fn axes(&self) -> impl Iterator<Item = &StepperAxis<STEP, DIR, EN>> {
+        [&self.alt, &self.az].into_iter().chain(self.derotator.as_ref())
+    }
+}
+
+impl<STEP, DIR, EN> MountBackend for StepperBackend<STEP, DIR, EN>
+where
+    StepperAxis<STEP, DIR, EN>: Send + Sync,
+{
+    fn has_axis(&self, axis: Axis) -> bool {
+        self.axis(axis).is_some()
+    }
+
+    fn move_to(&self, axis: Axis, position: f32) {
+        if let Some(axis) = self.axis(axis) {
+            axis.move_to(position);
+        }
+    }
+
+    fn set_velocity(&self, axis: Axis, velocity: f32) {
+        if let Some(axis) = self.axis(axis) {
+            axis.set_velocity(velocity);
+        }
+    }
+
+    fn stop(&self, axis: Axis) {
+        if let Some(axis) = self.axis(axis) {
+            axis.stop();
+        }
+    }
+
+    fn position(&self, axis: Axis) -> f32 {
+        self.axis(axis).map_or(0.0, StepperAxis::position)
+    }
+
+    fn set_position(&self, axis: Axis, position: f32) {
+        if let Some(axis) = self.axis(axis) {
+            axis.set_position(position);
+        }
+    }
+
+    fn enable(&self) {
+        self.axes().for_each(StepperAxis::enable);
+    }
+
+    fn disable(&self) {
+        self.axes().for_each(StepperAxis::disable);
+    }
+
+    fn status(&self, axis: Axis) -> AxisMotion {
+        let Some(axis) = self.axis(axis) else {
+            return AxisMotion {
+                position: 0.0,
+                target: 0.0,
+                velocity: 0.0,
+                phase: MotionPhase::Idle,
+                moving: false,
+            };
+        };
+        AxisMotion {
+            position: axis.position(),
+            target: axis.target(),
+            velocity: axis.velocity(),
+            phase: axis.phase(),
+            moving: axis.is_moving(),
+        }
+    }
+}
+
+impl GpioBackend {
+    pub fn new(alt: &AxisConfig, az: &AxisConfig, derotator: Option<&AxisConfig>) -> Result<Self> {
+        let axes: Vec<(&str, &AxisConfig)> = [("alt", alt), ("az", az)]
+            .into_iter()
+            .chain(derotator.map(|config| ("derotator", config)))
+            .collect();
+        for (i, (name, config)) in axes.iter().enumerate() {
+            for (other_name, other) in &axes[i + 1..] {
+                if let Some(pin) = config.pins().into_iter().find(|pin| other.pins().contains(pin)) {
+                    bail!("GPIO {pin} is used by both mount.{name} and mount.{other_name}");
+                }
+            }
+        }
+        let gpio = Gpio::new().context("Failed to initialize GPIO")?;
+        Ok(StepperBackend {
+            alt: gpio_axis(&gpio, "mount.alt", &alt.stepper())?,
+            az: gpio_axis(&gpio, "mount.az", &az.stepper())?,
+            derotator: derotator
+                .map(|config| gpio_axis(&gpio, "mount.derotator", &config.stepper()))
+                .transpose()?,
+        })
+    }
+}
+
+fn gpio_axis(
+    gpio: &Gpio,
+    section: &str,
+    config: &StepperConfig,
+) -> Result<StepperAxis<RppalOutputPin, RppalOutputPin, RppalOutputPin>> {
+    let output = |pin: u8| -> Result<RppalOutputPin> {
+        Ok(gpio
+            .get(pin)
+            .with_context(|| format!("{section}: GPIO {pin} is not available"))?
+            .into_output())
+    };
+    let enable = config.enable_pin.map(output).transpose()?;
+    let axis = StepperAxis::new(
+        output(config.step_pin)?,
+        output(config.dir_pin)?,
+        enable,
+        config.polarity,
+        config.steps_per_unit,
+        config.max_speed,
+        config.acceleration,
+    );
+    axis.set_profile(config.profile);
+    Ok(axis)
+}
+
+impl SimulatedBackend {
+    pub fn new(alt: &AxisConfig, az: &AxisConfig, derotator: Option<&AxisConfig>) -> Self {
+        StepperBackend {
+            alt: simulated_axis(&alt.stepper()),
+            az: simulated_axis(&az.stepper()),
+            derotator: derotator.map(|config| simulated_axis(&config.stepper())),
+        }
+    }
+}
+
+fn simulated_axis(config: &StepperConfig) -> StepperAxis<SimulatedPin, SimulatedPin, SimulatedPin> {
+    let axis = StepperAxis::new(
+        SimulatedPin,
+        SimulatedPin,
+        Some(SimulatedPin),
+        config.polarity,
+        config.steps_per_unit,
+        config.max_speed,
+        config.acceleration,
+    );
+    axis.set_profile(config.profile);
+    axis
+}
+
+/// Ausgang ohne Hardware, der Step-Thread läuft trotzdem im echten Takt
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SimulatedPin;
+
+impl ErrorType for SimulatedPin {
+    type Error = Infallible;
+}
+
+impl OutputPin for SimulatedPin {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
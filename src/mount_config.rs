@@ -0,0 +1,210 @@
+use anyhow::{bail, Result};
+use serde::Deserialize;
+
+use crate::motion_profile::MotionProfile;
+use crate::stepper_motor::Polarity;
+
+/// Höchste BCM-Nummer, die auf der Stiftleiste des Raspberry Pi liegt
+const MAX_BCM_PIN: u8 = 27;
+/// Vom DRV8825/TMC22xx unterstützte Mikroschritt-Teilungen
+const MICROSTEPS: [u32; 9] = [1, 2, 4, 8, 16, 32, 64, 128, 256];
+
+fn default_motor_steps() -> u32 {
+    200
+}
+
+fn default_microsteps() -> u32 {
+    16
+}
+
+fn default_gear_ratio() -> f32 {
+    1.0
+}
+
+fn default_max_speed() -> f32 {
+    10.0
+}
+
+fn default_acceleration() -> f32 {
+    1.0
+}
+
+/// Form der Beschleunigungsrampe, `[mount.<axis>] profile`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RampShape {
+    #[default]
+    Trapezoidal,
+    /// Ruckbegrenzt, braucht `jerk`
+    SCurve,
+}
+
+/// Anschluss und Bewegungsgrenzen eines Schrittmotors in der Einheit der jeweiligen Achse
+#[derive(Debug, Clone, Copy)]
+pub struct StepperConfig {
+    pub step_pin: u8,
+    pub dir_pin: u8,
+    pub enable_pin: Option<u8>,
+    pub polarity: Polarity,
+    /// Mikroschritte pro Einheit
+    pub steps_per_unit: f32,
+    /// in Einheiten/s
+    pub max_speed: f32,
+    /// in Einheiten/s²
+    pub acceleration: f32,
+    /// Rampenform, der Ruck einer S-Kurve in Einheiten/s³
+    pub profile: MotionProfile,
+}
+
+/// Hardware einer Achse aus `[mount.alt]` bzw. `[mount.az]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AxisConfig {
+    /// BCM-Pins des Treibers
+    pub step_pin: u8,
+    pub dir_pin: u8,
+    pub enable_pin: Option<u8>,
+    /// Drehrichtung umkehren, falls die Achse falsch herum läuft
+    #[serde(default)]
+    pub invert_direction: bool,
+    /// `true`, wenn der Treiber mit HIGH statt LOW aktiviert wird
+    #[serde(default)]
+    pub enable_active_high: bool,
+    /// Vollschritte pro Motorumdrehung
+    #[serde(default = "default_motor_steps")]
+    pub motor_steps: u32,
+    #[serde(default = "default_microsteps")]
+    pub microsteps: u32,
+    /// Motorumdrehungen pro Umdrehung der Achse
+    #[serde(default = "default_gear_ratio")]
+    pub gear_ratio: f32,
+    /// in Grad/s
+    #[serde(default = "default_max_speed")]
+    pub max_speed: f32,
+    /// in Grad/s²
+    #[serde(default = "default_acceleration")]
+    pub acceleration: f32,
+    #[serde(default)]
+    pub profile: RampShape,
+    /// Ruck der S-Kurve in Grad/s³
+    pub jerk: Option<f32>,
+}
+
+impl AxisConfig {
+    /// Die früher fest eingebauten Pins und Werte (100 Mikroschritte pro Grad) für Konfigurationen
+    /// ohne `[mount.alt]` bzw. `[mount.az]`
+    pub fn builtin(axis: &str) -> Option<AxisConfig> {
+        let (step_pin, dir_pin, enable_pin) = match axis {
+            "alt" => (17, 27, 22),
+            "az" => (18, 24, 4),
+            _ => return None,
+        };
+        Some(AxisConfig {
+            step_pin,
+            dir_pin,
+            enable_pin: Some(enable_pin),
+            invert_direction: false,
+            enable_active_high: false,
+            motor_steps: default_motor_steps(),
+            microsteps: default_microsteps(),
+            gear_ratio: 11.25,
+            max_speed: default_max_speed(),
+            acceleration: default_acceleration(),
+            profile: RampShape::Trapezoidal,
+            jerk: None,
+        })
+    }
+
+    /// Mikroschritte pro Grad an der Achse
+    pub fn steps_per_degree(&self) -> f32 {
+        self.motor_steps as f32 * self.microsteps as f32 * self.gear_ratio / 360.0
+    }
+
+    pub fn polarity(&self) -> Polarity {
+        Polarity {
+            invert_direction: self.invert_direction,
+            enable_active_high: self.enable_active_high,
+        }
+    }
+
+    /// Schrittmotor mit Grad als Einheit
+    pub fn stepper(&self) -> StepperConfig {
+        StepperConfig {
+            step_pin: self.step_pin,
+            dir_pin: self.dir_pin,
+            enable_pin: self.enable_pin,
+            polarity: self.polarity(),
+            steps_per_unit: self.steps_per_degree(),
+            max_speed: self.max_speed,
+            acceleration: self.acceleration,
+            profile: match (self.profile, self.jerk) {
+                (RampShape::SCurve, Some(jerk)) => MotionProfile::SCurve { jerk },
+                _ => MotionProfile::Trapezoidal,
+            },
+        }
+    }
+
+    /// Weg in Grad, um aus `speed` Grad/s anzuhalten
+    pub fn braking_distance(&self, speed: f32) -> f32 {
+        let distance = speed * speed / (2.0 * self.acceleration);
+        match (self.profile, self.jerk) {
+            (RampShape::SCurve, Some(jerk)) => distance + speed * self.acceleration / jerk,
+            _ => distance,
+        }
+    }
+
+    pub fn pins(&self) -> Vec<u8> {
+        let mut pins = vec![self.step_pin, self.dir_pin];
+        pins.extend(self.enable_pin);
+        pins
+    }
+
+    /// Prüft die Werte, `axis` erscheint in den Fehlermeldungen
+    pub fn validate(&self, axis: &str) -> Result<()> {
+        check_pins(&format!("mount.{axis}"), &self.pins())?;
+        if self.motor_steps == 0 {
+            bail!("mount.{axis}: motor_steps must be greater than 0");
+        }
+        if !MICROSTEPS.contains(&self.microsteps) {
+            bail!(
+                "mount.{axis}: microsteps must be one of {MICROSTEPS:?}, got {}",
+                self.microsteps
+            );
+        }
+        for (name, value) in [
+            ("gear_ratio", self.gear_ratio),
+            ("max_speed", self.max_speed),
+            ("acceleration", self.acceleration),
+        ] {
+            if !value.is_finite() || value <= 0.0 {
+                bail!("mount.{axis}: {name} must be a positive number, got {value}");
+            }
+        }
+        match (self.profile, self.jerk) {
+            (RampShape::SCurve, None) => {
+                bail!("mount.{axis}: profile \"s_curve\" needs jerk")
+            }
+            (RampShape::Trapezoidal, Some(_)) => {
+                bail!("mount.{axis}: jerk only applies to profile \"s_curve\"")
+            }
+            (_, Some(jerk)) if !jerk.is_finite() || jerk <= 0.0 => {
+                bail!("mount.{axis}: jerk must be a positive number, got {jerk}")
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Prüft, ob die Pins auf der Stiftleiste liegen und nur einmal vorkommen
+pub fn check_pins(section: &str, pins: &[u8]) -> Result<()> {
+    for (i, pin) in pins.iter().enumerate() {
+        if *pin > MAX_BCM_PIN {
+            bail!("{section}: GPIO {pin} is not a BCM pin on the header (0..={MAX_BCM_PIN})");
+        }
+        if pins[..i].contains(pin) {
+            bail!("{section}: GPIO {pin} is used more than once");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,346 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use nalgebra::Vector3;
+use rppal::i2c::I2c;
+use serde::Deserialize;
+
+use crate::ahrs::Mahony;
+use crate::sensor::{
+    CalibrationStatus, OrientationReading, OrientationSensor, SensorError, SensorKind, SensorStage,
+};
+
+const MPU9250_ADDRESSES: [u8; 2] = [0x68, 0x69];
+/// WHO_AM_I von MPU-9250 bzw. MPU-9255
+const MPU9250_IDS: [u8; 2] = [0x71, 0x73];
+/// Magnetometer im Bypass-Modus direkt am Bus
+const AK8963_ADDRESS: u8 = 0x0C;
+const AK8963_ID: u8 = 0x48;
+
+// Register MPU-9250
+const CONFIG: u8 = 0x1A;
+const GYRO_CONFIG: u8 = 0x1B;
+const ACCEL_CONFIG: u8 = 0x1C;
+const INT_PIN_CFG: u8 = 0x37;
+const ACCEL_XOUT_H: u8 = 0x3B;
+const GYRO_XOUT_H: u8 = 0x43;
+const PWR_MGMT_1: u8 = 0x6B;
+const WHO_AM_I: u8 = 0x75;
+
+// Register AK8963
+const AK_WIA: u8 = 0x00;
+const AK_ST1: u8 = 0x02;
+const AK_HXL: u8 = 0x03;
+const AK_CNTL1: u8 = 0x0A;
+const AK_ASAX: u8 = 0x10;
+
+/// ±500 °/s
+const GYRO_SCALE: f32 = 1.0 / 65.5;
+/// ±2 g
+const ACCEL_SCALE: f32 = 1.0 / 16384.0;
+/// 16 Bit, in µT
+const MAG_SCALE: f32 = 0.15;
+/// Anzahl der Messungen für den Gyro-Nullpunkt beim Start
+const GYRO_BIAS_SAMPLES: u32 = 200;
+
+fn default_bus() -> u8 {
+    1
+}
+
+fn default_address() -> u8 {
+    0x68
+}
+
+fn default_sample_rate() -> f32 {
+    50.0
+}
+
+fn default_kp() -> f32 {
+    1.0
+}
+
+fn default_ki() -> f32 {
+    0.1
+}
+
+/// `[sensors.mpu9250]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Mpu9250Config {
+    #[serde(default = "default_bus")]
+    pub bus: u8,
+    #[serde(default = "default_address")]
+    pub address: u8,
+    /// in Hz
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: f32,
+    /// Verstärkungen des Mahony-Filters
+    #[serde(default = "default_kp")]
+    pub kp: f32,
+    #[serde(default = "default_ki")]
+    pub ki: f32,
+    /// Hard-Iron-Offset des Magnetometers in µT im Achsensystem des MPU-9250
+    /// (Motoren, Schrauben)
+    pub mag_offset: Option<[f32; 3]>,
+}
+
+impl Default for Mpu9250Config {
+    fn default() -> Self {
+        Mpu9250Config {
+            bus: default_bus(),
+            address: default_address(),
+            sample_rate: default_sample_rate(),
+            kp: default_kp(),
+            ki: default_ki(),
+            mag_offset: None,
+        }
+    }
+}
+
+impl Mpu9250Config {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !MPU9250_ADDRESSES.contains(&self.address) {
+            anyhow::bail!(
+                "sensors.mpu9250: address must be 0x68 or 0x69, got {:#04x}",
+                self.address
+            );
+        }
+        if !(self.sample_rate > 0.0 && self.sample_rate <= 1000.0) {
+            anyhow::bail!(
+                "sensors.mpu9250: sample_rate must be in 0..=1000 Hz, got {}",
+                self.sample_rate
+            );
+        }
+        if !(self.kp >= 0.0 && self.ki >= 0.0) {
+            anyhow::bail!("sensors.mpu9250: kp and ki must not be negative");
+        }
+        Ok(())
+    }
+}
+
+/// MPU-9250 (Beschleunigung, Drehrate) mit AK8963 (Magnetfeld), fusioniert mit `Mahony`
+pub struct Mpu9250 {
+    i2c: I2c,
+    address: u8,
+    sample_rate: f32,
+    filter: Mahony,
+    gyro_bias: Vector3<f32>,
+    /// Empfindlichkeitskorrektur aus dem Fuse-ROM des AK8963
+    mag_adjust: Vector3<f32>,
+    mag_offset: Option<Vector3<f32>>,
+    /// Letzte gültige Magnetfeldmessung, der AK8963 misst langsamer als das Gyro
+    mag: Option<Vector3<f32>>,
+    last_update: Option<Instant>,
+}
+
+impl Mpu9250 {
+    /// Initialisiert beide Chips und misst den Gyro-Nullpunkt.
+    /// Die Montierung muss dabei stillstehen.
+    pub fn new(config: &Mpu9250Config) -> Result<Self, SensorError> {
+        let i2c = I2c::with_bus(config.bus).map_err(|e| {
+            SensorError::new(SensorStage::Bus, format!("/dev/i2c-{}: {e}", config.bus))
+        })?;
+        let mut sensor = Mpu9250 {
+            i2c,
+            address: config.address,
+            sample_rate: config.sample_rate,
+            filter: Mahony::new(config.kp, config.ki),
+            gyro_bias: Vector3::zeros(),
+            mag_adjust: Vector3::repeat(1.0),
+            mag_offset: config.mag_offset.map(Vector3::from),
+            mag: None,
+            last_update: None,
+        };
+        sensor.init()?;
+        sensor.calibrate_gyro()?;
+        Ok(sensor)
+    }
+
+    fn init(&mut self) -> Result<(), SensorError> {
+        let init_error = |e: rppal::i2c::Error| SensorError::new(SensorStage::Init, e);
+
+        let id = self.read_register(self.address, WHO_AM_I).map_err(init_error)?;
+        if !MPU9250_IDS.contains(&id) {
+            return Err(SensorError::new(
+                SensorStage::Init,
+                format!("MPU-9250 at {:#04x}: unexpected WHO_AM_I {id:#04x}", self.address),
+            ));
+        }
+        // Aufwecken, Takt vom Gyro-PLL
+        self.write_register(self.address, PWR_MGMT_1, 0x01)
+            .map_err(init_error)?;
+        thread::sleep(Duration::from_millis(100));
+        // Tiefpass 41 Hz, ±500 °/s, ±2 g
+        self.write_register(self.address, CONFIG, 0x03)
+            .map_err(init_error)?;
+        self.write_register(self.address, GYRO_CONFIG, 0x08)
+            .map_err(init_error)?;
+        self.write_register(self.address, ACCEL_CONFIG, 0x00)
+            .map_err(init_error)?;
+        // Bypass, damit der AK8963 direkt am Bus erscheint
+        self.write_register(self.address, INT_PIN_CFG, 0x02)
+            .map_err(init_error)?;
+        thread::sleep(Duration::from_millis(10));
+
+        let id = self
+            .read_register(AK8963_ADDRESS, AK_WIA)
+            .map_err(init_error)?;
+        if id != AK8963_ID {
+            return Err(SensorError::new(
+                SensorStage::Init,
+                format!("AK8963: unexpected WIA {id:#04x}"),
+            ));
+        }
+        let calibration_error = |e: rppal::i2c::Error| SensorError::new(SensorStage::Calibration, e);
+        // Fuse-ROM lesen, dann 16 Bit, kontinuierlich mit 100 Hz
+        self.write_register(AK8963_ADDRESS, AK_CNTL1, 0x00)
+            .map_err(calibration_error)?;
+        thread::sleep(Duration::from_millis(10));
+        self.write_register(AK8963_ADDRESS, AK_CNTL1, 0x0F)
+            .map_err(calibration_error)?;
+        thread::sleep(Duration::from_millis(10));
+        let mut asa = [0u8; 3];
+        self.read_registers(AK8963_ADDRESS, AK_ASAX, &mut asa)
+            .map_err(calibration_error)?;
+        self.mag_adjust = Vector3::from(asa.map(|asa| (asa as f32 - 128.0) / 256.0 + 1.0));
+        self.write_register(AK8963_ADDRESS, AK_CNTL1, 0x00)
+            .map_err(calibration_error)?;
+        thread::sleep(Duration::from_millis(10));
+        self.write_register(AK8963_ADDRESS, AK_CNTL1, 0x16)
+            .map_err(calibration_error)?;
+        thread::sleep(Duration::from_millis(10));
+        Ok(())
+    }
+
+    fn calibrate_gyro(&mut self) -> Result<(), SensorError> {
+        let mut sum = Vector3::zeros();
+        for _ in 0..GYRO_BIAS_SAMPLES {
+            sum += self
+                .read_gyro()
+                .map_err(|e| SensorError::new(SensorStage::Calibration, e))?;
+            thread::sleep(Duration::from_millis(5));
+        }
+        self.gyro_bias = sum / GYRO_BIAS_SAMPLES as f32;
+        Ok(())
+    }
+
+    /// in rad/s
+    fn read_gyro(&mut self) -> Result<Vector3<f32>, rppal::i2c::Error> {
+        let mut buffer = [0u8; 6];
+        self.read_registers(self.address, GYRO_XOUT_H, &mut buffer)?;
+        Ok(big_endian_vector(&buffer) * GYRO_SCALE.to_radians())
+    }
+
+    /// in g
+    fn read_accel(&mut self) -> Result<Vector3<f32>, rppal::i2c::Error> {
+        let mut buffer = [0u8; 6];
+        self.read_registers(self.address, ACCEL_XOUT_H, &mut buffer)?;
+        Ok(big_endian_vector(&buffer) * ACCEL_SCALE)
+    }
+
+    /// in µT im Achsensystem des MPU-9250, `None` wenn keine neue Messung vorliegt
+    fn read_mag(&mut self) -> Result<Option<Vector3<f32>>, rppal::i2c::Error> {
+        if self.read_register(AK8963_ADDRESS, AK_ST1)? & 0x01 == 0 {
+            return Ok(None);
+        }
+        // HXL..HZH und ST2, das Lesen von ST2 gibt die nächste Messung frei
+        let mut buffer = [0u8; 7];
+        self.read_registers(AK8963_ADDRESS, AK_HXL, &mut buffer)?;
+        if buffer[6] & 0x08 != 0 {
+            // Magnetischer Überlauf
+            return Ok(None);
+        }
+        let raw = Vector3::new(
+            i16::from_le_bytes([buffer[0], buffer[1]]) as f32,
+            i16::from_le_bytes([buffer[2], buffer[3]]) as f32,
+            i16::from_le_bytes([buffer[4], buffer[5]]) as f32,
+        );
+        let mag = raw.component_mul(&self.mag_adjust) * MAG_SCALE;
+        // Beim AK8963 sind x und y vertauscht und z zeigt in die Gegenrichtung
+        let mag = Vector3::new(mag.y, mag.x, -mag.z);
+        Ok(Some(mag - self.mag_offset.unwrap_or_else(Vector3::zeros)))
+    }
+
+    fn read_register(&mut self, address: u8, register: u8) -> Result<u8, rppal::i2c::Error> {
+        let mut buffer = [0u8; 1];
+        self.read_registers(address, register, &mut buffer)?;
+        Ok(buffer[0])
+    }
+
+    fn read_registers(
+        &mut self,
+        address: u8,
+        register: u8,
+        buffer: &mut [u8],
+    ) -> Result<(), rppal::i2c::Error> {
+        self.i2c.set_slave_address(address as u16)?;
+        self.i2c.write_read(&[register], buffer)
+    }
+
+    fn write_register(
+        &mut self,
+        address: u8,
+        register: u8,
+        value: u8,
+    ) -> Result<(), rppal::i2c::Error> {
+        self.i2c.set_slave_address(address as u16)?;
+        self.i2c.write(&[register, value])?;
+        Ok(())
+    }
+}
+
+impl OrientationSensor for Mpu9250 {
+    fn kind(&self) -> SensorKind {
+        SensorKind::Mpu9250
+    }
+
+    fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    fn read(&mut self) -> Result<OrientationReading, SensorError> {
+        let read_error = |e: rppal::i2c::Error| SensorError::new(SensorStage::Read, e);
+        let gyro = self.read_gyro().map_err(read_error)? - self.gyro_bias;
+        let accel = self.read_accel().map_err(read_error)?;
+        if let Some(mag) = self.read_mag().map_err(read_error)? {
+            self.mag = Some(mag);
+        }
+
+        let now = Instant::now();
+        match (self.last_update, self.mag) {
+            (Some(last_update), mag) => {
+                let dt = (now - last_update).as_secs_f32();
+                let mag = mag.unwrap_or_else(Vector3::zeros);
+                self.filter.update(gyro, accel, mag, dt);
+                self.last_update = Some(now);
+            }
+            (None, Some(mag)) => {
+                self.filter.initialize(accel, mag);
+                self.last_update = Some(now);
+            }
+            // Auf die erste Magnetfeldmessung warten
+            (None, None) => {}
+        }
+
+        Ok(OrientationReading {
+            quaternion: self.filter.orientation(),
+            calibration: CalibrationStatus {
+                system: if self.mag.is_some() { 3 } else { 1 },
+                gyroscope: 3,
+                accelerometer: 3,
+                // Ohne Hard-Iron-Offset zeigt der Azimut in der Nähe der Motoren daneben
+                magnetometer: if self.mag_offset.is_some() { 3 } else { 1 },
+            },
+        })
+    }
+}
+
+fn big_endian_vector(buffer: &[u8; 6]) -> Vector3<f32> {
+    Vector3::new(
+        i16::from_be_bytes([buffer[0], buffer[1]]) as f32,
+        i16::from_be_bytes([buffer[2], buffer[3]]) as f32,
+        i16::from_be_bytes([buffer[4], buffer[5]]) as f32,
+    )
+}
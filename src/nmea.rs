@@ -0,0 +1,462 @@
+//! NMEA 0183 von einem direkt angeschlossenen Empfänger: GGA und RMC für Position und
+//! Geschwindigkeit, GSA für Fix-Art, DOP und verwendete Satelliten, GSV für die Satelliten
+//! in Sicht.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use open_pi_scope::gnss::{GnssSystem, Mode, Satellite};
+
+use crate::gnss_source::{GnssFix, GnssParser, GnssUpdate};
+
+/// Längere Zeilen sind kein NMEA (höchstens 82 Zeichen, mit Reserve für Erweiterungen)
+const MAX_SENTENCE: usize = 120;
+const KNOTS_TO_METERS_PER_SECOND: f32 = 0.514_444;
+/// Fehler pro DOP in m wie bei gpsd ohne DGPS (95 %)
+const H_UERE: f32 = 15.0;
+const V_UERE: f32 = 23.0;
+
+#[derive(Debug, Default)]
+pub struct NmeaParser {
+    line: Vec<u8>,
+    /// Fix-Art aus dem letzten GSA
+    mode: Option<Mode>,
+    /// Verwendete Satelliten je GSA-Quelle (Talker bzw. System-ID)
+    used: HashMap<String, Vec<(GnssSystem, i32)>>,
+    /// Satelliten je GSV-Zyklus (Talker und Signal), `pending` bis zur letzten Nachricht
+    in_view: HashMap<String, Vec<Satellite>>,
+    pending: HashMap<String, Vec<Satellite>>,
+}
+
+impl GnssParser for NmeaParser {
+    fn feed(&mut self, data: &[u8], updates: &mut Vec<GnssUpdate>) {
+        for &byte in data {
+            match byte {
+                b'$' => {
+                    self.line.clear();
+                    self.line.push(byte);
+                }
+                b'\r' | b'\n' => {
+                    let line = std::mem::take(&mut self.line);
+                    if let Ok(text) = std::str::from_utf8(&line) {
+                        self.sentence(text, updates);
+                    }
+                }
+                _ if !self.line.is_empty() && self.line.len() < MAX_SENTENCE => {
+                    self.line.push(byte)
+                }
+                // Bytes außerhalb eines Satzes bzw. zu lange Zeilen verwerfen
+                _ => self.line.clear(),
+            }
+        }
+    }
+}
+
+impl NmeaParser {
+    fn sentence(&mut self, text: &str, updates: &mut Vec<GnssUpdate>) {
+        let Some(body) = checked(text) else {
+            return;
+        };
+        let fields: Vec<&str> = body.split(',').collect();
+        let address = fields[0];
+        if address.len() != 5 {
+            return;
+        }
+        let (talker, kind) = address.split_at(2);
+        match kind {
+            "GGA" => updates.push(GnssUpdate::Fix(self.gga(&fields))),
+            "RMC" => updates.push(GnssUpdate::Fix(rmc(&fields))),
+            "GSA" => {
+                if let Some(fix) = self.gsa(talker, &fields) {
+                    updates.push(GnssUpdate::Fix(fix));
+                }
+            }
+            "GSV" => {
+                if let Some(satellites) = self.gsv(talker, &fields) {
+                    updates.push(GnssUpdate::Satellites(satellites));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Zeit, Breite, N/S, Länge, O/W, Qualität, Satelliten, HDOP, Höhe über NN, …
+    fn gga(&self, fields: &[&str]) -> GnssFix {
+        let quality: u8 = field(fields, 6).parse().unwrap_or(0);
+        if quality == 0 {
+            return GnssFix {
+                mode: Some(Mode::NoFix),
+                ..GnssFix::default()
+            };
+        }
+        let mode = self.mode.unwrap_or(Mode::Fix3d);
+        // GSA kommt meist erst nach GGA, den Lagefehler dieser Epoche liefert GGA selbst
+        let horizontal = field(fields, 8).parse::<f32>().ok().map(|hdop| hdop * H_UERE);
+        GnssFix {
+            mode: Some(mode),
+            lat: coordinate(field(fields, 2), field(fields, 3)),
+            lon: coordinate(field(fields, 4), field(fields, 5)),
+            alt: if mode == Mode::Fix3d {
+                field(fields, 9).parse().ok()
+            } else {
+                None
+            },
+            estimated_error_plane: horizontal,
+            estimated_error_latitude: horizontal.map(|error| error / std::f32::consts::SQRT_2),
+            estimated_error_longitude: horizontal.map(|error| error / std::f32::consts::SQRT_2),
+            epoch: true,
+            ..GnssFix::default()
+        }
+    }
+
+    /// Modus, Fix-Art, 12 Satelliten, PDOP, HDOP, VDOP, ab NMEA 4.10 die System-ID
+    fn gsa(&mut self, talker: &str, fields: &[&str]) -> Option<GnssFix> {
+        let mode = match field(fields, 2) {
+            "2" => Mode::Fix2d,
+            "3" => Mode::Fix3d,
+            "1" => Mode::NoFix,
+            _ => return None,
+        };
+        self.mode = Some(mode);
+
+        let source = match field(fields, 18) {
+            "1" => "GP",
+            "2" => "GL",
+            "3" => "GA",
+            "4" => "GB",
+            "5" => "GQ",
+            "6" => "GI",
+            _ => talker,
+        };
+        let used = (3..=14)
+            .filter_map(|i| field(fields, i).parse().ok())
+            .map(|prn| system_and_prn(source, prn))
+            .collect();
+        self.used.insert(format!("{talker}{source}"), used);
+
+        let hdop: Option<f32> = field(fields, 16).parse().ok();
+        let vdop: Option<f32> = field(fields, 17).parse().ok();
+        let horizontal = hdop.map(|hdop| hdop * H_UERE);
+        Some(GnssFix {
+            // Einen Fix meldet erst GGA zusammen mit der Position
+            mode: (mode == Mode::NoFix).then_some(mode),
+            estimated_error_plane: horizontal,
+            estimated_error_latitude: horizontal.map(|error| error / std::f32::consts::SQRT_2),
+            estimated_error_longitude: horizontal.map(|error| error / std::f32::consts::SQRT_2),
+            estimated_error_altitude: vdop.map(|vdop| vdop * V_UERE),
+            ..GnssFix::default()
+        })
+    }
+
+    /// Anzahl Nachrichten, Nummer, Satelliten in Sicht, dann je vier Felder pro Satellit
+    /// (PRN, Elevation, Azimut, SNR), ab NMEA 4.10 am Ende die Signal-ID
+    fn gsv(&mut self, talker: &str, fields: &[&str]) -> Option<Vec<Satellite>> {
+        if fields.len() < 4 {
+            return None;
+        }
+        let total: u32 = field(fields, 1).parse().ok()?;
+        let number: u32 = field(fields, 2).parse().ok()?;
+        let signal = if (fields.len() - 4) % 4 == 1 {
+            fields[fields.len() - 1]
+        } else {
+            ""
+        };
+        let key = format!("{talker}{signal}");
+        if number == 1 {
+            self.pending.insert(key.clone(), Vec::new());
+        }
+        let pending = self.pending.get_mut(&key)?;
+        for group in fields[4..].chunks_exact(4) {
+            let Ok(prn) = group[0].parse() else {
+                continue;
+            };
+            let (system, prn) = system_and_prn(talker, prn);
+            pending.push(Satellite {
+                prn,
+                elevation: group[1].parse().unwrap_or_default(),
+                azimuth: group[2].parse().unwrap_or_default(),
+                signal_strength: group[3].parse().unwrap_or_default(),
+                used: false,
+                system,
+            });
+        }
+        if number < total {
+            return None;
+        }
+        let complete = self.pending.remove(&key)?;
+        self.in_view.insert(key, complete);
+        Some(self.satellites())
+    }
+
+    /// Alle Satelliten in Sicht, bei mehreren Signalen eines Satelliten das stärkste
+    fn satellites(&self) -> Vec<Satellite> {
+        let mut satellites: Vec<Satellite> = Vec::new();
+        for satellite in self.in_view.values().flatten() {
+            match satellites
+                .iter_mut()
+                .find(|known| known.system == satellite.system && known.prn == satellite.prn)
+            {
+                Some(known) => {
+                    known.signal_strength = known.signal_strength.max(satellite.signal_strength)
+                }
+                None => satellites.push(*satellite),
+            }
+        }
+        for satellite in &mut satellites {
+            satellite.used = self
+                .used
+                .values()
+                .flatten()
+                .any(|&(system, prn)| system == satellite.system && prn == satellite.prn);
+        }
+        satellites.sort_by_key(|satellite| (satellite.system, satellite.prn));
+        satellites
+    }
+}
+
+/// Zeit, Status (A/V), Breite, N/S, Länge, O/W, Geschwindigkeit in Knoten, Kurs, Datum, …
+fn rmc(fields: &[&str]) -> GnssFix {
+    if field(fields, 2) != "A" {
+        return GnssFix {
+            mode: Some(Mode::NoFix),
+            ..GnssFix::default()
+        };
+    }
+    // Die Fix-Art (2D/3D) kommt aus GGA bzw. GSA
+    GnssFix {
+        lat: coordinate(field(fields, 3), field(fields, 4)),
+        lon: coordinate(field(fields, 5), field(fields, 6)),
+        speed: field(fields, 7)
+            .parse::<f32>()
+            .ok()
+            .map(|knots| knots * KNOTS_TO_METERS_PER_SECOND),
+        track: field(fields, 8).parse().ok(),
+        time: date_time(field(fields, 9), field(fields, 1)),
+        ..GnssFix::default()
+    }
+}
+
+/// "ddmmyy" und "hhmmss.ss" in UTC
+fn date_time(date: &str, time: &str) -> Option<DateTime<Utc>> {
+    let date = NaiveDate::parse_from_str(date, "%d%m%y").ok()?;
+    let time = NaiveTime::parse_from_str(time, "%H%M%S%.f").ok()?;
+    Some(date.and_time(time).and_utc())
+}
+
+/// Inhalt zwischen '$' und '*', falls die Prüfsumme stimmt
+fn checked(text: &str) -> Option<&str> {
+    let (body, checksum) = text.strip_prefix('$')?.split_once('*')?;
+    let expected = u8::from_str_radix(checksum.get(..2)?, 16).ok()?;
+    (body.bytes().fold(0, |sum, byte| sum ^ byte) == expected).then_some(body)
+}
+
+fn field<'a>(fields: &[&'a str], index: usize) -> &'a str {
+    fields.get(index).copied().unwrap_or_default()
+}
+
+/// "dddmm.mmmm" mit Halbkugel in Grad
+fn coordinate(value: &str, hemisphere: &str) -> Option<f64> {
+    let dot = value.find('.').unwrap_or(value.len());
+    let degrees: f64 = value.get(..dot.checked_sub(2)?)?.parse().ok()?;
+    let minutes: f64 = value[dot - 2..].parse().ok()?;
+    let sign = match hemisphere {
+        "N" | "E" => 1.0,
+        "S" | "W" => -1.0,
+        _ => return None,
+    };
+    Some(sign * (degrees + minutes / 60.0))
+}
+
+/// System aus dem Talker; bei GP/GN entscheidet der Nummernbereich (NMEA bzw. u-blox
+/// erweitert). SBAS wird wie bei gpsd auf 120–151 umgerechnet.
+fn system_and_prn(talker: &str, prn: i32) -> (GnssSystem, i32) {
+    match talker {
+        "GL" => (GnssSystem::Glonass, prn),
+        "GA" => (GnssSystem::Galileo, prn),
+        "GB" | "BD" => (GnssSystem::Beidou, prn),
+        "GQ" | "QZ" => (GnssSystem::Qzss, prn),
+        "GI" => (GnssSystem::Irnss, prn),
+        _ => match prn {
+            33..=64 => (GnssSystem::Sbas, prn + 87),
+            65..=96 => (GnssSystem::Glonass, prn),
+            173..=182 => (GnssSystem::Imes, prn),
+            193..=202 => (GnssSystem::Qzss, prn),
+            301..=336 => (GnssSystem::Galileo, prn - 300),
+            401..=437 => (GnssSystem::Beidou, prn - 400),
+            _ => (GnssSystem::Gps, prn),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Satz mit Prüfsumme und Zeilenende
+    fn sentence(body: &str) -> String {
+        let checksum = body.bytes().fold(0, |sum, byte| sum ^ byte);
+        format!("${body}*{checksum:02X}\r\n")
+    }
+
+    fn parse(parser: &mut NmeaParser, bodies: &[&str]) -> Vec<GnssUpdate> {
+        let mut updates = Vec::new();
+        for body in bodies {
+            parser.feed(sentence(body).as_bytes(), &mut updates);
+        }
+        updates
+    }
+
+    fn as_fix(update: &GnssUpdate) -> &GnssFix {
+        match update {
+            GnssUpdate::Fix(fix) => fix,
+            other => panic!("expected a fix, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn checksum() {
+        let gga = "$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47";
+        assert!(checked(gga).is_some());
+        assert!(checked(&gga.replace("*47", "*48")).is_none());
+        assert!(checked(&gga.replace("545.4", "545.5")).is_none());
+        assert!(checked("$GPGGA,123519").is_none());
+
+        // Ohne gültige Prüfsumme kommt kein Update heraus
+        let mut updates = Vec::new();
+        NmeaParser::default().feed(b"$GPGGA,123519,4807.038,N,01131.000,E,1*00\r\n", &mut updates);
+        assert!(updates.is_empty());
+    }
+
+    #[test]
+    fn gga() {
+        let updates = parse(
+            &mut NmeaParser::default(),
+            &["GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,"],
+        );
+        let [update] = updates.as_slice() else {
+            panic!("expected one update, got {updates:?}");
+        };
+        let fix = as_fix(update);
+        assert_eq!(fix.mode, Some(Mode::Fix3d));
+        assert!((fix.lat.unwrap() - (48.0 + 7.038 / 60.0)).abs() < 1e-9);
+        assert!((fix.lon.unwrap() - (11.0 + 31.0 / 60.0)).abs() < 1e-9);
+        assert_eq!(fix.alt, Some(545.4));
+        assert!((fix.estimated_error_plane.unwrap() - 0.9 * H_UERE).abs() < 1e-4);
+        assert!(fix.epoch);
+
+        let updates = parse(
+            &mut NmeaParser::default(),
+            &["GPGGA,123519,,,,,0,00,,,M,,M,,"],
+        );
+        assert_eq!(as_fix(&updates[0]).mode, Some(Mode::NoFix));
+        assert_eq!(as_fix(&updates[0]).lat, None);
+    }
+
+    #[test]
+    fn rmc() {
+        let updates = parse(
+            &mut NmeaParser::default(),
+            &["GPRMC,123519,A,4807.038,S,01131.000,W,022.4,084.4,230394,003.1,W"],
+        );
+        let fix = as_fix(&updates[0]);
+        assert_eq!(fix.mode, None);
+        assert!((fix.lat.unwrap() + (48.0 + 7.038 / 60.0)).abs() < 1e-9);
+        assert!((fix.lon.unwrap() + (11.0 + 31.0 / 60.0)).abs() < 1e-9);
+        assert!((fix.speed.unwrap() - 22.4 * KNOTS_TO_METERS_PER_SECOND).abs() < 1e-4);
+        assert_eq!(fix.track, Some(84.4));
+        // RMC ergänzt nur, in den Standort geht die Epoche über GGA ein
+        assert!(!fix.epoch);
+        assert_eq!(
+            fix.time,
+            NaiveDate::from_ymd_opt(1994, 3, 23)
+                .and_then(|date| date.and_hms_opt(12, 35, 19))
+                .map(|time| time.and_utc())
+        );
+
+        let updates = parse(&mut NmeaParser::default(), &["GPRMC,123519,V,,,,,,,230394,,"]);
+        assert_eq!(as_fix(&updates[0]).mode, Some(Mode::NoFix));
+    }
+
+    #[test]
+    fn gsa() {
+        let mut parser = NmeaParser::default();
+        let updates = parse(&mut parser, &["GPGSA,A,2,04,05,,09,12,,,24,,,,,2.5,1.3,2.1"]);
+        let fix = as_fix(&updates[0]);
+        // Den Fix selbst meldet GGA
+        assert_eq!(fix.mode, None);
+        assert!((fix.estimated_error_plane.unwrap() - 1.3 * H_UERE).abs() < 1e-4);
+        assert!((fix.estimated_error_altitude.unwrap() - 2.1 * V_UERE).abs() < 1e-4);
+
+        // GGA übernimmt die Fix-Art aus GSA, im 2D-Fix ohne Höhe
+        let updates = parse(
+            &mut parser,
+            &["GPGGA,123519,4807.038,N,01131.000,E,1,05,1.3,545.4,M,46.9,M,,"],
+        );
+        let fix = as_fix(&updates[0]);
+        assert_eq!(fix.mode, Some(Mode::Fix2d));
+        assert_eq!(fix.alt, None);
+
+        let updates = parse(&mut parser, &["GPGSA,A,1,,,,,,,,,,,,,,,"]);
+        assert_eq!(as_fix(&updates[0]).mode, Some(Mode::NoFix));
+    }
+
+    #[test]
+    fn gsv_cycle() {
+        let mut parser = NmeaParser::default();
+        let updates = parse(
+            &mut parser,
+            &[
+                "GPGSA,A,3,12,14,,,,,,,,,,,2.5,1.3,2.1",
+                "GPGSV,2,1,05,01,40,083,46,02,17,308,41,12,07,344,39,14,22,228,45",
+            ],
+        );
+        // Bis zur letzten Nachricht des Zyklus nur die GSA-Lösung
+        assert_eq!(updates.len(), 1);
+
+        let updates = parse(&mut parser, &["GPGSV,2,2,05,40,30,120,35"]);
+        let [GnssUpdate::Satellites(satellites)] = updates.as_slice() else {
+            panic!("expected satellites, got {updates:?}");
+        };
+        let summary: Vec<_> = satellites
+            .iter()
+            .map(|satellite| (satellite.system, satellite.prn, satellite.used))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (GnssSystem::Gps, 1, false),
+                (GnssSystem::Gps, 2, false),
+                (GnssSystem::Gps, 12, true),
+                (GnssSystem::Gps, 14, true),
+                (GnssSystem::Sbas, 127, false),
+            ]
+        );
+        let first = &satellites[0];
+        assert_eq!((first.elevation, first.azimuth, first.signal_strength), (40.0, 83.0, 46.0));
+
+        // Ein neuer Zyklus ersetzt die Satelliten, GLONASS läuft getrennt mit
+        let updates = parse(
+            &mut parser,
+            &["GPGSV,1,1,01,12,08,340,38", "GLGSV,1,1,01,65,50,010,30"],
+        );
+        let Some(GnssUpdate::Satellites(satellites)) = updates.last() else {
+            panic!("expected satellites, got {updates:?}");
+        };
+        let summary: Vec<_> = satellites
+            .iter()
+            .map(|satellite| (satellite.system, satellite.prn))
+            .collect();
+        assert_eq!(summary, [(GnssSystem::Gps, 12), (GnssSystem::Glonass, 65)]);
+    }
+
+    #[test]
+    fn gsv_without_start_or_fields() {
+        let mut parser = NmeaParser::default();
+        // Zweite Nachricht ohne die erste und zu kurze Sätze
+        let updates = parse(
+            &mut parser,
+            &["GPGSV,2,2,05,40,30,120,35", "GPGSV,1", "GPGSV", "GPGSV,1,1"],
+        );
+        assert!(updates.is_empty());
+    }
+}
@@ -0,0 +1,543 @@
+use nalgebra::{DMatrix, DVector, Quaternion, UnitQuaternion, Vector4};
+use serde::{Deserialize, Serialize};
+
+use crate::telescope_position::AltAZPostion;
+
+/// Bis zu dieser Höhe in Grad werden die Terme ausgewertet, am Zenit wächst tan(h) über alle Grenzen
+const MAX_TERM_ALTITUDE: f32 = 89.0;
+/// Singulärwerte unter diesem Anteil des größten gelten beim Ausgleich als null
+const SVD_RELATIVE_EPSILON: f64 = 1e-6;
+/// CA, NPAE und TF hängen nur über die Höhe von IA bzw. IE ab. Sie werden erst bestimmt,
+/// wenn die Höhen der Sterne mindestens so weit auseinanderliegen, in Grad.
+const MIN_ALTITUDE_SPAN: f32 = 20.0;
+/// Ein neuer Stern ersetzt die bisherigen, die am Himmel näher als dies liegen, in Grad
+const REPLACE_DISTANCE: f32 = 3.0;
+/// Höchstens so viele Sterne, darüber fällt der älteste heraus
+const MAX_STARS: usize = 50;
+
+/// Terme des Pointing-Modells einer Alt-Az-Montierung, Namen wie in TPoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum PointingTerm {
+    /// Indexfehler im Azimut
+    Ia,
+    /// Indexfehler in der Höhe
+    Ie,
+    /// Kollimationsfehler, optische Achse nicht senkrecht zur Höhenachse
+    Ca,
+    /// Höhenachse nicht senkrecht zur Azimutachse
+    Npae,
+    /// Durchbiegung des Tubus
+    Tf,
+}
+
+impl PointingTerm {
+    pub const ALL: [PointingTerm; 5] = [
+        PointingTerm::Ia,
+        PointingTerm::Ie,
+        PointingTerm::Ca,
+        PointingTerm::Npae,
+        PointingTerm::Tf,
+    ];
+
+    /// Terme, die sich aus den Referenzsternen überbestimmt ermitteln lassen: bis zu zwei
+    /// Sternen oder bei zu geringem Höhenbereich nur die Indexfehler
+    fn fitted(stars: &[AlignmentStar]) -> &'static [PointingTerm] {
+        let lowest = stars.iter().map(|star| star.alt).fold(f32::INFINITY, f32::min);
+        let highest = stars.iter().map(|star| star.alt).fold(f32::NEG_INFINITY, f32::max);
+        match stars.len() {
+            0 => &[],
+            1 | 2 => &[PointingTerm::Ia, PointingTerm::Ie],
+            _ if highest - lowest < MIN_ALTITUDE_SPAN => &[PointingTerm::Ia, PointingTerm::Ie],
+            _ => &PointingTerm::ALL,
+        }
+    }
+
+    /// Beitrag eines Terms von 1° zur Ablage in Höhe und Azimut (am Himmel, also ΔA·cos h)
+    /// bei der Höhe `alt` in Radiant
+    fn coefficients(&self, alt: f64) -> (f64, f64) {
+        match self {
+            PointingTerm::Ia => (0.0, alt.cos()),
+            PointingTerm::Ie => (1.0, 0.0),
+            PointingTerm::Ca => (0.0, 1.0),
+            PointingTerm::Npae => (0.0, alt.sin()),
+            PointingTerm::Tf => (alt.cos(), 0.0),
+        }
+    }
+}
+
+/// Ablage der Achsstellung gegenüber dem Himmel, alle Terme in Grad:
+///
+/// ΔA = IA + CA / cos h + NPAE · tan h
+///
+/// Δh = IE + TF · cos h
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "UPPERCASE", default, deny_unknown_fields)]
+pub struct PointingModel {
+    pub ia: f32,
+    pub ie: f32,
+    pub ca: f32,
+    pub npae: f32,
+    pub tf: f32,
+}
+
+impl PointingModel {
+    pub fn get(&self, term: PointingTerm) -> f32 {
+        match term {
+            PointingTerm::Ia => self.ia,
+            PointingTerm::Ie => self.ie,
+            PointingTerm::Ca => self.ca,
+            PointingTerm::Npae => self.npae,
+            PointingTerm::Tf => self.tf,
+        }
+    }
+
+    fn set(&mut self, term: PointingTerm, value: f32) {
+        match term {
+            PointingTerm::Ia => self.ia = value,
+            PointingTerm::Ie => self.ie = value,
+            PointingTerm::Ca => self.ca = value,
+            PointingTerm::Npae => self.npae = value,
+            PointingTerm::Tf => self.tf = value,
+        }
+    }
+
+    /// (Δh, ΔA) bei der Höhe `alt` in Grad
+    fn offset(&self, alt: f32) -> (f32, f32) {
+        let alt = alt.clamp(-MAX_TERM_ALTITUDE, MAX_TERM_ALTITUDE).to_radians();
+        let d_alt = self.ie + self.tf * alt.cos();
+        let d_az = self.ia + (self.ca + self.npae * alt.sin()) / alt.cos();
+        (d_alt, d_az)
+    }
+
+    /// Achsstellung, unter der ein Ort am Himmel erscheint
+    pub fn mount_position(&self, sky: AltAZPostion) -> AltAZPostion {
+        let (d_alt, d_az) = self.offset(sky.alt);
+        AltAZPostion {
+            alt: sky.alt + d_alt,
+            az: sky.az + d_az,
+        }
+    }
+
+    /// Ort am Himmel, auf den eine Achsstellung zeigt
+    pub fn sky_position(&self, mount: AltAZPostion) -> AltAZPostion {
+        // Die Terme hängen von der Höhe am Himmel ab, wenige Iterationen genügen
+        let mut sky = mount;
+        for _ in 0..10 {
+            let (d_alt, d_az) = self.offset(sky.alt);
+            sky = AltAZPostion {
+                alt: mount.alt - d_alt,
+                az: mount.az - d_az,
+            };
+        }
+        sky
+    }
+
+    /// Gleicht die Terme, die sich aus den Sternen bestimmen lassen, nach kleinsten Quadraten aus
+    pub fn fit(stars: &[AlignmentStar]) -> PointingModel {
+        let terms = PointingTerm::fitted(stars);
+        let mut model = PointingModel::default();
+        if terms.is_empty() {
+            return model;
+        }
+        let design = design_matrix(stars, terms);
+        let observed = DVector::from_iterator(
+            2 * stars.len(),
+            stars.iter().flat_map(|star| {
+                let (d_alt, d_az) = star.offset();
+                [d_alt as f64, d_az as f64]
+            }),
+        );
+        // SVD statt Normalgleichungen, fast abhängige Spalten bleiben so bei null statt
+        // sich mit großen Werten gegenseitig aufzuheben
+        let svd = design.svd(true, true);
+        let epsilon = SVD_RELATIVE_EPSILON * svd.singular_values.max();
+        let Ok(solution) = svd.solve(&observed, epsilon) else {
+            return model;
+        };
+        for (term, value) in terms.iter().zip(solution.iter()) {
+            model.set(*term, *value as f32);
+        }
+        model
+    }
+
+    /// Verschiebung der Indexterme IA und IE, mit der `star` exakt getroffen wird. Nach einem
+    /// Sync meldet die Montierung damit genau den synchronisierten Ort, die übrigen Sterne
+    /// bestimmen weiterhin die Form des Modells.
+    pub fn anchor(&self, star: &AlignmentStar) -> IndexOffset {
+        let predicted = self.mount_position(star.sky());
+        IndexOffset {
+            ia: wrap_degrees(star.mount_az - predicted.az),
+            ie: star.mount_alt - predicted.alt,
+        }
+    }
+
+    /// Modell mit um `offset` verschobenen Indextermen
+    pub fn shifted(&self, offset: IndexOffset) -> PointingModel {
+        PointingModel {
+            ia: self.ia + offset.ia,
+            ie: self.ie + offset.ie,
+            ..*self
+        }
+    }
+
+    /// Restfehler der Sterne unter diesem, dem ausgeglichenen Modell und Unsicherheit der
+    /// Terme. `anchor` wird nur mitgemeldet, Restfehler und Sigmas gelten ohne ihn.
+    pub fn statistics(&self, stars: &[AlignmentStar], anchor: Option<IndexOffset>) -> ModelStatistics {
+        let residuals: Vec<StarResidual> = stars
+            .iter()
+            .map(|star| {
+                let predicted = self.mount_position(star.sky());
+                let cos_alt = star.alt.to_radians().cos();
+                StarResidual {
+                    alt: star.alt,
+                    az: star.az,
+                    d_alt: star.mount_alt - predicted.alt,
+                    d_az: wrap_degrees(star.mount_az - predicted.az) * cos_alt,
+                }
+            })
+            .collect();
+        let sum_alt: f32 = residuals.iter().map(|residual| residual.d_alt.powi(2)).sum();
+        let sum_az: f32 = residuals.iter().map(|residual| residual.d_az.powi(2)).sum();
+        let count = residuals.len() as f32;
+        let rms_alt = (!residuals.is_empty()).then(|| (sum_alt / count).sqrt());
+        let rms_az = (!residuals.is_empty()).then(|| (sum_az / count).sqrt());
+
+        let fitted = PointingTerm::fitted(stars);
+        let sigmas = term_sigmas(stars, fitted, (sum_alt + sum_az) as f64);
+        let terms = PointingTerm::ALL
+            .iter()
+            .map(|term| TermStatistics {
+                term: *term,
+                value: self.get(*term),
+                fitted: fitted.contains(term),
+                sigma: fitted
+                    .iter()
+                    .position(|fitted| fitted == term)
+                    .and_then(|index| sigmas.as_ref().map(|sigmas| sigmas[index])),
+            })
+            .collect();
+
+        ModelStatistics {
+            stars: stars.len(),
+            terms,
+            rms_alt,
+            rms_az,
+            rms: rms_alt.zip(rms_az).map(|(alt, az)| (alt * alt + az * az).sqrt()),
+            residuals,
+            anchor,
+        }
+    }
+}
+
+/// Zwei Zeilen je Stern: Höhe und Azimut am Himmel
+fn design_matrix(stars: &[AlignmentStar], terms: &[PointingTerm]) -> DMatrix<f64> {
+    let mut design = DMatrix::zeros(2 * stars.len(), terms.len());
+    for (row, star) in stars.iter().enumerate() {
+        let alt = (star.alt.clamp(-MAX_TERM_ALTITUDE, MAX_TERM_ALTITUDE) as f64).to_radians();
+        for (column, term) in terms.iter().enumerate() {
+            let (alt_coefficient, az_coefficient) = term.coefficients(alt);
+            design[(2 * row, column)] = alt_coefficient;
+            design[(2 * row + 1, column)] = az_coefficient;
+        }
+    }
+    design
+}
+
+/// Standardabweichungen aus der Kovarianzmatrix, nur bei Überbestimmung
+fn term_sigmas(stars: &[AlignmentStar], terms: &[PointingTerm], sum_squares: f64) -> Option<Vec<f32>> {
+    let degrees_of_freedom = (2 * stars.len()).checked_sub(terms.len())?;
+    if terms.is_empty() || degrees_of_freedom == 0 {
+        return None;
+    }
+    let design = design_matrix(stars, terms);
+    let covariance = (design.transpose() * design).try_inverse()?;
+    let variance = sum_squares / degrees_of_freedom as f64;
+    Some(
+        (0..terms.len())
+            .map(|index| (variance * covariance[(index, index)]).sqrt() as f32)
+            .collect(),
+    )
+}
+
+/// Faltet einen Winkel auf ±180°
+fn wrap_degrees(angle: f32) -> f32 {
+    (angle + 180.0).rem_euclid(360.0) - 180.0
+}
+
+/// Verschiebung von IA und IE nach dem letzten Sync, in Grad. Sie wird getrennt vom
+/// ausgeglichenen Modell gespeichert und erst beim Zeigen addiert.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "UPPERCASE", deny_unknown_fields)]
+pub struct IndexOffset {
+    pub ia: f32,
+    pub ie: f32,
+}
+
+/// Bei der Synchronisation auf einen Stern festgehaltene Positionen, alle in Grad
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AlignmentStar {
+    /// Ort des Sterns zum Zeitpunkt der Synchronisation
+    pub alt: f32,
+    pub az: f32,
+    /// Achsstellung der Montierung
+    pub mount_alt: f32,
+    pub mount_az: f32,
+    /// Unkorrigierte Lage des Sensors als [w, x, y, z]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sensor: Option<[f32; 4]>,
+}
+
+impl AlignmentStar {
+    fn sky(&self) -> AltAZPostion {
+        AltAZPostion {
+            alt: self.alt,
+            az: self.az,
+        }
+    }
+
+    /// Gemessene Ablage (Δh, ΔA·cos h)
+    fn offset(&self) -> (f32, f32) {
+        (
+            self.mount_alt - self.alt,
+            wrap_degrees(self.mount_az - self.az) * self.alt.to_radians().cos(),
+        )
+    }
+}
+
+/// Hängt einen Stern an. Ein erneuter Sync in derselben Gegend ersetzt den alten Stern,
+/// statt ihn doppelt zu gewichten, und über `MAX_STARS` fällt der älteste heraus.
+pub fn add_star(stars: &mut Vec<AlignmentStar>, star: AlignmentStar) {
+    stars.retain(|other| separation(other.sky(), star.sky()) >= REPLACE_DISTANCE);
+    stars.push(star);
+    if stars.len() > MAX_STARS {
+        stars.drain(..stars.len() - MAX_STARS);
+    }
+}
+
+/// Winkelabstand zweier Orte in Grad
+fn separation(a: AltAZPostion, b: AltAZPostion) -> f32 {
+    let (a_alt, b_alt) = (a.alt.to_radians(), b.alt.to_radians());
+    let cos = a_alt.sin() * b_alt.sin()
+        + a_alt.cos() * b_alt.cos() * (a.az - b.az).to_radians().cos();
+    cos.clamp(-1.0, 1.0).acos().to_degrees()
+}
+
+/// Drehung im Sensorsystem, die die gemessenen Lagen bestmöglich auf die Sternörter abbildet.
+/// Der Rollwinkel des Sensors bleibt erhalten, bei einem Stern ist die Drehung exakt.
+pub fn fit_correction(stars: &[AlignmentStar]) -> Option<UnitQuaternion<f32>> {
+    let mut sum = Vector4::zeros();
+    for star in stars {
+        let Some([w, x, y, z]) = star.sensor else {
+            continue;
+        };
+        let measured = UnitQuaternion::from_quaternion(Quaternion::new(w, x, y, z));
+        let (roll, _, _) = measured.euler_angles();
+        let truth =
+            UnitQuaternion::from_euler_angles(roll, star.alt.to_radians(), star.az.to_radians());
+        let correction = (measured.inverse() * truth).into_inner().coords;
+        // q und -q beschreiben dieselbe Drehung, vor dem Mitteln auf eine Hemisphäre bringen
+        if sum.dot(&correction) < 0.0 {
+            sum -= correction;
+        } else {
+            sum += correction;
+        }
+    }
+    UnitQuaternion::try_new(Quaternion::from(sum), f32::EPSILON)
+}
+
+/// Referenzsterne und das daraus bestimmte Modell, `[alignment]`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AlignmentConfig {
+    /// Drehung im Sensorsystem als [w, x, y, z], siehe `AlignmentData.correction`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correction: Option<[f32; 4]>,
+    /// Aus den Sternen ausgeglichen bzw. eingetragen, ohne `anchor`
+    #[serde(default)]
+    pub model: PointingModel,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<IndexOffset>,
+    #[serde(default)]
+    pub stars: Vec<AlignmentStar>,
+}
+
+impl AlignmentConfig {
+    /// Modell, mit dem gezeigt wird
+    pub fn pointing_model(&self) -> PointingModel {
+        self.anchor.map_or(self.model, |offset| self.model.shifted(offset))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct TermStatistics {
+    pub term: PointingTerm,
+    /// in Grad
+    pub value: f32,
+    /// Aus den Sternen bestimmt, sonst eingetragen bzw. null
+    pub fitted: bool,
+    /// Standardabweichung in Grad, nur wenn mehr Messungen als Terme vorliegen
+    pub sigma: Option<f32>,
+}
+
+/// Verbleibende Ablage eines Sterns am Himmel, in Grad
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct StarResidual {
+    pub alt: f32,
+    pub az: f32,
+    pub d_alt: f32,
+    /// ΔA·cos h
+    pub d_az: f32,
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct ModelStatistics {
+    pub stars: usize,
+    pub terms: Vec<TermStatistics>,
+    pub residuals: Vec<StarResidual>,
+    /// Quadratisches Mittel der Restfehler in Grad
+    pub rms_alt: Option<f32>,
+    pub rms_az: Option<f32>,
+    pub rms: Option<f32>,
+    /// Zusätzlich angewandte Verschiebung von IA/IE seit dem letzten Sync
+    pub anchor: Option<IndexOffset>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODEL: PointingModel = PointingModel {
+        ia: 0.5,
+        ie: -0.3,
+        ca: 0.2,
+        npae: 0.1,
+        tf: 0.15,
+    };
+
+    /// Sterne, deren Achsstellungen genau `model` folgen
+    fn stars(model: &PointingModel, places: &[(f32, f32)]) -> Vec<AlignmentStar> {
+        places
+            .iter()
+            .map(|&(alt, az)| {
+                let mount = model.mount_position(AltAZPostion { alt, az });
+                AlignmentStar {
+                    alt,
+                    az,
+                    mount_alt: mount.alt,
+                    mount_az: mount.az,
+                    sensor: None,
+                }
+            })
+            .collect()
+    }
+
+    fn assert_model(actual: PointingModel, expected: PointingModel) {
+        for term in PointingTerm::ALL {
+            let (actual, expected) = (actual.get(term), expected.get(term));
+            assert!((actual - expected).abs() < 1e-3, "{term:?}: {actual} vs {expected}");
+        }
+    }
+
+    #[test]
+    fn mount_and_sky_position_are_inverse() {
+        let sky = AltAZPostion { alt: 35.0, az: 120.0 };
+        let mount = MODEL.mount_position(sky);
+        assert!((mount.alt - (35.0 - 0.3 + 0.15 * 35f32.to_radians().cos())).abs() < 1e-5);
+        let back = MODEL.sky_position(mount);
+        assert!((back.alt - sky.alt).abs() < 1e-4 && (back.az - sky.az).abs() < 1e-4);
+    }
+
+    #[test]
+    fn fit_recovers_all_terms() {
+        let stars = stars(
+            &MODEL,
+            &[(15.0, 20.0), (30.0, 110.0), (45.0, 200.0), (60.0, 290.0), (75.0, 350.0), (25.0, 250.0)],
+        );
+        let model = PointingModel::fit(&stars);
+        assert_model(model, MODEL);
+
+        let statistics = model.statistics(&stars, None);
+        assert!(statistics.rms.unwrap() < 1e-4);
+        assert!(statistics.terms.iter().all(|term| term.fitted && term.sigma.is_some()));
+    }
+
+    #[test]
+    fn few_stars_fit_index_terms_only() {
+        let index = PointingModel {
+            ia: 0.5,
+            ie: -0.3,
+            ..Default::default()
+        };
+        // Zwei Sterne ergeben vier Gleichungen, die nur IA und IE überbestimmen
+        let two = stars(&MODEL, &[(20.0, 40.0), (70.0, 220.0)]);
+        let model = PointingModel::fit(&two);
+        assert_eq!((model.ca, model.npae, model.tf), (0.0, 0.0, 0.0));
+        assert_model(PointingModel::fit(&stars(&index, &[(20.0, 40.0), (70.0, 220.0)])), index);
+
+        // Ebenso bei zu kleinem Höhenbereich
+        let narrow = stars(&index, &[(40.0, 10.0), (45.0, 130.0), (50.0, 250.0), (42.0, 300.0)]);
+        let model = PointingModel::fit(&narrow);
+        assert_model(model, index);
+        let statistics = model.statistics(&narrow, None);
+        let fitted: Vec<PointingTerm> = statistics
+            .terms
+            .iter()
+            .filter(|term| term.fitted)
+            .map(|term| term.term)
+            .collect();
+        assert_eq!(fitted, [PointingTerm::Ia, PointingTerm::Ie]);
+
+        assert_eq!(PointingModel::fit(&[]), PointingModel::default());
+    }
+
+    #[test]
+    fn anchor_hits_the_synced_star() {
+        let stars = stars(
+            &MODEL,
+            &[(15.0, 20.0), (30.0, 110.0), (45.0, 200.0), (60.0, 290.0), (75.0, 350.0)],
+        );
+        let model = PointingModel::fit(&stars);
+        // Nach dem Sync ist die Montierung um 0.2° im Azimut verstellt
+        let synced = AlignmentStar {
+            mount_az: stars[2].mount_az + 0.2,
+            ..stars[2]
+        };
+        let offset = model.anchor(&synced);
+        assert!((offset.ia - 0.2).abs() < 1e-4 && offset.ie.abs() < 1e-4, "{offset:?}");
+
+        let config = AlignmentConfig {
+            model,
+            anchor: Some(offset),
+            stars: stars.clone(),
+            ..Default::default()
+        };
+        let mount = config.pointing_model().mount_position(synced.sky());
+        assert!((mount.az - synced.mount_az).abs() < 1e-4);
+        assert!((mount.alt - synced.mount_alt).abs() < 1e-4);
+
+        // Die Statistik bewertet das ausgeglichene Modell, der Anker wird nur gemeldet
+        let statistics = model.statistics(&stars, Some(offset));
+        assert!(statistics.rms.unwrap() < 1e-4);
+        assert_eq!(statistics.anchor, Some(offset));
+        assert_eq!(statistics.terms[0].value, model.ia);
+    }
+
+    #[test]
+    fn add_star_replaces_nearby_stars() {
+        let mut list = Vec::new();
+        for (alt, az) in [(30.0, 100.0), (50.0, 200.0), (31.0, 101.0)] {
+            add_star(&mut list, stars(&MODEL, &[(alt, az)])[0]);
+        }
+        assert_eq!(list.len(), 2);
+        assert_eq!((list[0].alt, list[1].alt), (50.0, 31.0));
+
+        let mut list = Vec::new();
+        for index in 0..MAX_STARS + 5 {
+            add_star(&mut list, stars(&MODEL, &[(10.0 + index as f32, 7.0 * index as f32)])[0]);
+        }
+        assert_eq!(list.len(), MAX_STARS);
+        assert_eq!(list[0].alt, 15.0);
+    }
+}
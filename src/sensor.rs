@@ -0,0 +1,235 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use nalgebra::UnitQuaternion;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::bno055_sensor::Bno055Sensor;
+use crate::mpu9250::Mpu9250;
+use crate::storage::Storage;
+
+/// Wartezeit, bevor ein ausgefallener Sensor neu initialisiert wird
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+/// So oft wird gefragt, ob sich das Kalibrierprofil geändert hat
+const CALIBRATION_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Welcher Lagesensor verbaut ist, `[sensors] orientation`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SensorKind {
+    /// BNO055 mit eigener Sensorfusion
+    #[default]
+    Bno055,
+    /// MPU-9250 mit AK8963, fusioniert mit dem Mahony-Filter
+    Mpu9250,
+}
+
+impl SensorKind {
+    /// Abschnitt unter `[sensors]`
+    pub fn key(&self) -> &'static str {
+        match self {
+            SensorKind::Bno055 => "bno055",
+            SensorKind::Mpu9250 => "mpu9250",
+        }
+    }
+}
+
+/// Kalibrierzustand wie beim BNO055, jeweils 0 (unkalibriert) bis 3 (vollständig)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, utoipa::ToSchema)]
+pub struct CalibrationStatus {
+    pub system: u8,
+    pub gyroscope: u8,
+    pub accelerometer: u8,
+    pub magnetometer: u8,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct OrientationReading {
+    /// Drehung vom Sensor- ins Erdsystem
+    pub quaternion: UnitQuaternion<f32>,
+    pub calibration: CalibrationStatus,
+}
+
+/// Quelle der Lage des Teleskops. Die Methoden blockieren für die Dauer eines I2C-Zugriffs.
+pub trait OrientationSensor: Send {
+    fn kind(&self) -> SensorKind;
+    /// Messungen pro Sekunde
+    fn sample_rate(&self) -> f32;
+    fn read(&mut self) -> Result<OrientationReading, SensorError>;
+    /// Kalibrierprofil, das gespeichert werden soll, falls es sich seit dem letzten Aufruf geändert hat
+    fn changed_calibration_profile(&mut self) -> Result<Option<Vec<u8>>, SensorError> {
+        Ok(None)
+    }
+}
+
+/// Schritt, an dem der Sensor gescheitert ist
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, utoipa::ToSchema)]
+pub enum SensorStage {
+    Config,
+    Bus,
+    Init,
+    Mode,
+    Calibration,
+    Read,
+}
+
+#[derive(Debug, Clone)]
+pub struct SensorError {
+    pub stage: SensorStage,
+    pub message: String,
+}
+
+impl SensorError {
+    pub fn new(stage: SensorStage, error: impl fmt::Display) -> Self {
+        SensorError {
+            stage,
+            message: error.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for SensorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}", self.stage, self.message)
+    }
+}
+
+/// Zustand des Lagesensors für die API
+#[derive(Debug, Clone, Default, Serialize, utoipa::ToSchema)]
+#[serde(tag = "state")]
+pub enum SensorStatus {
+    #[default]
+    Starting,
+    Running {
+        sensor: SensorKind,
+        sample_rate: f32,
+        calibration: CalibrationStatus,
+    },
+    /// Letzter Fehler, die Initialisierung wird nach `RETRY_INTERVAL` wiederholt
+    Failed {
+        stage: SensorStage,
+        message: String,
+    },
+}
+
+/// Liest den Lagesensor und startet ihn nach Fehlern neu, statt den Task zu beenden
+pub(crate) async fn handle_orientation_sensor(storage: &Storage) -> anyhow::Result<()> {
+    loop {
+        storage.set_sensor_status(SensorStatus::Starting).await;
+        if let Err(error) = run_sensor(storage).await {
+            println!("Orientation sensor failed at {error}");
+            storage
+                .set_sensor_status(SensorStatus::Failed {
+                    stage: error.stage,
+                    message: error.message,
+                })
+                .await;
+        }
+        tokio::time::sleep(RETRY_INTERVAL).await;
+    }
+}
+
+async fn open_sensor(storage: &Storage) -> Result<Box<dyn OrientationSensor>, SensorError> {
+    let config_error = |e: anyhow::Error| SensorError::new(SensorStage::Config, e);
+    let kind = storage.get_sensor_kind().await.map_err(config_error)?;
+    let calibration = storage.get_sensor_calibration(kind).await;
+
+    // Die Initialisierung wartet blockierend auf den Sensor
+    let sensor: Result<Box<dyn OrientationSensor>, SensorError> = match kind {
+        SensorKind::Bno055 => {
+            let config = storage.get_bno055_config().await.map_err(config_error)?;
+            tokio::task::spawn_blocking(move || {
+                Bno055Sensor::new(&config, calibration)
+                    .map(|sensor| Box::new(sensor) as Box<dyn OrientationSensor>)
+            })
+            .await
+        }
+        SensorKind::Mpu9250 => {
+            let config = storage.get_mpu9250_config().await.map_err(config_error)?;
+            tokio::task::spawn_blocking(move || {
+                Mpu9250::new(&config).map(|sensor| Box::new(sensor) as Box<dyn OrientationSensor>)
+            })
+            .await
+        }
+    }
+    .map_err(|e| SensorError::new(SensorStage::Init, e))?;
+    sensor
+}
+
+async fn run_sensor(storage: &Storage) -> Result<(), SensorError> {
+    let sensor = open_sensor(storage).await?;
+    let kind = sensor.kind();
+    let sample_rate = sensor.sample_rate();
+
+    // Die I2C-Zugriffe blockieren, bis zu 1 kHz gehören nicht auf einen Tokio-Worker
+    let (sender, mut receiver) = mpsc::channel(16);
+    let reader = tokio::task::spawn_blocking(move || read_sensor(sensor, sender));
+
+    while let Some(event) = receiver.recv().await {
+        match event {
+            SensorEvent::Reading(reading) => {
+                let dec = storage.magnetic_data.get_declination().await.to_radians();
+                // Rotation um Z-Achse
+                let declination_rotation =
+                    UnitQuaternion::from_axis_angle(&nalgebra::Vector3::z_axis(), dec);
+
+                let quat = reading.quaternion * declination_rotation;
+                storage.update_orientation(quat).await;
+                storage
+                    .set_sensor_status(SensorStatus::Running {
+                        sensor: kind,
+                        sample_rate,
+                        calibration: reading.calibration,
+                    })
+                    .await;
+            }
+            SensorEvent::Calibration(profile) => {
+                if let Err(e) = storage.set_sensor_calibration(kind, &profile).await {
+                    println!("Could not save sensor calibration: {e}");
+                }
+            }
+        }
+    }
+    // Der Kanal schließt erst, wenn der Lesethread ausgestiegen ist
+    reader
+        .await
+        .map_err(|e| SensorError::new(SensorStage::Read, e))?
+}
+
+/// Nachricht vom Lesethread an `run_sensor`
+enum SensorEvent {
+    Reading(OrientationReading),
+    /// Geändertes Kalibrierprofil, das gespeichert werden soll
+    Calibration(Vec<u8>),
+}
+
+/// Läuft auf einem eigenen Thread, bis der Sensor einen Fehler meldet
+fn read_sensor(
+    mut sensor: Box<dyn OrientationSensor>,
+    sender: mpsc::Sender<SensorEvent>,
+) -> Result<(), SensorError> {
+    let interval = Duration::from_secs_f32(1.0 / sensor.sample_rate());
+    let mut last_calibration_check = Instant::now();
+
+    loop {
+        let reading = sensor.read()?;
+        if sender.blocking_send(SensorEvent::Reading(reading)).is_err() {
+            return Ok(());
+        }
+
+        // Der BNO055 muss dafür den Modus wechseln, daher nur selten
+        if last_calibration_check.elapsed() >= CALIBRATION_CHECK_INTERVAL {
+            last_calibration_check = Instant::now();
+            if let Some(profile) = sensor.changed_calibration_profile()? {
+                if sender.blocking_send(SensorEvent::Calibration(profile)).is_err() {
+                    return Ok(());
+                }
+            }
+        }
+
+        std::thread::sleep(interval);
+    }
+}
@@ -0,0 +1,163 @@
+use anyhow::{bail, Result};
+use open_pi_scope::gnss::Position;
+use serde::{Deserialize, Serialize};
+
+use crate::site_estimator::{EstimatorConfig, SiteEstimate};
+
+/// Ab dieser Abweichung in m wird die letzte gültige Position neu gespeichert, damit
+/// nicht jede Lösung die Konfiguration schreibt
+const LAST_FIX_DISTANCE: f64 = 100.0;
+const LAST_FIX_ELEVATION: f32 = 50.0;
+pub(crate) const METERS_PER_DEGREE: f64 = 111_320.0;
+
+/// Woher der Standort kommt, `[site] policy`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SitePolicy {
+    /// Nur `[site.manual]`
+    Manual,
+    /// Nur GNSS, ohne Lösung die letzte gültige Position
+    Gnss,
+    /// GNSS, ohne Lösung `[site.manual]` und danach die letzte gültige Position
+    #[default]
+    GnssFallback,
+}
+
+impl SitePolicy {
+    /// Wert von `[site] policy`
+    pub fn key(&self) -> &'static str {
+        match self {
+            SitePolicy::Manual => "manual",
+            SitePolicy::Gnss => "gnss",
+            SitePolicy::GnssFallback => "gnss_fallback",
+        }
+    }
+}
+
+/// Standort in Grad, Höhe in m über NN
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SiteLocation {
+    pub latitude: f64,
+    pub longitude: f64,
+    #[serde(default)]
+    pub elevation: f32,
+}
+
+impl SiteLocation {
+    /// Bereiche wie bei ASCOM
+    pub fn validate(&self) -> Result<()> {
+        if !self.latitude.is_finite() || !(-90.0..=90.0).contains(&self.latitude) {
+            bail!("Latitude {} is outside -90..90°", self.latitude);
+        }
+        if !self.longitude.is_finite() || !(-180.0..=180.0).contains(&self.longitude) {
+            bail!("Longitude {} is outside -180..180°", self.longitude);
+        }
+        if !self.elevation.is_finite() || !(-300.0..=10000.0).contains(&self.elevation) {
+            bail!("Elevation {} is outside -300..10000 m", self.elevation);
+        }
+        Ok(())
+    }
+
+    /// Lohnt es sich, `self` statt `stored` als letzte Position zu speichern?
+    pub fn moved_from(&self, stored: &SiteLocation) -> bool {
+        let north = (self.latitude - stored.latitude) * METERS_PER_DEGREE;
+        let east = (self.longitude - stored.longitude)
+            * METERS_PER_DEGREE
+            * self.latitude.to_radians().cos();
+        north.hypot(east) > LAST_FIX_DISTANCE
+            || (self.elevation - stored.elevation).abs() > LAST_FIX_ELEVATION
+    }
+}
+
+impl From<SiteLocation> for Position {
+    fn from(location: SiteLocation) -> Self {
+        Position {
+            latitude: location.latitude,
+            longitude: location.longitude,
+            altitude: location.elevation,
+        }
+    }
+}
+
+/// Herkunft des verwendeten Standorts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SiteSource {
+    Gnss,
+    Manual,
+    LastFix,
+    /// Kein Standort bekannt, es wird mit 0°/0° gerechnet
+    None,
+}
+
+/// `[site]`
+#[derive(Debug, Clone, Default, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SiteConfig {
+    #[serde(default)]
+    pub policy: SitePolicy,
+    /// Von Hand bzw. über ASCOM oder die HTTP-API eingetragen
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manual: Option<SiteLocation>,
+    /// Letzte gültige GNSS-Position, wird automatisch geschrieben
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_fix: Option<SiteLocation>,
+    #[serde(default)]
+    pub estimate: EstimatorConfig,
+}
+
+impl SiteConfig {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(manual) = &self.manual {
+            manual.validate().map_err(|e| anyhow::anyhow!("site.manual: {e}"))?;
+        }
+        if let Some(last_fix) = &self.last_fix {
+            last_fix.validate().map_err(|e| anyhow::anyhow!("site.last_fix: {e}"))?;
+        }
+        self.estimate.validate()?;
+        if self.policy == SitePolicy::Manual && self.manual.is_none() {
+            bail!("site: policy \"manual\" needs a [site.manual] location");
+        }
+        Ok(())
+    }
+
+    /// Wählt den Standort nach `policy`; `gnss` ist der gemittelte GNSS-Standort, solange
+    /// der Empfänger einen Fix hat
+    pub fn resolve(&self, gnss: Option<SiteLocation>) -> (SiteLocation, SiteSource) {
+        let candidates = match self.policy {
+            SitePolicy::Manual => vec![(self.manual, SiteSource::Manual)],
+            SitePolicy::Gnss => vec![
+                (gnss, SiteSource::Gnss),
+                (self.last_fix, SiteSource::LastFix),
+            ],
+            SitePolicy::GnssFallback => vec![
+                (gnss, SiteSource::Gnss),
+                (self.manual, SiteSource::Manual),
+                (self.last_fix, SiteSource::LastFix),
+            ],
+        };
+        candidates
+            .into_iter()
+            .find_map(|(location, source)| Some((location?, source)))
+            .unwrap_or((
+                SiteLocation {
+                    latitude: 0.0,
+                    longitude: 0.0,
+                    elevation: 0.0,
+                },
+                SiteSource::None,
+            ))
+    }
+}
+
+/// Antwort von GET /api/site
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct SiteStatus {
+    /// Verwendeter Standort
+    pub location: SiteLocation,
+    pub source: SiteSource,
+    /// Gemittelter GNSS-Standort, auch wenn er gerade nicht verwendet wird
+    pub estimate: Option<SiteEstimate>,
+    pub config: SiteConfig,
+}
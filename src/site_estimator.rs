@@ -0,0 +1,423 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use open_pi_scope::gnss::Mode;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use crate::site::{SiteLocation, METERS_PER_DEGREE};
+
+/// GNSS-Fehler hängen über etwa diese Zeit zusammen, in s. Innerhalb davon zählen
+/// mehrere Lösungen für die Unsicherheit nur einmal.
+const CORRELATION_TIME: f64 = 60.0;
+/// Springt eine Lösung um mehr als diese Strecke in m, wurde die Montierung umgestellt
+/// und der Mittelwert beginnt neu
+const RESET_DISTANCE: f64 = 1000.0;
+
+fn default_min_mode() -> Mode {
+    Mode::Fix3d
+}
+
+fn default_max_error_plane() -> f32 {
+    50.0
+}
+
+fn default_max_error_altitude() -> f32 {
+    100.0
+}
+
+fn default_max_samples() -> usize {
+    3600
+}
+
+/// `[site.estimate]`: welche Lösungen in den gemittelten Standort eingehen
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct EstimatorConfig {
+    /// "Fix2d" oder "Fix3d"
+    #[serde(default = "default_min_mode")]
+    pub min_mode: Mode,
+    /// Größter geschätzter Lagefehler in m
+    #[serde(default = "default_max_error_plane")]
+    pub max_error_plane: f32,
+    /// Größter geschätzter Höhenfehler in m, schlechtere Höhen bleiben unberücksichtigt
+    #[serde(default = "default_max_error_altitude")]
+    pub max_error_altitude: f32,
+    /// Lösungen bzw. Höhen ohne Fehlerangabe aufnehmen und mit `max_error_plane` bzw.
+    /// `max_error_altitude` gewichten, sonst werden sie verworfen
+    #[serde(default)]
+    pub accept_unknown_error: bool,
+    /// Anzahl der jüngsten Lösungen im Mittelwert
+    #[serde(default = "default_max_samples")]
+    pub max_samples: usize,
+}
+
+impl Default for EstimatorConfig {
+    fn default() -> Self {
+        EstimatorConfig {
+            min_mode: default_min_mode(),
+            max_error_plane: default_max_error_plane(),
+            max_error_altitude: default_max_error_altitude(),
+            accept_unknown_error: false,
+            max_samples: default_max_samples(),
+        }
+    }
+}
+
+impl EstimatorConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.min_mode == Mode::NoFix {
+            bail!("site.estimate: min_mode must be \"Fix2d\" or \"Fix3d\"");
+        }
+        for (name, value) in [
+            ("max_error_plane", self.max_error_plane),
+            ("max_error_altitude", self.max_error_altitude),
+        ] {
+            if !value.is_finite() || value <= 0.0 {
+                bail!("site.estimate: {name} must be a positive number, got {value}");
+            }
+        }
+        if self.max_samples == 0 {
+            bail!("site.estimate: max_samples must be at least 1");
+        }
+        Ok(())
+    }
+}
+
+/// Vollständige Lösung einer Epoche
+#[derive(Debug, Clone, Copy)]
+pub struct FixSample {
+    pub mode: Mode,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// `None`, wenn die Lösung keine Höhe enthält
+    pub altitude: Option<f32>,
+    /// in m, `None` wenn der Empfänger keinen Fehler meldet
+    pub error_plane: Option<f32>,
+    pub error_altitude: Option<f32>,
+    pub time: DateTime<Utc>,
+}
+
+/// Angenommene Lösung mit den Fehlern, mit denen sie gewichtet wird
+#[derive(Debug, Clone, Copy)]
+struct Accepted {
+    latitude: f64,
+    longitude: f64,
+    error_plane: f64,
+    /// Nur bei einem 3D-Fix mit ausreichend genauer Höhe
+    altitude: Option<(f64, f64)>,
+    time: DateTime<Utc>,
+}
+
+/// Gemittelter Standort, Unsicherheiten in m
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct SiteEstimate {
+    pub location: SiteLocation,
+    pub horizontal_uncertainty: f32,
+    /// `None` ohne angenommene Höhe, die Höhe kommt dann aus dem gespeicherten Standort
+    pub vertical_uncertainty: Option<f32>,
+    /// Lösungen im Mittelwert
+    pub samples: usize,
+    /// Lösungen, die seit dem Start verworfen wurden
+    pub rejected: u64,
+    /// Grund der letzten verworfenen Lösung
+    pub last_rejection: Option<String>,
+}
+
+/// Mittelt die Lösungen, die `[site.estimate]` genügen, gewichtet mit 1/Fehler²
+#[derive(Debug, Default)]
+pub struct SiteEstimator {
+    samples: VecDeque<Accepted>,
+    rejected: u64,
+    last_rejection: Option<String>,
+    estimate: Option<SiteEstimate>,
+}
+
+impl SiteEstimator {
+    pub fn estimate(&self) -> Option<SiteEstimate> {
+        self.estimate.clone()
+    }
+
+    /// Nimmt eine Lösung auf, falls sie gut genug ist
+    pub fn add(&mut self, config: &EstimatorConfig, fix: FixSample) {
+        if let Err(reason) = self.check(config, &fix) {
+            self.rejected += 1;
+            self.last_rejection = Some(reason);
+            if let Some(estimate) = self.estimate.as_mut() {
+                estimate.rejected = self.rejected;
+                estimate.last_rejection = self.last_rejection.clone();
+            }
+            return;
+        }
+        let error_altitude = known(fix.error_altitude)
+            .or(config.accept_unknown_error.then_some(config.max_error_altitude))
+            .filter(|error| *error <= config.max_error_altitude);
+        let sample = Accepted {
+            latitude: fix.latitude,
+            longitude: fix.longitude,
+            error_plane: known(fix.error_plane).unwrap_or(config.max_error_plane) as f64,
+            altitude: fix
+                .altitude
+                .zip(error_altitude)
+                .filter(|_| fix.mode == Mode::Fix3d)
+                .map(|(altitude, error)| (altitude as f64, error as f64)),
+            time: fix.time,
+        };
+        if let Some(estimate) = &self.estimate {
+            let (north, east) = offset(&estimate.location, sample.latitude, sample.longitude);
+            let distance = north.hypot(east);
+            if distance > RESET_DISTANCE {
+                println!("GNSS position moved by {distance:.0} m, restarting the site average");
+                self.samples.clear();
+            }
+        }
+        self.samples.push_back(sample);
+        while self.samples.len() > config.max_samples {
+            self.samples.pop_front();
+        }
+        self.estimate = self.average();
+    }
+
+    fn check(&self, config: &EstimatorConfig, fix: &FixSample) -> Result<(), String> {
+        if fix.mode < config.min_mode {
+            return Err(format!("{:?} is below {:?}", fix.mode, config.min_mode));
+        }
+        match known(fix.error_plane) {
+            Some(error) if error > config.max_error_plane => {
+                return Err(format!(
+                    "Horizontal error {error:.1} m exceeds {:.1} m",
+                    config.max_error_plane
+                ));
+            }
+            None if !config.accept_unknown_error => {
+                return Err("No horizontal error reported".to_owned());
+            }
+            _ => {}
+        }
+        let location = SiteLocation {
+            latitude: fix.latitude,
+            longitude: fix.longitude,
+            elevation: 0.0,
+        };
+        location.validate().map_err(|e| e.to_string())
+    }
+
+    fn average(&self) -> Option<SiteEstimate> {
+        let first = self.samples.front()?;
+        let last = self.samples.back()?;
+        let reference = SiteLocation {
+            latitude: first.latitude,
+            longitude: first.longitude,
+            elevation: 0.0,
+        };
+        let span = (last.time - first.time).as_seconds_f64().max(0.0);
+        // Zusammenhängende Fehler mitteln sich nicht heraus
+        let independent = (1.0 + span / CORRELATION_TIME).min(self.samples.len() as f64);
+
+        let horizontal: Vec<(f64, f64, f64)> = self
+            .samples
+            .iter()
+            .map(|sample| {
+                let (north, east) = offset(&reference, sample.latitude, sample.longitude);
+                (north, east, sample.error_plane)
+            })
+            .collect();
+        let (north, east, horizontal_uncertainty) = weighted_mean_2d(&horizontal, independent);
+
+        let altitudes: Vec<(f64, f64)> =
+            self.samples.iter().filter_map(|sample| sample.altitude).collect();
+        let vertical = (!altitudes.is_empty())
+            .then(|| weighted_mean(&altitudes, independent.min(altitudes.len() as f64)));
+
+        let latitude = reference.latitude + north / METERS_PER_DEGREE;
+        let longitude = reference.longitude
+            + east / (METERS_PER_DEGREE * reference.latitude.to_radians().cos());
+        Some(SiteEstimate {
+            location: SiteLocation {
+                latitude,
+                longitude: (longitude + 540.0).rem_euclid(360.0) - 180.0,
+                elevation: vertical.map_or(0.0, |(altitude, _)| altitude as f32),
+            },
+            horizontal_uncertainty: horizontal_uncertainty as f32,
+            vertical_uncertainty: vertical.map(|(_, uncertainty)| uncertainty as f32),
+            samples: self.samples.len(),
+            rejected: self.rejected,
+            last_rejection: self.last_rejection.clone(),
+        })
+    }
+}
+
+/// Gemeldeter Fehler, ein Fehler von 0 gilt wie ein fehlender als unbekannt
+fn known(error: Option<f32>) -> Option<f32> {
+    error.filter(|error| *error > 0.0)
+}
+
+/// Nord- und Ostabstand in m, über die Datumsgrenze hinweg
+fn offset(reference: &SiteLocation, latitude: f64, longitude: f64) -> (f64, f64) {
+    let north = (latitude - reference.latitude) * METERS_PER_DEGREE;
+    let east = ((longitude - reference.longitude + 540.0).rem_euclid(360.0) - 180.0)
+        * METERS_PER_DEGREE
+        * reference.latitude.to_radians().cos();
+    (north, east)
+}
+
+/// Gewichteter Mittelwert aus (Wert, Fehler) und seine Unsicherheit: der größere von
+/// mittlerem Fehler und Streuung, geteilt durch die Wurzel der unabhängigen Messungen
+fn weighted_mean(values: &[(f64, f64)], independent: f64) -> (f64, f64) {
+    let weight: f64 = values.iter().map(|(_, error)| error.powi(-2)).sum();
+    let mean = values.iter().map(|(value, error)| value * error.powi(-2)).sum::<f64>() / weight;
+    let spread = (values
+        .iter()
+        .map(|(value, error)| (value - mean).powi(2) * error.powi(-2))
+        .sum::<f64>()
+        / weight)
+        .sqrt();
+    let error = (values.len() as f64 / weight).sqrt();
+    (mean, error.max(spread) / independent.sqrt())
+}
+
+/// Wie `weighted_mean` für (Nord, Ost, Lagefehler); der Lagefehler verteilt sich auf
+/// beide Richtungen
+fn weighted_mean_2d(values: &[(f64, f64, f64)], independent: f64) -> (f64, f64, f64) {
+    let axis_error = |error: f64| error / std::f64::consts::SQRT_2;
+    let north: Vec<(f64, f64)> = values
+        .iter()
+        .map(|&(north, _, error)| (north, axis_error(error)))
+        .collect();
+    let east: Vec<(f64, f64)> = values
+        .iter()
+        .map(|&(_, east, error)| (east, axis_error(error)))
+        .collect();
+    let (north, north_uncertainty) = weighted_mean(&north, independent);
+    let (east, east_uncertainty) = weighted_mean(&east, independent);
+    (north, east, north_uncertainty.hypot(east_uncertainty))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+
+    const LATITUDE: f64 = 48.0;
+    const LONGITUDE: f64 = 11.0;
+
+    fn time(seconds: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, 20, 22, 0, 0).unwrap() + Duration::seconds(seconds)
+    }
+
+    /// 3D-Fix `north` m nördlich des Bezugsorts
+    fn fix(north: f64, error_plane: Option<f32>) -> FixSample {
+        FixSample {
+            mode: Mode::Fix3d,
+            latitude: LATITUDE + north / METERS_PER_DEGREE,
+            longitude: LONGITUDE,
+            altitude: Some(500.0),
+            error_plane,
+            error_altitude: Some(5.0),
+            time: time(0),
+        }
+    }
+
+    fn north(estimate: &SiteEstimate) -> f64 {
+        (estimate.location.latitude - LATITUDE) * METERS_PER_DEGREE
+    }
+
+    #[test]
+    fn weights_by_inverse_squared_error() {
+        let config = EstimatorConfig::default();
+        let mut estimator = SiteEstimator::default();
+        estimator.add(&config, fix(0.0, Some(2.0)));
+        estimator.add(&config, fix(10.0, Some(4.0)));
+        let estimate = estimator.estimate().unwrap();
+        // Gewichte 1/4 und 1/16: 10 m · 0.2
+        assert!((north(&estimate) - 2.0).abs() < 1e-6, "{}", north(&estimate));
+        assert!((estimate.location.longitude - LONGITUDE).abs() < 1e-12);
+        assert_eq!(estimate.location.elevation, 500.0);
+        assert_eq!(estimate.samples, 2);
+        // Nach Norden übertrifft die Streuung von 4 m den mittleren Fehler von √3.2 m,
+        // nach Osten bleibt nur dieser
+        let expected = 4f32.hypot(3.2f32.sqrt());
+        assert!((estimate.horizontal_uncertainty - expected).abs() < 1e-3);
+    }
+
+    #[test]
+    fn correlated_fixes_count_once() {
+        let config = EstimatorConfig::default();
+        let mut estimator = SiteEstimator::default();
+        for seconds in 0..60 {
+            estimator.add(&config, FixSample { time: time(seconds), ..fix(0.0, Some(2.0)) });
+        }
+        // Innerhalb einer Minute nur knapp zwei unabhängige Messungen
+        let uncertainty = estimator.estimate().unwrap().horizontal_uncertainty as f64;
+        let independent = 1.0 + 59.0 / CORRELATION_TIME;
+        assert!((uncertainty - 2.0 / independent.sqrt()).abs() < 1e-4, "{uncertainty}");
+    }
+
+    #[test]
+    fn rejects_fixes_without_error() {
+        let mut estimator = SiteEstimator::default();
+        estimator.add(&EstimatorConfig::default(), fix(0.0, None));
+        estimator.add(&EstimatorConfig::default(), fix(0.0, Some(0.0)));
+        assert!(estimator.estimate().is_none());
+        assert_eq!(estimator.rejected, 2);
+        assert_eq!(estimator.last_rejection.as_deref(), Some("No horizontal error reported"));
+
+        // Auf Wunsch mit dem größten erlaubten Fehler gewichtet
+        let config = EstimatorConfig {
+            accept_unknown_error: true,
+            ..Default::default()
+        };
+        estimator.add(&config, fix(0.0, Some(5.0)));
+        estimator.add(&config, fix(100.0, None));
+        let estimate = estimator.estimate().unwrap();
+        assert_eq!(estimate.samples, 2);
+        assert_eq!(estimate.rejected, 2);
+        // Gewichte 1/25 und 1/2500
+        assert!((north(&estimate) - 100.0 / 101.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn error_limits() {
+        let config = EstimatorConfig::default();
+        let mut estimator = SiteEstimator::default();
+        estimator.add(&config, fix(0.0, Some(60.0)));
+        assert_eq!(
+            estimator.last_rejection.as_deref(),
+            Some("Horizontal error 60.0 m exceeds 50.0 m")
+        );
+        estimator.add(&config, FixSample { mode: Mode::Fix2d, ..fix(0.0, Some(2.0)) });
+        assert_eq!(estimator.rejected, 2);
+
+        // Ungenaue Höhen und Höhen eines 2D-Fixes gehen nicht ein
+        estimator.add(&config, FixSample { error_altitude: Some(150.0), ..fix(0.0, Some(2.0)) });
+        let config = EstimatorConfig {
+            min_mode: Mode::Fix2d,
+            ..Default::default()
+        };
+        estimator.add(&config, FixSample { mode: Mode::Fix2d, ..fix(0.0, Some(2.0)) });
+        let estimate = estimator.estimate().unwrap();
+        assert_eq!(estimate.samples, 2);
+        assert_eq!(estimate.vertical_uncertainty, None);
+        assert_eq!(estimate.location.elevation, 0.0);
+    }
+
+    #[test]
+    fn restarts_after_moving_the_mount() {
+        let config = EstimatorConfig {
+            max_samples: 3,
+            ..Default::default()
+        };
+        let mut estimator = SiteEstimator::default();
+        for north in [0.0, 1.0, 2.0, 3.0] {
+            estimator.add(&config, fix(north, Some(2.0)));
+        }
+        let estimate = estimator.estimate().unwrap();
+        assert_eq!(estimate.samples, 3);
+        assert!((north(&estimate) - 2.0).abs() < 1e-6);
+
+        estimator.add(&config, fix(2000.0, Some(2.0)));
+        let estimate = estimator.estimate().unwrap();
+        assert_eq!(estimate.samples, 1);
+        assert!((north(&estimate) - 2000.0).abs() < 1e-6);
+    }
+}
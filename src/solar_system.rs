@@ -0,0 +1,266 @@
+//! Orte von Sonne, Mond und Planeten für das Anfahren aus dem Katalog.
+//!
+//! Die vollständigen VSOP87- und ELP-Reihen liegen noch nicht bei, bis dahin gelten
+//! genäherte Verfahren:
+//! die Planeten aus den Kepler-Elementen von E. M. Standish, "Keplerian Elements for
+//! Approximate Positions of the Major Planets" (JPL, gültig 1800–2050, wenige Bogenminuten),
+//! der Mond aus den Hauptgliedern der ELP-2000/82-Reihe nach Meeus Kap. 47 (~0.1°)
+//! mit topozentrischer Parallaxe nach Meeus Kap. 40.
+
+use chrono::{DateTime, Utc};
+use nalgebra::Vector3;
+use open_pi_scope::gnss::Position;
+use serde::Serialize;
+
+use crate::astronomy::{apparent_to_mean, julian_date, local_sidereal_time, J2000};
+use crate::telescope_position::EqPostion;
+
+/// Schiefe der Ekliptik J2000.0 in Grad
+const OBLIQUITY_J2000: f64 = 23.439_28;
+/// Lichtlaufzeit für 1 AE in Tagen
+const LIGHT_TIME_PER_AU: f64 = 0.005_775_518_3;
+/// Äquatorradius der Erde in km
+const EARTH_RADIUS: f64 = 6_378.14;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, utoipa::ToSchema)]
+pub enum Body {
+    Sun,
+    Moon,
+    Mercury,
+    Venus,
+    Mars,
+    Jupiter,
+    Saturn,
+    Uranus,
+    Neptune,
+}
+
+impl Body {
+    pub const ALL: [Body; 9] = [
+        Body::Sun,
+        Body::Moon,
+        Body::Mercury,
+        Body::Venus,
+        Body::Mars,
+        Body::Jupiter,
+        Body::Saturn,
+        Body::Uranus,
+        Body::Neptune,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Body::Sun => "Sun",
+            Body::Moon => "Moon",
+            Body::Mercury => "Mercury",
+            Body::Venus => "Venus",
+            Body::Mars => "Mars",
+            Body::Jupiter => "Jupiter",
+            Body::Saturn => "Saturn",
+            Body::Uranus => "Uranus",
+            Body::Neptune => "Neptune",
+        }
+    }
+
+    /// Mittlerer Ort J2000.0 vom Standort aus gesehen
+    pub fn position(&self, site: &Position, time: DateTime<Utc>) -> EqPostion {
+        let t = (julian_date(time) - J2000) / 36_525.0;
+        let (ra, dec) = match self {
+            Body::Moon => return moon_position(site, time),
+            Body::Sun => vector_to_equatorial(&-heliocentric(&EARTH, t)),
+            planet => {
+                let elements = planet.elements().expect("planets have orbital elements");
+                let earth = heliocentric(&EARTH, t);
+                // Den Planeten dort nehmen, wo er das Licht ausgesandt hat
+                let mut geocentric = heliocentric(elements, t) - earth;
+                let light_time = geocentric.norm() * LIGHT_TIME_PER_AU / 36_525.0;
+                geocentric = heliocentric(elements, t - light_time) - earth;
+                vector_to_equatorial(&geocentric)
+            }
+        };
+        EqPostion {
+            ra: (ra.to_degrees() / 15.0) as f32,
+            dec: dec.to_degrees() as f32,
+        }
+    }
+
+    fn elements(&self) -> Option<&'static Elements> {
+        match self {
+            Body::Mercury => Some(&MERCURY),
+            Body::Venus => Some(&VENUS),
+            Body::Mars => Some(&MARS),
+            Body::Jupiter => Some(&JUPITER),
+            Body::Saturn => Some(&SATURN),
+            Body::Uranus => Some(&URANUS),
+            Body::Neptune => Some(&NEPTUNE),
+            Body::Sun | Body::Moon => None,
+        }
+    }
+}
+
+/// Bahnelemente zu J2000.0 und ihre Änderung pro Jahrhundert:
+/// a [AE], e, I, L, ϖ (Länge des Perihels), Ω [Grad]
+struct Elements {
+    elements: [f64; 6],
+    rates: [f64; 6],
+}
+
+const MERCURY: Elements = Elements {
+    elements: [0.387_099_27, 0.205_635_93, 7.004_979_02, 252.250_323_50, 77.457_796_28, 48.330_765_93],
+    rates: [0.000_000_37, 0.000_019_06, -0.005_947_49, 149_472.674_111_75, 0.160_476_89, -0.125_340_81],
+};
+const VENUS: Elements = Elements {
+    elements: [0.723_335_66, 0.006_776_72, 3.394_676_05, 181.979_099_50, 131.602_467_18, 76.679_842_55],
+    rates: [0.000_003_90, -0.000_041_07, -0.000_788_90, 58_517.815_387_29, 0.002_683_29, -0.277_694_18],
+};
+/// Schwerpunkt Erde-Mond
+const EARTH: Elements = Elements {
+    elements: [1.000_002_61, 0.016_711_23, -0.000_015_31, 100.464_571_66, 102.937_681_93, 0.0],
+    rates: [0.000_005_62, -0.000_043_92, -0.012_946_68, 35_999.372_449_81, 0.323_273_64, 0.0],
+};
+const MARS: Elements = Elements {
+    elements: [1.523_710_34, 0.093_394_10, 1.849_691_42, -4.553_432_05, -23.943_629_59, 49.559_538_91],
+    rates: [0.000_018_47, 0.000_078_82, -0.008_131_31, 19_140.302_684_99, 0.444_410_88, -0.292_573_43],
+};
+const JUPITER: Elements = Elements {
+    elements: [5.202_887_00, 0.048_386_24, 1.304_396_95, 34.396_440_51, 14.728_479_83, 100.473_909_09],
+    rates: [-0.000_116_07, -0.000_132_53, -0.001_837_14, 3_034.746_127_75, 0.212_526_68, 0.204_691_06],
+};
+const SATURN: Elements = Elements {
+    elements: [9.536_675_94, 0.053_861_79, 2.485_991_87, 49.954_244_23, 92.598_878_31, 113.662_424_48],
+    rates: [-0.001_250_60, -0.000_509_91, 0.001_936_09, 1_222.493_622_01, -0.418_972_16, -0.288_677_94],
+};
+const URANUS: Elements = Elements {
+    elements: [19.189_164_64, 0.047_257_44, 0.772_637_83, 313.238_104_51, 170.954_276_30, 74.016_925_03],
+    rates: [-0.001_961_76, -0.000_043_97, -0.002_429_39, 428.482_027_85, 0.408_052_81, 0.042_405_89],
+};
+const NEPTUNE: Elements = Elements {
+    elements: [30.069_922_76, 0.008_590_48, 1.770_043_47, -55.120_029_69, 44.964_762_27, 131.784_225_74],
+    rates: [0.000_262_91, 0.000_051_05, 0.000_353_72, 218.459_453_25, -0.322_414_64, -0.005_086_64],
+};
+
+/// Heliozentrischer Ort in AE, Ekliptik und Äquinoktium J2000.0
+fn heliocentric(orbit: &Elements, t: f64) -> Vector3<f64> {
+    let [a, e, inclination, mean_longitude, perihelion, node] =
+        std::array::from_fn(|i| orbit.elements[i] + orbit.rates[i] * t);
+    let mean_anomaly = ((mean_longitude - perihelion + 180.0).rem_euclid(360.0) - 180.0).to_radians();
+    let argument = (perihelion - node).to_radians();
+    let (inclination, node) = (inclination.to_radians(), node.to_radians());
+
+    // Kepler-Gleichung nach Newton
+    let mut eccentric = mean_anomaly + e * mean_anomaly.sin();
+    for _ in 0..10 {
+        let delta = (eccentric - e * eccentric.sin() - mean_anomaly) / (1.0 - e * eccentric.cos());
+        eccentric -= delta;
+        if delta.abs() < 1e-12 {
+            break;
+        }
+    }
+    let x = a * (eccentric.cos() - e);
+    let y = a * (1.0 - e * e).sqrt() * eccentric.sin();
+
+    let (sin_w, cos_w) = argument.sin_cos();
+    let (sin_o, cos_o) = node.sin_cos();
+    let (sin_i, cos_i) = inclination.sin_cos();
+    Vector3::new(
+        (cos_w * cos_o - sin_w * sin_o * cos_i) * x + (-sin_w * cos_o - cos_w * sin_o * cos_i) * y,
+        (cos_w * sin_o + sin_w * cos_o * cos_i) * x + (-sin_w * sin_o + cos_w * cos_o * cos_i) * y,
+        sin_w * sin_i * x + cos_w * sin_i * y,
+    )
+}
+
+/// Ekliptikaler Vektor J2000.0 -> Rektaszension/Deklination in Radiant
+fn vector_to_equatorial(v: &Vector3<f64>) -> (f64, f64) {
+    let (sin_e, cos_e) = OBLIQUITY_J2000.to_radians().sin_cos();
+    let equatorial = Vector3::new(v.x, cos_e * v.y - sin_e * v.z, sin_e * v.y + cos_e * v.z);
+    let ra = equatorial.y.atan2(equatorial.x).rem_euclid(std::f64::consts::TAU);
+    let dec = (equatorial.z / equatorial.norm()).asin();
+    (ra, dec)
+}
+
+/// Glieder für Länge und Entfernung (Meeus Tab. 47.A): D, M, M', F, Σl [1e-6 °], Σr [1e-3 km]
+const MOON_LONGITUDE: [(i8, i8, i8, i8, f64, f64); 14] = [
+    (0, 0, 1, 0, 6_288_774.0, -20_905_355.0),
+    (2, 0, -1, 0, 1_274_027.0, -3_699_111.0),
+    (2, 0, 0, 0, 658_314.0, -2_955_968.0),
+    (0, 0, 2, 0, 213_618.0, -569_925.0),
+    (0, 1, 0, 0, -185_116.0, 48_888.0),
+    (0, 0, 0, 2, -114_332.0, -3_149.0),
+    (2, 0, -2, 0, 58_793.0, 246_158.0),
+    (2, -1, -1, 0, 57_066.0, -152_138.0),
+    (2, 0, 1, 0, 53_322.0, -170_733.0),
+    (2, -1, 0, 0, 45_758.0, -204_586.0),
+    (0, 1, -1, 0, -40_923.0, -129_620.0),
+    (1, 0, 0, 0, -34_720.0, 108_743.0),
+    (0, 1, 1, 0, -30_383.0, 104_755.0),
+    (2, 0, 0, -2, 15_327.0, 10_321.0),
+];
+
+/// Glieder für die Breite (Meeus Tab. 47.B): D, M, M', F, Σb [1e-6 °]
+const MOON_LATITUDE: [(i8, i8, i8, i8, f64); 8] = [
+    (0, 0, 0, 1, 5_128_122.0),
+    (0, 0, 1, 1, 280_602.0),
+    (0, 0, 1, -1, 277_693.0),
+    (2, 0, 0, -1, 173_237.0),
+    (2, 0, -1, 1, 55_413.0),
+    (2, 0, -1, -1, 46_271.0),
+    (2, 0, 0, 1, 32_573.0),
+    (0, 0, 2, 1, 17_198.0),
+];
+
+/// Topozentrischer Ort des Mondes, mittlerer Ort J2000.0
+fn moon_position(site: &Position, time: DateTime<Utc>) -> EqPostion {
+    let t = (julian_date(time) - J2000) / 36_525.0;
+    let mean_longitude = 218.316_447_7 + 481_267.881_234_21 * t;
+    let elongation = (297.850_192_1 + 445_267.111_403_4 * t).to_radians();
+    let sun_anomaly = (357.529_109_2 + 35_999.050_290_9 * t).to_radians();
+    let moon_anomaly = (134.963_396_4 + 477_198.867_505_5 * t).to_radians();
+    let latitude_argument = (93.272_095_0 + 483_202.017_523_3 * t).to_radians();
+    // Abnahme der Exzentrizität der Erdbahn
+    let eccentricity = 1.0 - 0.002_516 * t;
+
+    let argument = |d: i8, m: i8, mp: i8, f: i8| {
+        let value = f64::from(d) * elongation
+            + f64::from(m) * sun_anomaly
+            + f64::from(mp) * moon_anomaly
+            + f64::from(f) * latitude_argument;
+        (value, eccentricity.powi(i32::from(m.abs())))
+    };
+    let (mut longitude, mut distance) = (0.0, 0.0);
+    for (d, m, mp, f, l, r) in MOON_LONGITUDE {
+        let (value, factor) = argument(d, m, mp, f);
+        longitude += l * factor * value.sin();
+        distance += r * factor * value.cos();
+    }
+    let mut latitude = 0.0;
+    for (d, m, mp, f, b) in MOON_LATITUDE {
+        let (value, factor) = argument(d, m, mp, f);
+        latitude += b * factor * value.sin();
+    }
+    let longitude = (mean_longitude + longitude * 1e-6).to_radians();
+    let latitude = (latitude * 1e-6).to_radians();
+    let distance = 385_000.56 + distance * 1e-3;
+
+    // Ekliptik des Datums -> Äquator des Datums mit der mittleren Schiefe
+    let obliquity = (OBLIQUITY_J2000 - 0.013_004_2 * t).to_radians();
+    let ra = (longitude.sin() * obliquity.cos() - latitude.tan() * obliquity.sin())
+        .atan2(longitude.cos());
+    let dec = (latitude.sin() * obliquity.cos()
+        + latitude.cos() * obliquity.sin() * longitude.sin())
+    .asin();
+
+    // Parallaxe: vom Erdmittelpunkt zum Standort (Erde als Kugel genähert)
+    let parallax = (EARTH_RADIUS / distance).asin();
+    let hour_angle = local_sidereal_time(time, site.longitude).to_radians() - ra;
+    let (sin_phi, cos_phi) = site.latitude.to_radians().sin_cos();
+    let denominator = dec.cos() - cos_phi * parallax.sin() * hour_angle.cos();
+    let delta_ra = (-cos_phi * parallax.sin() * hour_angle.sin()).atan2(denominator);
+    let topocentric_dec =
+        ((dec.sin() - sin_phi * parallax.sin()) * delta_ra.cos()).atan2(denominator);
+
+    let (ra, dec) = apparent_to_mean(ra + delta_ra, topocentric_dec, time);
+    EqPostion {
+        ra: (ra.to_degrees() / 15.0) as f32,
+        dec: dec.to_degrees() as f32,
+    }
+}
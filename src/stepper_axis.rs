@@ -1,71 +1,92 @@
-use super::stepper_motor::Stepper;
-use embedded_hal::delay::DelayNs;
+use super::motion_profile::{MotionPhase, MotionProfile};
+use super::stepper_motor::{Polarity, Stepper};
 use embedded_hal::digital::OutputPin;
 
 #[derive(Debug)]
 pub struct StepperAxis<STEP, DIR, EN> {
-    stepper: Stepper<STEP,DIR, EN>,
+    stepper: Stepper<STEP, DIR, EN>,
     steps_per_unit: f32,
 }
 
 impl<STEP, DIR, EN> StepperAxis<STEP, DIR, EN>
 where
-    STEP: OutputPin,
-    DIR: OutputPin,
-    EN: OutputPin,
+    STEP: OutputPin + Send + 'static,
+    DIR: OutputPin + Send + 'static,
+    EN: OutputPin + Send + 'static,
 {
-    pub fn new(step: STEP, dir: DIR, enable: Option<EN>, steps_per_unit: f32,max_speed_units_per_sec:f32, acceleration:f32) -> Self {
-
-        
-        let stepper = Stepper::new(step, dir, enable, max_speed_units_per_sec/steps_per_unit, acceleration/steps_per_unit);
+    pub fn new(step: STEP, dir: DIR, enable: Option<EN>, polarity: Polarity, steps_per_unit: f32,max_speed_units_per_sec:f32, acceleration:f32) -> Self {
+        let stepper = Stepper::new(step, dir, enable, polarity, max_speed_units_per_sec*steps_per_unit, acceleration*steps_per_unit);
 
         Self {
             stepper,
             steps_per_unit,
         }
     }
+}
 
+impl<STEP, DIR, EN> StepperAxis<STEP, DIR, EN> {
     /// Enables the stepper motor
     /// This will set the enable pin to low (if available)
-    pub fn enable(&mut self) {
+    pub fn enable(&self) {
         self.stepper.enable();
     }
 
    /// Disables the stepper motor
    /// This will set the enable pin to high (if available)
-    pub fn disable(&mut self) {
+    pub fn disable(&self) {
         self.stepper.disable();
     }
 
-    
-    pub fn step_to_position<D: DelayNs>(&mut self, delay: &mut D, target_position: f32) {
-        let target_steps = (target_position * self.steps_per_unit) as i32;
-        self.stepper.step_to_position(delay, target_steps);
+    /// Starts moving to the target position and returns immediately
+    pub fn move_to(&self, target_position: f32) {
+        let target_steps = (target_position * self.steps_per_unit).round() as i32;
+        self.stepper.move_to(target_steps);
     }
 
-    pub fn position(&self) -> f32 {
-        self.stepper.position() as f32 / self.steps_per_unit
+    /// Moves continuously with the given speed in units/sec
+    pub fn set_velocity(&self, units_per_sec: f32) {
+        self.stepper.set_velocity(units_per_sec * self.steps_per_unit);
     }
 
-    pub fn set_position(&mut self, pos: f32) {
-        let pos_steps = (pos * self.steps_per_unit) as i32;
-        self.stepper.set_position(pos_steps);
+    pub fn stop(&self) {
+        self.stepper.stop();
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.stepper.is_moving()
     }
 
-    pub fn set_max_speed(&mut self, max_speed_units_per_sec: f32) {
-        self.stepper.set_max_speed(max_speed_units_per_sec / self.steps_per_unit);
+    pub fn phase(&self) -> MotionPhase {
+        self.stepper.phase()
     }
 
-    pub fn set_acceleration(&mut self, acceleration_units_per_sec: f32) {
-        self.stepper.set_acceleration(acceleration_units_per_sec / self.steps_per_unit);
+    /// Target of the last move in units
+    pub fn target(&self) -> f32 {
+        self.stepper.target() as f32 / self.steps_per_unit
     }
 
-    pub fn max_speed(&self) -> f32 {
-        self.stepper.get_max_speed() * self.steps_per_unit
+    pub fn position(&self) -> f32 {
+        self.stepper.position() as f32 / self.steps_per_unit
     }
-    pub fn acceleration(&self) -> f32 {
-        self.stepper.get_acceleration() * self.steps_per_unit
+
+    /// Current speed in units/sec
+    pub fn velocity(&self) -> f32 {
+        self.stepper.velocity() / self.steps_per_unit
     }
 
+    pub fn set_position(&self, pos: f32) {
+        let pos_steps = (pos * self.steps_per_unit).round() as i32;
+        self.stepper.set_position(pos_steps);
+    }
 
-}
\ No newline at end of file
+    /// Sets the ramp shape, the jerk of an S-curve is given in units/sec^3
+    pub fn set_profile(&self, profile: MotionProfile) {
+        let profile = match profile {
+            MotionProfile::SCurve { jerk } => MotionProfile::SCurve {
+                jerk: jerk * self.steps_per_unit,
+            },
+            profile => profile,
+        };
+        self.stepper.set_profile(profile);
+    }
+}
@@ -1,113 +1,391 @@
+use crate::motion_profile::{Goal, MotionPhase, MotionPlanner, MotionProfile};
 use embedded_hal::digital::OutputPin;
-use embedded_hal::delay::DelayNs;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, AtomicU8, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
+/// Mindestbreite des Step-Pulses (DRV8825: 1.9 µs)
+const PULSE_WIDTH: Duration = Duration::from_micros(2);
+/// Wartezeit nach einem Richtungswechsel (DRV8825: 650 ns)
+const DIR_SETUP: Duration = Duration::from_micros(1);
+/// Kürzere Wartezeiten werden aktiv abgewartet statt geschlafen
+const SPIN_THRESHOLD: Duration = Duration::from_micros(200);
+/// Wie oft ein untätiger Step-Thread nach neuen Kommandos schaut
+const IDLE_POLL: Duration = Duration::from_millis(100);
+
+/// Pegel der Treibereingänge, der Default entspricht dem DRV8825
+/// (DIR LOW = vorwärts, EN LOW = aktiv)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Polarity {
+    pub invert_direction: bool,
+    pub enable_active_high: bool,
+}
+
+/// f32, das über ein AtomicU32 geteilt wird
+#[derive(Debug, Default)]
+struct AtomicF32(AtomicU32);
+
+impl AtomicF32 {
+    fn new(value: f32) -> Self {
+        AtomicF32(AtomicU32::new(value.to_bits()))
+    }
+    fn load(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Acquire))
+    }
+    fn store(&self, value: f32) {
+        self.0.store(value.to_bits(), Ordering::Release)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum Mode {
+    Idle = 0,
+    Position = 1,
+    Velocity = 2,
+}
+
+impl From<u8> for Mode {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Mode::Position,
+            2 => Mode::Velocity,
+            _ => Mode::Idle,
+        }
+    }
+}
+
+/// Zustand, den sich Step-Thread und Aufrufer ohne Lock teilen
+#[derive(Debug)]
+struct Shared {
+    position: AtomicI32,
+    target: AtomicI32,
+    /// Geschwindigkeit im Velocity-Modus in Schritten/s (mit Vorzeichen)
+    command_velocity: AtomicF32,
+    /// Tatsächliche Geschwindigkeit in Schritten/s (mit Vorzeichen)
+    velocity: AtomicF32,
+    /// MotionPhase des Step-Threads
+    phase: AtomicU8,
+    mode: AtomicU8,
+    max_speed_hz: AtomicF32,
+    acceleration: AtomicF32,
+    /// Ruckbegrenzung in Schritten/s³, 0 = trapezförmiges Profil
+    jerk: AtomicF32,
+    enabled: AtomicBool,
+    shutdown: AtomicBool,
+}
+
+impl Shared {
+    fn profile(&self) -> MotionProfile {
+        let jerk = self.jerk.load();
+        if jerk > 0.0 {
+            MotionProfile::SCurve { jerk }
+        } else {
+            MotionProfile::Trapezoidal
+        }
+    }
+}
+
+/// Schrittmotor, dessen Pulse ein eigener Thread mit Echtzeitpriorität erzeugt.
+/// Alle Methoden kehren sofort zurück; Ziel und Geschwindigkeit können während
+/// einer laufenden Bewegung geändert werden.
 #[derive(Debug)]
 pub struct Stepper<STEP, DIR, EN> {
-    step: STEP,
-    dir: DIR,
-    enable: Option<EN>,
-    max_speed_hz: f32,
-    acceleration: f32,
-    position: i32,
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+    _pins: PhantomData<(STEP, DIR, EN)>,
 }
 
 impl<STEP, DIR, EN> Stepper<STEP, DIR, EN>
 where
-    STEP: OutputPin,
-    DIR: OutputPin,
-    EN: OutputPin,
+    STEP: OutputPin + Send + 'static,
+    DIR: OutputPin + Send + 'static,
+    EN: OutputPin + Send + 'static,
 {
-    /// Erstelle einen neuen Stepper mit optionalem Enable-Pin
-    pub fn new(step: STEP, dir: DIR, enable: Option<EN>, max_speed_hz: f32, acceleration: f32) -> Self {
-        Self {
+    /// Erstelle einen neuen Stepper mit optionalem Enable-Pin und starte seinen Step-Thread
+    pub fn new(
+        step: STEP,
+        dir: DIR,
+        enable: Option<EN>,
+        polarity: Polarity,
+        max_speed_hz: f32,
+        acceleration: f32,
+    ) -> Self {
+        let shared = Arc::new(Shared {
+            position: AtomicI32::new(0),
+            target: AtomicI32::new(0),
+            command_velocity: AtomicF32::new(0.0),
+            velocity: AtomicF32::new(0.0),
+            phase: AtomicU8::new(MotionPhase::Idle as u8),
+            mode: AtomicU8::new(Mode::Idle as u8),
+            max_speed_hz: AtomicF32::new(max_speed_hz),
+            acceleration: AtomicF32::new(acceleration),
+            jerk: AtomicF32::new(0.0),
+            enabled: AtomicBool::new(true),
+            shutdown: AtomicBool::new(false),
+        });
+
+        let mut generator = PulseGenerator {
+            shared: shared.clone(),
             step,
             dir,
             enable,
-            max_speed_hz,
-            acceleration,
-            position: 0,
+            polarity,
+        };
+        let thread = thread::Builder::new()
+            .name("stepper".to_owned())
+            .spawn(move || {
+                set_realtime_priority();
+                generator.run();
+            })
+            .expect("Failed to spawn stepper thread");
+
+        Self {
+            shared,
+            thread: Some(thread),
+            _pins: PhantomData,
+        }
+    }
+}
+
+impl<STEP, DIR, EN> Stepper<STEP, DIR, EN> {
+    fn notify(&self) {
+        if let Some(thread) = &self.thread {
+            thread.thread().unpark();
         }
-    } 
+    }
 
     /// Aktiviere den Treiber (falls EN vorhanden)
-    pub fn enable(&mut self) {
-        if let Some(en) = self.enable.as_mut() {
-            let _ = en.set_low(); // DRV8825: LOW = enable
-        }
+    pub fn enable(&self) {
+        self.shared.enabled.store(true, Ordering::Release);
+        self.notify();
     }
 
     /// Deaktiviere den Treiber (falls EN vorhanden)
-    pub fn disable(&mut self) {
-        if let Some(en) = self.enable.as_mut() {
-            let _ = en.set_high(); // DRV8825: HIGH = disable
-        }
+    pub fn disable(&self) {
+        self.shared.enabled.store(false, Ordering::Release);
+        self.notify();
     }
 
-    pub fn step<D: DelayNs>(&mut self, delay: &mut D, steps: i32) {
-        if steps> 0 {
-            let _ = self.dir.set_low();
-        } else {
-            let _ = self.dir.set_high();
-        }
-        let _ = self.dir.set_low();
-        self.perform_ramped_steps(delay, steps );
-    }
-    /// Schritt mit Rampe (sehr einfaches Modell)
-    fn perform_ramped_steps<D: DelayNs>(&mut self, delay: &mut D, delta_steps: i32) {
-        let steps = delta_steps.abs() as u32;
-        let min_delay_us = (1_000_000.0 / self.max_speed_hz) as u32;
-
-        for i in 0..steps {
-            let progress = i as f32 / steps as f32;
-            let ramp = if progress < 0.5 {
-                2.0 * progress
-            } else {
-                2.0 * (1.0 - progress)
-            };
-            let adjusted_delay = (min_delay_us as f32 / ramp.max(0.1)) as u32;
+    /// Fährt zur angegebenen Position, ein laufender Auftrag wird ersetzt
+    pub fn move_to(&self, target_position: i32) {
+        self.shared.target.store(target_position, Ordering::Release);
+        self.shared
+            .mode
+            .store(Mode::Position as u8, Ordering::Release);
+        self.notify();
+    }
 
-            self.pulse_step(delay, adjusted_delay);
-            self.position += delta_steps.signum(); // +1 vorwärts, -1 rückwärts
+    /// Dreht dauerhaft mit der angegebenen Geschwindigkeit in Schritten/s
+    pub fn set_velocity(&self, steps_per_sec: f32) {
+        if steps_per_sec == 0.0 {
+            self.stop();
+            return;
         }
+        self.shared.command_velocity.store(steps_per_sec);
+        self.shared
+            .mode
+            .store(Mode::Velocity as u8, Ordering::Release);
+        self.notify();
+    }
+
+    /// Beendet den aktuellen Auftrag und bremst bis zum Stillstand ab
+    pub fn stop(&self) {
+        self.shared.mode.store(Mode::Idle as u8, Ordering::Release);
+        self.notify();
     }
 
-    fn pulse_step<D: DelayNs>(&mut self, delay: &mut D, pulse_width_us: u32) {
-        let _ = self.step.set_high();
-        let _ = delay.delay_us(pulse_width_us / 2);
-        let _ = self.step.set_low();
-        let _ = delay.delay_us(pulse_width_us / 2);
+    /// `true` solange ein Auftrag läuft oder der Motor noch abbremst
+    pub fn is_moving(&self) -> bool {
+        Mode::from(self.shared.mode.load(Ordering::Acquire)) != Mode::Idle
+            || self.shared.velocity.load() != 0.0
     }
 
-        /// Gibt die aktuelle Position zurück
+    /// Gibt die aktuelle Position zurück
     pub fn position(&self) -> i32 {
-        self.position
+        self.shared.position.load(Ordering::Acquire)
     }
 
-    /// Setzt die aktuelle Position (z. B. bei Homing)
-    pub fn set_position(&mut self, pos: i32) {
-        self.position = pos;
+    /// Aktuelle Geschwindigkeit in Schritten/s
+    pub fn velocity(&self) -> f32 {
+        self.shared.velocity.load()
     }
 
-    pub fn set_max_speed(&mut self, max_speed_hz: f32) {
-        self.max_speed_hz = max_speed_hz;
+    pub fn phase(&self) -> MotionPhase {
+        MotionPhase::from(self.shared.phase.load(Ordering::Acquire))
     }
 
-    pub fn set_acceleration(&mut self, acceleration: f32) {
-        self.acceleration = acceleration;
+    /// Ziel des letzten Positionsauftrags
+    pub fn target(&self) -> i32 {
+        self.shared.target.load(Ordering::Acquire)
     }
 
-    pub fn get_max_speed(&self) -> f32 {
-        self.max_speed_hz
+    /// Setzt die aktuelle Position (z. B. bei Homing).
+    /// Ein laufender Auftrag wird um denselben Betrag verschoben.
+    pub fn set_position(&self, pos: i32) {
+        let offset = pos - self.shared.position.load(Ordering::Acquire);
+        self.shared.position.fetch_add(offset, Ordering::AcqRel);
+        self.shared.target.fetch_add(offset, Ordering::AcqRel);
     }
 
-    pub fn get_acceleration(&self) -> f32 {
-        self.acceleration
+    pub fn set_profile(&self, profile: MotionProfile) {
+        match profile {
+            MotionProfile::Trapezoidal => self.shared.jerk.store(0.0),
+            MotionProfile::SCurve { jerk } => self.shared.jerk.store(jerk),
+        }
     }
+}
 
-    pub fn step_to_position<D: DelayNs>(&mut self, delay: &mut D, target_position: i32) {
-        let delta_steps = target_position - self.position;
-        if delta_steps != 0 {
-            self.step(delay, delta_steps);
+impl<STEP, DIR, EN> Drop for Stepper<STEP, DIR, EN> {
+    fn drop(&mut self) {
+        self.shared.shutdown.store(true, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
         }
     }
-}
\ No newline at end of file
+}
+
+/// Läuft im Step-Thread und besitzt die Pins
+struct PulseGenerator<STEP, DIR, EN> {
+    shared: Arc<Shared>,
+    step: STEP,
+    dir: DIR,
+    enable: Option<EN>,
+    polarity: Polarity,
+}
+
+impl<STEP, DIR, EN> PulseGenerator<STEP, DIR, EN>
+where
+    STEP: OutputPin,
+    DIR: OutputPin,
+    EN: OutputPin,
+{
+    fn run(&mut self) {
+        let mut enabled = None;
+        let mut direction = 0;
+        let mut planner = MotionPlanner::default();
+        let mut last_step: Option<Instant> = None;
+
+        while !self.shared.shutdown.load(Ordering::Acquire) {
+            let want_enabled = self.shared.enabled.load(Ordering::Acquire);
+            if enabled != Some(want_enabled) {
+                self.apply_enable(want_enabled);
+                enabled = Some(want_enabled);
+            }
+
+            if !want_enabled {
+                // Ohne Strom hält der Motor keine Geschwindigkeit
+                planner.reset();
+                self.shared.mode.store(Mode::Idle as u8, Ordering::Release);
+            }
+
+            let Some((delta, interval)) = self.next_step(&mut planner) else {
+                self.shared.velocity.store(0.0);
+                self.shared
+                    .phase
+                    .store(MotionPhase::Idle as u8, Ordering::Release);
+                thread::park_timeout(IDLE_POLL);
+                last_step = None;
+                continue;
+            };
+            if delta != direction {
+                let _ = if (delta > 0) != self.polarity.invert_direction {
+                    self.dir.set_low()
+                } else {
+                    self.dir.set_high()
+                };
+                direction = delta;
+                wait_until(Instant::now() + DIR_SETUP);
+            }
+
+            if let Some(last_step) = last_step {
+                wait_until(last_step + interval);
+            }
+            let pulse = Instant::now();
+            let _ = self.step.set_high();
+            wait_until(pulse + PULSE_WIDTH);
+            let _ = self.step.set_low();
+            last_step = Some(pulse);
+
+            self.shared.position.fetch_add(delta, Ordering::AcqRel);
+            self.shared.velocity.store(planner.velocity());
+            self.shared
+                .phase
+                .store(planner.phase() as u8, Ordering::Release);
+        }
+        self.shared.velocity.store(0.0);
+    }
+
+    fn apply_enable(&mut self, enabled: bool) {
+        if let Some(en) = self.enable.as_mut() {
+            let _ = if enabled == self.polarity.enable_active_high {
+                en.set_high()
+            } else {
+                en.set_low()
+            };
+        }
+    }
+
+    /// Richtung und Abstand des nächsten Schritts, `None` wenn nichts zu tun ist
+    fn next_step(&self, planner: &mut MotionPlanner) -> Option<(i32, Duration)> {
+        let mode = Mode::from(self.shared.mode.load(Ordering::Acquire));
+        let goal = match mode {
+            // Ein gestoppter Motor bremst mit der eingestellten Verzögerung ab
+            Mode::Idle => Goal::Velocity(0.0),
+            Mode::Velocity => Goal::Velocity(self.shared.command_velocity.load()),
+            Mode::Position => Goal::Position(
+                self.shared.target.load(Ordering::Acquire)
+                    - self.shared.position.load(Ordering::Acquire),
+            ),
+        };
+        let step = planner.next_step(
+            goal,
+            self.shared.profile(),
+            self.shared.max_speed_hz.load(),
+            self.shared.acceleration.load(),
+        );
+        if step.is_none() && mode == Mode::Position {
+            // Nur in den Ruhezustand wechseln, wenn kein neues Kommando kam
+            let _ = self.shared.mode.compare_exchange(
+                Mode::Position as u8,
+                Mode::Idle as u8,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            );
+        }
+        step
+    }
+}
+
+/// Wartet bis `deadline`, lange Strecken schlafend, das Ende aktiv
+fn wait_until(deadline: Instant) {
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            return;
+        }
+        let remaining = deadline - now;
+        if remaining > SPIN_THRESHOLD {
+            thread::sleep(remaining - SPIN_THRESHOLD);
+        } else {
+            std::hint::spin_loop();
+        }
+    }
+}
+
+/// Versucht, den aktuellen Thread mit SCHED_FIFO laufen zu lassen.
+/// Ohne CAP_SYS_NICE bleibt es bei der normalen Priorität.
+fn set_realtime_priority() {
+    let param = libc::sched_param { sched_priority: 80 };
+    // SAFETY: sched_param ist vollständig initialisiert, 0 bezeichnet den aufrufenden Thread
+    let result = unsafe { libc::sched_setscheduler(0, libc::SCHED_FIFO, &param) };
+    if result != 0 {
+        println!(
+            "Stepper thread runs without real-time priority: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
@@ -1,19 +1,21 @@
-use bno055::BNO055Calibration;
-use chrono::Datelike;
+use atomic_struct_core::AtomicMember;
+use chrono::{DateTime, Datelike, Utc};
 use gpsd_proto::UnifiedResponse;
 use nalgebra::UnitQuaternion;
+use serde::{de::DeserializeOwned, Deserialize};
 use open_pi_scope::{
     alignment::{AlignmentData, EulerAngle, Orientation},
-    gnss::{GnssData, Position},
+    gnss::{GnssData, Mode, Position},
     magnetic::MagneticData,
 };
 use std::{
     fs,
     sync::{Arc, OnceLock},
+    time::Duration,
 };
 use tokio::sync::Mutex;
 use tokio_util::codec::LinesCodecError;
-use toml_edit::{value, DocumentMut};
+use toml_edit::{value, Array, ArrayOfTables, DocumentMut, Item, Table, Value};
 use world_magnetic_model::{
     time::Date,
     uom::si::{
@@ -25,9 +27,46 @@ use world_magnetic_model::{
     GeomagneticField,
 };
 
-use crate::helpers::{hex_decode, hex_encode, vec_to_calib};
+use crate::helpers::{hex_decode, hex_encode};
+use crate::mount_backend::BackendKind;
+use crate::bno055_sensor::Bno055Config;
+use crate::astronomy::AstronomyConfig;
+use crate::catalog::CatalogConfig;
+use crate::clock::{clock, TimeConfig};
+use crate::closed_loop::ClosedLoopConfig;
+use crate::focuser::FocuserConfig;
+use crate::gnss_source::{GnssConfig, GnssFix, GnssUpdate};
+use crate::limits::{parse_horizon, LimitsConfig};
+use crate::mpu9250::Mpu9250Config;
+use crate::sensor::{SensorKind, SensorStatus};
+use crate::site::{SiteConfig, SiteLocation, SitePolicy, SiteStatus};
+use crate::site_estimator::{FixSample, SiteEstimator};
+use crate::switch::SwitchConfig;
+use crate::mount_config::AxisConfig;
+use crate::pointing_model::{
+    add_star, fit_correction, AlignmentConfig, AlignmentStar, ModelStatistics, PointingModel,
+};
+use crate::telescope_position::AltAZPostion;
 
 const CONFIG_PATH: &str = "/boot/open-pi-scope/config.toml";
+/// Überschreibt `CONFIG_PATH`, z. B. für die Simulation auf einem PC
+const CONFIG_PATH_ENV: &str = "OPEN_PI_SCOPE_CONFIG";
+/// Waagerecht nach Norden, falls keine Parkposition eingetragen ist
+const DEFAULT_PARK: AltAZPostion = AltAZPostion { alt: 0.0, az: 0.0 };
+const DEFAULT_HOME: AltAZPostion = AltAZPostion { alt: 0.0, az: 0.0 };
+/// Schreibt die Achsstellung höchstens alle 10 Minuten auf die SD-Karte
+const DEFAULT_POSITION_SAVE_INTERVAL: Duration = Duration::from_secs(600);
+
+/// PPS-Meldung von gpsd; `gpsd_proto::Pps` hat die Sekunden nur als f32
+#[derive(Deserialize)]
+struct GpsdPps {
+    real_sec: i64,
+    real_nsec: i64,
+    clock_sec: i64,
+    clock_nsec: i64,
+    /// log2 der Genauigkeit in s
+    precision: Option<i32>,
+}
 
 pub(crate) fn storage() -> &'static Arc<Storage> {
     static STORAGE: OnceLock<Arc<Storage>> = OnceLock::new();
@@ -39,6 +78,15 @@ pub(crate) struct Storage {
     pub(crate) gnss_data: Arc<GnssData>,
     pub(crate) magnetic_data: MagneticData,
     pub(crate) alingment_data: AlignmentData,
+    sensor_status: AtomicMember<SensorStatus>,
+    /// Referenzsterne und Pointing-Modell, die Korrektur liegt zusätzlich in `alingment_data`
+    alignment: AtomicMember<AlignmentConfig>,
+    /// `[mount.limits]` mit eingelesener Horizontdatei, wird bei jeder Bewegung gebraucht
+    limits: AtomicMember<LimitsConfig>,
+    /// `[site]`, die letzte gültige GNSS-Position wird hier nachgeführt
+    site: AtomicMember<SiteConfig>,
+    /// Mittelt die GNSS-Lösungen zum Standort
+    site_estimator: Mutex<SiteEstimator>,
     config: Arc<Mutex<DocumentMut>>,
 }
 
@@ -48,62 +96,80 @@ impl Storage {
             gnss_data: Arc::new(GnssData::default()),
             magnetic_data: MagneticData::default(),
             alingment_data: AlignmentData::default(),
+            sensor_status: AtomicMember::new(SensorStatus::default()),
+            alignment: AtomicMember::new(AlignmentConfig::default()),
+            limits: AtomicMember::new(LimitsConfig::default()),
+            site: AtomicMember::new(SiteConfig::default()),
+            site_estimator: Mutex::new(SiteEstimator::default()),
             config: Arc::new(Mutex::new(DocumentMut::new())),
         }
     }
     pub async fn load_config(&self) -> anyhow::Result<()> {
         // Datei einlesen
-        let content = fs::read_to_string(CONFIG_PATH)?;
+        let content = fs::read_to_string(config_path())?;
         let doc = content.parse::<DocumentMut>()?;
         // TOML-Dokument parsen (Kommentare bleiben erhalten)
-        let mut document = self.config.lock().await;
-        *document = doc.clone();
+        let alignment = alignment_config(&doc)?;
+        let correction = alignment.correction.map(|[w, x, y, z]| {
+            UnitQuaternion::from_quaternion(nalgebra::Quaternion::new(w, x, y, z))
+        });
+        self.alingment_data.set_correction(correction).await;
+        self.alignment.set(alignment).await;
+        self.limits.set(limits_config(&doc)?).await;
+        self.site.set(site_config(&doc)?).await;
+
+        {
+            let mut document = self.config.lock().await;
+            *document = doc.clone();
+        }
+        // Ohne GNSS gilt sonst bis zur ersten Lösung 0°/0°
+        self.update_magnetic().await;
         Ok(())
     }
 
     pub async fn update_gpsd(&self, line: String) -> Result<(), LinesCodecError> {
+        // TOFF (Zeit der seriellen Meldung) kennt gpsd_proto nicht, TPV reicht dafür
+        if line.starts_with("{\"class\":\"TOFF\"") {
+            return Ok(());
+        }
         match serde_json::from_str(&line) {
             Ok(rd) => match rd {
                 UnifiedResponse::Tpv(t) => {
-                    self.gnss_data.set_lat(t.lat.unwrap_or_default()).await;
-                    self.gnss_data.set_lon(t.lon.unwrap_or_default()).await;
-                    self.gnss_data.set_alt(t.alt.unwrap_or_default()).await;
-                    self.gnss_data
-                        .set_leap_seconds(t.leapseconds.unwrap_or_default())
-                        .await;
-                    self.gnss_data
-                        .set_estimated_error_altitude(t.epv.unwrap_or_default())
-                        .await;
-                    self.gnss_data
-                        .set_estimated_error_climb(t.epc.unwrap_or_default())
-                        .await;
-                    self.gnss_data
-                        .set_estimated_error_speed(t.eps.unwrap_or_default())
-                        .await;
-                    self.gnss_data
-                        .set_estimated_error_track(t.epd.unwrap_or_default())
-                        .await;
-                    self.gnss_data.set_speed(t.speed.unwrap_or_default()).await;
-                    self.gnss_data.set_track(t.track.unwrap_or_default()).await;
-                    self.gnss_data.set_mode(t.mode.into()).await;
-                    self.gnss_data.set_climb(t.climb.unwrap_or_default()).await;
-                    self.gnss_data
-                        .set_estimated_error_plane(t.eph.unwrap_or_default())
-                        .await;
-                    self.gnss_data
-                        .set_estimated_error_latitude(t.epy.unwrap_or_default())
-                        .await;
-                    self.gnss_data
-                        .set_estimated_error_longitude(t.epx.unwrap_or_default())
-                        .await;
+                    let mode: Mode = t.mode.into();
+                    let time = match mode {
+                        Mode::NoFix => None,
+                        _ => t.time.as_deref().and_then(|time| {
+                            DateTime::parse_from_rfc3339(time)
+                                .ok()
+                                .map(|time| time.with_timezone(&Utc))
+                        }),
+                    };
+                    self.update_fix(GnssFix {
+                        mode: Some(mode),
+                        lat: t.lat,
+                        lon: t.lon,
+                        alt: t.alt_msl.or(t.alt),
+                        leap_seconds: t.leapseconds,
+                        estimated_error_latitude: t.epy,
+                        estimated_error_longitude: t.epx,
+                        estimated_error_plane: t.eph,
+                        estimated_error_altitude: t.epv,
+                        estimated_error_speed: t.eps,
+                        estimated_error_track: t.epd,
+                        estimated_error_climb: t.epc,
+                        speed: t.speed,
+                        track: t.track,
+                        climb: t.climb,
+                        time,
+                        epoch: true,
+                    })
+                    .await;
 
                     println!(
                         "Fix: {} / Sattelites: {}",
                         t.mode,
                         self.gnss_data.get_satellites().await.len()
                     );
-
-                    self.update_magnetic().await;
                 }
                 UnifiedResponse::Sky(s) => {
                     if let Some(sats) = s.satellites.clone() {
@@ -112,6 +178,16 @@ impl Storage {
                         self.gnss_data.set_satellites(sats).await;
                     }
                 }
+                UnifiedResponse::Pps(_) => {
+                    if let Ok(pps) = serde_json::from_str::<GpsdPps>(&line) {
+                        let real = DateTime::from_timestamp(pps.real_sec, pps.real_nsec as u32);
+                        let system = DateTime::from_timestamp(pps.clock_sec, pps.clock_nsec as u32);
+                        if let (Some(real), Some(system)) = (real, system) {
+                            let error = 2f64.powi(pps.precision.unwrap_or(-20));
+                            clock().add_pps(real, system, error).await;
+                        }
+                    }
+                }
                 _ => {}
             },
             Err(e) => {
@@ -120,10 +196,129 @@ impl Storage {
         };
         Ok(())
     }
+    /// Übernimmt Daten eines direkt angeschlossenen Empfängers
+    pub async fn update_gnss(&self, update: GnssUpdate) {
+        match update {
+            GnssUpdate::Fix(fix) => self.update_fix(fix).await,
+            GnssUpdate::Satellites(satellites) => self.gnss_data.set_satellites(satellites).await,
+        }
+    }
+    /// Schreibt die vorhandenen Werte nach `gnss_data`, fehlende bleiben stehen
+    async fn update_fix(&self, fix: GnssFix) {
+        if let Some(time) = fix.time {
+            clock().add_gnss_time(time, Utc::now()).await;
+        }
+        let data = &self.gnss_data;
+        if let Some(mode) = fix.mode {
+            data.set_mode(mode).await;
+        }
+        if let Some(lat) = fix.lat {
+            data.set_lat(lat).await;
+        }
+        if let Some(lon) = fix.lon {
+            data.set_lon(lon).await;
+        }
+        if let Some(alt) = fix.alt {
+            data.set_alt(alt).await;
+        }
+        if let Some(leap_seconds) = fix.leap_seconds {
+            data.set_leap_seconds(leap_seconds).await;
+        }
+        if let Some(error) = fix.estimated_error_latitude {
+            data.set_estimated_error_latitude(error).await;
+        }
+        if let Some(error) = fix.estimated_error_longitude {
+            data.set_estimated_error_longitude(error).await;
+        }
+        if let Some(error) = fix.estimated_error_plane {
+            data.set_estimated_error_plane(error).await;
+        }
+        if let Some(error) = fix.estimated_error_altitude {
+            data.set_estimated_error_altitude(error).await;
+        }
+        if let Some(error) = fix.estimated_error_speed {
+            data.set_estimated_error_speed(error).await;
+        }
+        if let Some(error) = fix.estimated_error_track {
+            data.set_estimated_error_track(error).await;
+        }
+        if let Some(error) = fix.estimated_error_climb {
+            data.set_estimated_error_climb(error).await;
+        }
+        if let Some(speed) = fix.speed {
+            data.set_speed(speed).await;
+        }
+        if let Some(track) = fix.track {
+            data.set_track(track).await;
+        }
+        if let Some(climb) = fix.climb {
+            data.set_climb(climb).await;
+        }
+        if fix.epoch {
+            self.estimate_site(&fix).await;
+        }
+        if fix.lat.is_some() || fix.lon.is_some() || fix.alt.is_some() {
+            self.update_magnetic().await;
+        }
+    }
+    /// Gibt eine vollständige Lösung mit den Fehlern ihrer Epoche an die Mittelung weiter
+    async fn estimate_site(&self, fix: &GnssFix) {
+        let (Some(latitude), Some(longitude)) = (fix.lat, fix.lon) else {
+            return;
+        };
+        let sample = FixSample {
+            mode: fix.mode.unwrap_or(self.gnss_data.get_mode().await),
+            latitude,
+            longitude,
+            altitude: fix.alt,
+            error_plane: fix.estimated_error_plane,
+            error_altitude: fix.estimated_error_altitude,
+            time: fix.time.unwrap_or_else(Utc::now),
+        };
+        let config = self.site.get().await.estimate;
+        self.site_estimator.lock().await.add(&config, sample);
+        self.remember_fix().await;
+    }
+    /// Gemittelte GNSS-Position, `None` ohne Fix oder solange keine Lösung gut genug war.
+    /// Ohne brauchbare Höhe wird sie vom gespeicherten Standort übernommen.
+    async fn gnss_location(&self) -> Option<SiteLocation> {
+        if self.gnss_data.get_mode().await == Mode::NoFix {
+            return None;
+        }
+        let estimate = self.site_estimator.lock().await.estimate()?;
+        let mut location = estimate.location;
+        if estimate.vertical_uncertainty.is_none() {
+            let site = self.site.get().await;
+            location.elevation = site
+                .last_fix
+                .or(site.manual)
+                .map_or(self.gnss_data.get_alt().await, |stored| stored.elevation);
+        }
+        Some(location)
+    }
+    /// Speichert die Position als `[site.last_fix]`, sobald sie sich merklich geändert hat
+    async fn remember_fix(&self) {
+        let Some(location) = self.gnss_location().await else {
+            return;
+        };
+        let mut site = self.site.get().await;
+        if site.last_fix.is_some_and(|last_fix| !location.moved_from(&last_fix)) {
+            return;
+        }
+        site.last_fix = Some(location);
+        self.site.set(site).await;
+        {
+            let mut document = self.config.lock().await;
+            set_site_location(&mut document, "last_fix", location);
+        }
+        if let Err(e) = self.update_file().await {
+            println!("Could not save last GNSS fix: {e}");
+        }
+    }
     async fn update_magnetic(&self) {
         let pos = self.get_position().await;
 
-        let now = chrono::Utc::now();
+        let now = clock().now().await;
 
         if let Ok(geomagnetic_field) = GeomagneticField::new(
             Length::new::<meter>(pos.altitude),         // height
@@ -152,12 +347,67 @@ impl Storage {
     pub async fn get_magnetic_data(&self) -> MagneticData {
         self.magnetic_data.clone()
     }
+    /// Standort nach `[site] policy`
     pub async fn get_position(&self) -> Position {
-        Position {
-            latitude: self.gnss_data.get_lat().await,
-            longitude: self.gnss_data.get_lon().await,
-            altitude: self.gnss_data.get_alt().await,
+        let gnss = self.gnss_location().await;
+        self.site.get().await.resolve(gnss).0.into()
+    }
+    pub async fn get_site_status(&self) -> SiteStatus {
+        let gnss = self.gnss_location().await;
+        let config = self.site.get().await;
+        let (location, source) = config.resolve(gnss);
+        SiteStatus {
+            location,
+            source,
+            estimate: self.site_estimator.lock().await.estimate(),
+            config,
+        }
+    }
+    /// Von Hand eingetragener Standort, ohne Eintrag der aktuell verwendete
+    pub async fn get_manual_site(&self) -> SiteLocation {
+        match self.site.get().await.manual {
+            Some(manual) => manual,
+            None => self.get_site_status().await.location,
+        }
+    }
+    pub async fn set_manual_site(&self, location: SiteLocation) -> anyhow::Result<()> {
+        location.validate()?;
+        let mut site = self.site.get().await;
+        site.manual = Some(location);
+        self.site.set(site).await;
+        {
+            let mut document = self.config.lock().await;
+            set_site_location(&mut document, "manual", location);
+        }
+        self.update_magnetic().await;
+        self.update_file().await
+    }
+    pub async fn clear_manual_site(&self) -> anyhow::Result<()> {
+        let mut site = self.site.get().await;
+        site.manual = None;
+        // Ohne Standort wäre `manual` ungültig
+        if site.policy == SitePolicy::Manual {
+            anyhow::bail!("The manual site is in use, change the site policy first");
+        }
+        self.site.set(site).await;
+        {
+            let mut document = self.config.lock().await;
+            site_table(&mut document).remove("manual");
+        }
+        self.update_magnetic().await;
+        self.update_file().await
+    }
+    pub async fn set_site_policy(&self, policy: SitePolicy) -> anyhow::Result<()> {
+        let mut site = self.site.get().await;
+        site.policy = policy;
+        site.validate()?;
+        self.site.set(site).await;
+        {
+            let mut document = self.config.lock().await;
+            site_table(&mut document).insert("policy", value(policy.key()));
         }
+        self.update_magnetic().await;
+        self.update_file().await
     }
     pub async fn update_orientation(&self, orientation: UnitQuaternion<f32>) {
         self.alingment_data.set_alignment(Some(orientation)).await;
@@ -174,7 +424,7 @@ impl Storage {
 
         let (roll, pitch, yaw) = quat.euler_angles();
         Some(Orientation {
-            quaternion: quat.clone(),
+            quaternion: quat,
             euler: EulerAngle {
                 roll: roll.to_degrees(),
                 pitch: pitch.to_degrees(),
@@ -182,24 +432,476 @@ impl Storage {
             },
         })
     }
-    pub async fn get_bno055_calib(&self) -> Option<BNO055Calibration> {
+    /// Gespeichertes Kalibrierprofil des Lagesensors
+    pub async fn get_sensor_calibration(&self, kind: SensorKind) -> Option<Vec<u8>> {
         let document = self.config.lock().await;
 
-        document["sensors"]["bno055"]["calibration"]
+        sensor_item(&document, kind.key())?
+            .get("calibration")?
             .as_str()
-            .map(|s| String::from(s))
-            .map(|string| -> BNO055Calibration { vec_to_calib(hex_decode(string.as_str())) })
+            .map(hex_decode)
+    }
+    pub async fn set_sensor_calibration(&self, kind: SensorKind, profile: &[u8]) -> anyhow::Result<()> {
+        {
+            let mut document = self.config.lock().await;
+
+            document["sensors"][kind.key()]["calibration"] = value(hex_encode(profile));
+        }
+        // Sperre vorher freigeben, `update_file` sperrt selbst
+        self.update_file().await
+    }
+    pub async fn get_sensor_kind(&self) -> anyhow::Result<SensorKind> {
+        let document = self.config.lock().await;
+
+        match sensor_item(&document, "orientation").and_then(Item::as_str) {
+            None => Ok(SensorKind::default()),
+            Some("bno055") => Ok(SensorKind::Bno055),
+            Some("mpu9250") => Ok(SensorKind::Mpu9250),
+            Some(other) => anyhow::bail!(
+                "Unknown orientation sensor \"{other}\", expected \"bno055\" or \"mpu9250\""
+            ),
+        }
+    }
+    /// Bus, Adresse und Betriebsart aus `[sensors.bno055]`, geprüft
+    pub async fn get_bno055_config(&self) -> anyhow::Result<Bno055Config> {
+        let document = self.config.lock().await;
+
+        let config: Bno055Config = sensor_section(&document, SensorKind::Bno055)?;
+        config.validate()?;
+        Ok(config)
+    }
+    /// `[sensors.mpu9250]`, geprüft
+    pub async fn get_mpu9250_config(&self) -> anyhow::Result<Mpu9250Config> {
+        let document = self.config.lock().await;
+
+        let config: Mpu9250Config = sensor_section(&document, SensorKind::Mpu9250)?;
+        config.validate()?;
+        Ok(config)
+    }
+    pub async fn get_sensor_status(&self) -> SensorStatus {
+        self.sensor_status.get().await
+    }
+    pub async fn set_sensor_status(&self, status: SensorStatus) {
+        self.sensor_status.set(status).await;
+    }
+    /// BCM-Pin eines optionalen Not-Aus-Tasters
+    pub async fn get_estop_pin(&self) -> Option<u8> {
+        let document = self.config.lock().await;
+
+        mount_item(&document, "estop_pin")?
+            .as_integer()
+            .and_then(|pin| u8::try_from(pin).ok())
+    }
+    /// Abstand, in dem die Achsstellung gespeichert wird; `None` (0) speichert sie nur
+    /// beim Parken und Beenden
+    pub async fn get_position_save_interval(&self) -> anyhow::Result<Option<Duration>> {
+        let document = self.config.lock().await;
+
+        let Some(item) = mount_item(&document, "position_save_interval") else {
+            return Ok(Some(DEFAULT_POSITION_SAVE_INTERVAL));
+        };
+        match as_f32(item) {
+            Some(0.0) => Ok(None),
+            Some(seconds) if seconds.is_finite() && seconds > 0.0 => {
+                Ok(Some(Duration::from_secs_f32(seconds)))
+            }
+            _ => anyhow::bail!(
+                "mount: position_save_interval must be 0 or a positive number of seconds"
+            ),
+        }
+    }
+    /// Hardware einer Achse aus `[mount.<axis>]`, geprüft
+    /// `[mount.alt]` bzw. `[mount.az]`, ohne Abschnitt die früher fest eingebauten Werte
+    pub async fn get_axis_config(&self, axis: &str) -> anyhow::Result<AxisConfig> {
+        match self.get_optional_axis_config(axis).await? {
+            Some(config) => Ok(config),
+            None => AxisConfig::builtin(axis).ok_or_else(|| {
+                anyhow::anyhow!("Missing [mount.{axis}] section in {}", config_path())
+            }),
+        }
+    }
+    /// Optionaler Bildfeldrotator aus `[mount.derotator]`
+    pub async fn get_derotator_config(&self) -> anyhow::Result<Option<AxisConfig>> {
+        self.get_optional_axis_config("derotator").await
+    }
+    pub async fn get_optional_axis_config(&self, axis: &str) -> anyhow::Result<Option<AxisConfig>> {
+        let document = self.config.lock().await;
+
+        let Some(table) = mount_item(&document, axis).and_then(Item::as_table) else {
+            return Ok(None);
+        };
+        let config: AxisConfig = toml_edit::de::from_document(DocumentMut::from(table.clone()))
+            .map_err(|e| anyhow::anyhow!("Invalid [mount.{axis}] section: {e}"))?;
+        config.validate(axis)?;
+        Ok(Some(config))
+    }
+    /// Zusätzliche Katalogdateien aus `[catalog]`
+    pub async fn get_catalog_config(&self) -> anyhow::Result<CatalogConfig> {
+        let document = self.config.lock().await;
+
+        let Some(table) = document.get("catalog").and_then(Item::as_table) else {
+            return Ok(CatalogConfig::default());
+        };
+        toml_edit::de::from_document(DocumentMut::from(table.clone()))
+            .map_err(|e| anyhow::anyhow!("Invalid [catalog] section: {e}"))
+    }
+    /// Korrekturen für den scheinbaren Ort aus `[astronomy]`
+    pub async fn get_astronomy_config(&self) -> anyhow::Result<AstronomyConfig> {
+        let document = self.config.lock().await;
+
+        let Some(table) = document.get("astronomy").and_then(Item::as_table) else {
+            return Ok(AstronomyConfig::default());
+        };
+        toml_edit::de::from_document(DocumentMut::from(table.clone()))
+            .map_err(|e| anyhow::anyhow!("Invalid [astronomy] section: {e}"))
     }
-    pub async fn set_bno055_calib(&self, calib: BNO055Calibration) -> anyhow::Result<()> {
-        let mut document = self.config.lock().await;
+    /// GNSS-Quelle aus `[gnss]`
+    pub async fn get_gnss_config(&self) -> anyhow::Result<GnssConfig> {
+        let document = self.config.lock().await;
+
+        let Some(table) = document.get("gnss").and_then(Item::as_table) else {
+            return Ok(GnssConfig::default());
+        };
+        toml_edit::de::from_document(DocumentMut::from(table.clone()))
+            .map_err(|e| anyhow::anyhow!("Invalid [gnss] section: {e}"))
+    }
+    /// Zeitquelle und Umgang mit der Systemuhr aus `[time]`, geprüft
+    pub async fn get_time_config(&self) -> anyhow::Result<TimeConfig> {
+        let document = self.config.lock().await;
+
+        let Some(table) = document.get("time").and_then(Item::as_table) else {
+            return Ok(TimeConfig::default());
+        };
+        let config: TimeConfig = toml_edit::de::from_document(DocumentMut::from(table.clone()))
+            .map_err(|e| anyhow::anyhow!("Invalid [time] section: {e}"))?;
+        config.validate()?;
+        Ok(config)
+    }
+    /// Fokussierer aus `[focuser]`, geprüft
+    pub async fn get_focuser_config(&self) -> anyhow::Result<Option<FocuserConfig>> {
+        let document = self.config.lock().await;
+
+        let Some(table) = document.get("focuser").and_then(Item::as_table) else {
+            return Ok(None);
+        };
+        let config: FocuserConfig = toml_edit::de::from_document(DocumentMut::from(table.clone()))
+            .map_err(|e| anyhow::anyhow!("Invalid [focuser] section: {e}"))?;
+        config.validate()?;
+        Ok(Some(config))
+    }
+    pub async fn set_focuser_position(&self, position: u32) -> anyhow::Result<()> {
+        {
+            let mut document = self.config.lock().await;
+
+            document["focuser"]["position"] = value(position as i64);
+        }
+        self.update_file().await
+    }
+    /// Schaltausgänge aus `[[switch]]`, geprüft
+    pub async fn get_switch_configs(&self) -> anyhow::Result<Vec<SwitchConfig>> {
+        let document = self.config.lock().await;
+
+        let Some(tables) = document.get("switch") else {
+            return Ok(Vec::new());
+        };
+        let Some(tables) = tables.as_array_of_tables() else {
+            anyhow::bail!("switch must be an array of tables ([[switch]])");
+        };
+        tables
+            .iter()
+            .enumerate()
+            .map(|(index, table)| {
+                let config: SwitchConfig =
+                    toml_edit::de::from_document(DocumentMut::from(table.clone()))
+                        .map_err(|e| anyhow::anyhow!("Invalid [[switch]] entry {index}: {e}"))?;
+                config.validate(index)?;
+                Ok(config)
+            })
+            .collect()
+    }
+    pub async fn get_backend_kind(&self) -> anyhow::Result<BackendKind> {
+        let document = self.config.lock().await;
+
+        match mount_item(&document, "backend").and_then(Item::as_str) {
+            None => Ok(BackendKind::default()),
+            Some("gpio") => Ok(BackendKind::Gpio),
+            Some("simulated") => Ok(BackendKind::Simulated),
+            Some(other) => anyhow::bail!(
+                "Unknown mount backend \"{other}\", expected \"gpio\" or \"simulated\""
+            ),
+        }
+    }
+    /// Regelung über den Lagesensor aus `[mount.closed_loop]`, ohne Abschnitt abgeschaltet
+    pub async fn get_closed_loop_config(&self) -> anyhow::Result<ClosedLoopConfig> {
+        let document = self.config.lock().await;
+
+        let Some(table) = mount_item(&document, "closed_loop").and_then(Item::as_table) else {
+            return Ok(ClosedLoopConfig::default());
+        };
+        let config: ClosedLoopConfig =
+            toml_edit::de::from_document(DocumentMut::from(table.clone()))
+                .map_err(|e| anyhow::anyhow!("Invalid [mount.closed_loop] section: {e}"))?;
+        config.validate()?;
+        Ok(config)
+    }
+    pub async fn get_park_position(&self) -> AltAZPostion {
+        self.get_mount_position("park").await.unwrap_or(DEFAULT_PARK)
+    }
+    pub async fn set_park_position(&self, position: AltAZPostion) -> anyhow::Result<()> {
+        self.get_limits().await.check_altitude_range(position)?;
+        self.set_mount_position("park", position).await
+    }
+    pub async fn get_home_position(&self) -> AltAZPostion {
+        self.get_mount_position("home").await.unwrap_or(DEFAULT_HOME)
+    }
+    /// Zuletzt bekannte Achsstellung, damit die Position einen Neustart übersteht
+    pub async fn get_last_position(&self) -> Option<AltAZPostion> {
+        self.get_mount_position("last_position").await
+    }
+    pub async fn set_last_position(&self, position: AltAZPostion) -> anyhow::Result<()> {
+        self.set_mount_position("last_position", position).await
+    }
+    pub async fn get_parked(&self) -> bool {
+        let document = self.config.lock().await;
+
+        mount_item(&document, "parked")
+            .and_then(Item::as_bool)
+            .unwrap_or(false)
+    }
+    pub async fn set_parked(&self, parked: bool) -> anyhow::Result<()> {
+        {
+            let mut document = self.config.lock().await;
+
+            document["mount"]["parked"] = value(parked);
+        }
+        self.update_file().await
+    }
+    async fn get_mount_position(&self, key: &str) -> Option<AltAZPostion> {
+        let document = self.config.lock().await;
+
+        let position = mount_item(&document, key)?;
+        Some(AltAZPostion {
+            alt: as_f32(position.get("alt")?)?,
+            az: as_f32(position.get("az")?)?,
+        })
+    }
+    async fn set_mount_position(&self, key: &str, position: AltAZPostion) -> anyhow::Result<()> {
+        {
+            let mut document = self.config.lock().await;
+
+            document["mount"][key]["alt"] = value(position.alt as f64);
+            document["mount"][key]["az"] = value(position.az as f64);
+        }
+        self.update_file().await
+    }
+    /// Höhen-, Horizont- und Kabelgrenzen aus `[mount.limits]`
+    pub async fn get_limits(&self) -> LimitsConfig {
+        self.limits.get().await
+    }
+    /// Ersetzt das Horizontprofil durch `horizon` (leer: kein Profil) und trägt es in
+    /// `[mount.limits]` ein, eine `horizon_file` wird dabei entfernt
+    pub async fn set_horizon(&self, horizon: Vec<[f32; 2]>) -> anyhow::Result<()> {
+        let mut limits = self.get_limits().await;
+        limits.horizon = horizon;
+        limits.horizon_file = None;
+        {
+            let mut document = self.config.lock().await;
 
-        document["sensors"]["bno055"]["calibration"] = value(hex_encode(calib.as_bytes()));
+            let limits_table = &mut document["mount"]["limits"];
+            if let Some(table) = limits_table.as_table_like_mut() {
+                table.remove("horizon_file");
+            }
+            if limits.horizon.is_empty() {
+                if let Some(table) = limits_table.as_table_like_mut() {
+                    table.remove("horizon");
+                }
+            } else {
+                let mut points: Array = limits
+                    .horizon
+                    .iter()
+                    .map(|[az, alt]| Value::Array(Array::from_iter([*az as f64, *alt as f64])))
+                    .collect();
+                // Ein Punkt pro Zeile
+                for point in points.iter_mut() {
+                    point.decor_mut().set_prefix("\n    ");
+                }
+                points.set_trailing("\n");
+                points.set_trailing_comma(true);
+                limits_table["horizon"] = value(points);
+            }
+        }
+        self.limits.set(limits).await;
+        self.update_file().await
+    }
+    /// Pointing-Modell für die Umrechnung zwischen Achsstellung und Himmel
+    pub async fn get_pointing_model(&self) -> PointingModel {
+        self.alignment.get().await.pointing_model()
+    }
+    pub async fn get_alignment_stars(&self) -> Vec<AlignmentStar> {
+        self.alignment.get().await.stars
+    }
+    pub async fn get_pointing_statistics(&self) -> ModelStatistics {
+        let alignment = self.alignment.get().await;
+        alignment.model.statistics(&alignment.stars, alignment.anchor)
+    }
+    pub async fn add_alignment_star(&self, star: AlignmentStar) -> anyhow::Result<()> {
+        let mut stars = self.get_alignment_stars().await;
+        add_star(&mut stars, star);
+        self.set_alignment_stars(stars, Some(star)).await
+    }
+    pub async fn remove_alignment_star(&self, index: usize) -> anyhow::Result<()> {
+        let mut stars = self.get_alignment_stars().await;
+        if index >= stars.len() {
+            anyhow::bail!("No alignment star {index}, there are {}", stars.len());
+        }
+        stars.remove(index);
+        self.set_alignment_stars(stars, None).await
+    }
+    pub async fn clear_alignment(&self) -> anyhow::Result<()> {
+        self.set_alignment_stars(Vec::new(), None).await
+    }
+    /// Bestimmt Korrektur und Pointing-Modell aus den Sternen neu und speichert alles in
+    /// `[alignment]`. Die Sensorkorrektur mittelt über alle Sterne, nach einem Sync auf
+    /// `synced` trifft das Pointing-Modell dessen Ablage exakt.
+    async fn set_alignment_stars(
+        &self,
+        stars: Vec<AlignmentStar>,
+        synced: Option<AlignmentStar>,
+    ) -> anyhow::Result<()> {
+        let model = PointingModel::fit(&stars);
+        // `stars` enthält den synchronisierten Stern bereits
+        let correction = fit_correction(&stars);
+        let alignment = AlignmentConfig {
+            correction: correction.map(|q| [q.w, q.i, q.j, q.k]),
+            model,
+            anchor: synced.map(|star| model.anchor(&star)),
+            stars,
+        };
+        let item = alignment_item(&alignment)?;
+        self.alingment_data.set_correction(correction).await;
+        self.alignment.set(alignment).await;
+        {
+            let mut document = self.config.lock().await;
+            document["alignment"] = item;
+        }
         self.update_file().await
     }
     pub async fn update_file(&self) -> anyhow::Result<()> {
         let document = self.config.lock().await;
         let string = document.to_string();
-        fs::write(CONFIG_PATH, string)?;
+        fs::write(config_path(), string)?;
         Ok(())
     }
 }
+
+/// Eintrag aus `[sensors]`, `None` wenn der Abschnitt oder der Schlüssel fehlt
+fn sensor_item<'a>(document: &'a DocumentMut, key: &str) -> Option<&'a Item> {
+    document.get("sensors")?.get(key)
+}
+
+/// Liest `[sensors.<kind>]`, ohne Abschnitt gelten die Standardwerte
+fn sensor_section<T: DeserializeOwned + Default>(
+    document: &DocumentMut,
+    kind: SensorKind,
+) -> anyhow::Result<T> {
+    let Some(table) = sensor_item(document, kind.key()).and_then(Item::as_table) else {
+        return Ok(T::default());
+    };
+    let mut table = table.clone();
+    // Das Kalibrierprofil verwaltet `get_sensor_calibration`
+    table.remove("calibration");
+    toml_edit::de::from_document(DocumentMut::from(table))
+        .map_err(|e| anyhow::anyhow!("Invalid [sensors.{}] section: {e}", kind.key()))
+}
+
+/// Liest `[alignment]`, ohne Abschnitt gibt es weder Sterne noch Korrektur
+fn alignment_config(document: &DocumentMut) -> anyhow::Result<AlignmentConfig> {
+    let Some(table) = document.get("alignment").and_then(Item::as_table) else {
+        return Ok(AlignmentConfig::default());
+    };
+    toml_edit::de::from_document(DocumentMut::from(table.clone()))
+        .map_err(|e| anyhow::anyhow!("Invalid [alignment] section: {e}"))
+}
+
+/// `[alignment]` mit `[alignment.model]` und `[[alignment.stars]]` statt Inline-Tabellen
+fn alignment_item(alignment: &AlignmentConfig) -> anyhow::Result<Item> {
+    let mut table = toml_edit::ser::to_document(alignment)?.as_table().clone();
+    for key in ["model", "anchor"] {
+        if let Some(terms) = table.get(key).and_then(Item::as_inline_table) {
+            let terms = terms.clone().into_table();
+            table.insert(key, Item::Table(terms));
+        }
+    }
+    if let Some(stars) = table.get("stars").and_then(Item::as_array) {
+        let mut tables = ArrayOfTables::new();
+        for star in stars.iter().filter_map(Value::as_inline_table) {
+            tables.push(star.clone().into_table());
+        }
+        table.insert("stars", Item::ArrayOfTables(tables));
+    }
+    Ok(Item::Table(table))
+}
+
+/// Liest `[mount.limits]` und die darin genannte Horizontdatei, ohne Abschnitt ist
+/// nur der Bereich über dem mathematischen Horizont erlaubt
+fn limits_config(document: &DocumentMut) -> anyhow::Result<LimitsConfig> {
+    let Some(table) = mount_item(document, "limits").and_then(Item::as_table) else {
+        return Ok(LimitsConfig::default());
+    };
+    let mut limits: LimitsConfig = toml_edit::de::from_document(DocumentMut::from(table.clone()))
+        .map_err(|e| anyhow::anyhow!("Invalid [mount.limits] section: {e}"))?;
+    limits.validate()?;
+    if let Some(path) = &limits.horizon_file {
+        let text = fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Could not read horizon file {path}: {e}"))?;
+        limits.horizon = parse_horizon(&text)
+            .map_err(|e| anyhow::anyhow!("Invalid horizon file {path}: {e}"))?;
+    }
+    Ok(limits)
+}
+
+/// Liest `[site]`, ohne Abschnitt zuerst GNSS und ohne Lösung 0°/0°
+fn site_config(document: &DocumentMut) -> anyhow::Result<SiteConfig> {
+    let Some(table) = document.get("site").and_then(Item::as_table) else {
+        return Ok(SiteConfig::default());
+    };
+    let site: SiteConfig = toml_edit::de::from_document(DocumentMut::from(table.clone()))
+        .map_err(|e| anyhow::anyhow!("Invalid [site] section: {e}"))?;
+    site.validate()?;
+    Ok(site)
+}
+
+/// `[site]`, wird bei Bedarf am Ende der Datei angelegt
+fn site_table(document: &mut DocumentMut) -> &mut Table {
+    if document.get("site").and_then(Item::as_table).is_none() {
+        let mut table = Table::new();
+        table.set_implicit(true);
+        document.insert("site", Item::Table(table));
+    }
+    document["site"].as_table_mut().expect("[site] was just created")
+}
+
+/// Schreibt `[site.<key>]` als eigene Tabelle statt inline
+fn set_site_location(document: &mut DocumentMut, key: &str, location: SiteLocation) {
+    let mut table = Table::new();
+    table.insert("latitude", value(location.latitude));
+    table.insert("longitude", value(location.longitude));
+    table.insert("elevation", value(location.elevation as f64));
+    site_table(document).insert(key, Item::Table(table));
+}
+
+/// Eintrag aus `[mount]`, `None` wenn der Abschnitt oder der Schlüssel fehlt
+fn mount_item<'a>(document: &'a DocumentMut, key: &str) -> Option<&'a Item> {
+    document.get("mount")?.get(key)
+}
+
+/// Liest Zahlen unabhängig davon, ob sie mit oder ohne Nachkommastellen eingetragen sind
+fn as_f32(item: &Item) -> Option<f32> {
+    item.as_float()
+        .or_else(|| item.as_integer().map(|integer| integer as f64))
+        .map(|float| float as f32)
+}
+
+fn config_path() -> String {
+    std::env::var(CONFIG_PATH_ENV).unwrap_or_else(|_| CONFIG_PATH.to_owned())
+}
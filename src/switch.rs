@@ -0,0 +1,153 @@
+use anyhow::{bail, Context, Result};
+use rppal::gpio::{Gpio, OutputPin};
+use serde::Deserialize;
+use std::sync::{Mutex, OnceLock};
+
+use crate::mount_backend::BackendKind;
+use crate::mount_config::check_pins;
+use crate::storage::storage;
+
+fn default_frequency() -> f64 {
+    100.0
+}
+
+/// `[[switch]]`: Ausgang an einem GPIO-Pin, z. B. ein Relais oder eine Taukappenheizung
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SwitchConfig {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// BCM-Pin
+    pub pin: u8,
+    /// Software-PWM mit 0..100 % statt Ein/Aus
+    #[serde(default)]
+    pub pwm: bool,
+    /// PWM-Frequenz in Hz
+    #[serde(default = "default_frequency")]
+    pub frequency: f64,
+    /// Eingeschaltet bei LOW, wie bei vielen Relaismodulen
+    #[serde(default)]
+    pub active_low: bool,
+    /// Wert beim Start, 0/1 bzw. Prozent
+    #[serde(default)]
+    pub value: f64,
+}
+
+impl SwitchConfig {
+    pub fn max_value(&self) -> f64 {
+        if self.pwm {
+            100.0
+        } else {
+            1.0
+        }
+    }
+
+    pub fn validate(&self, index: usize) -> Result<()> {
+        let section = format!("switch[{index}]");
+        check_pins(&section, &[self.pin])?;
+        if self.name.trim().is_empty() {
+            bail!("{section}: name must not be empty");
+        }
+        if !self.frequency.is_finite() || self.frequency <= 0.0 {
+            bail!("{section}: frequency must be a positive number");
+        }
+        if !(0.0..=self.max_value()).contains(&self.value) {
+            bail!("{section}: value must be in 0..={}", self.max_value());
+        }
+        Ok(())
+    }
+}
+
+static SWITCHES: OnceLock<Vec<Switch>> = OnceLock::new();
+
+/// Ausgänge aus `[[switch]]` in der Reihenfolge der Konfiguration
+pub fn switches() -> &'static [Switch] {
+    SWITCHES.get().map(Vec::as_slice).unwrap_or_default()
+}
+
+/// Liest `[[switch]]` und schaltet die Ausgänge auf ihren Startwert
+pub(crate) async fn init_switches() -> Result<()> {
+    let configs = storage().get_switch_configs().await?;
+    let pins: Vec<u8> = configs.iter().map(|config| config.pin).collect();
+    check_pins("switch", &pins)?;
+    let gpio = match storage().get_backend_kind().await? {
+        BackendKind::Gpio if !configs.is_empty() => {
+            Some(Gpio::new().context("Failed to initialize GPIO")?)
+        }
+        _ => None,
+    };
+    let mut switches = Vec::with_capacity(configs.len());
+    for config in configs {
+        let pin = match &gpio {
+            Some(gpio) => Some(
+                gpio.get(config.pin)
+                    .with_context(|| format!("switch \"{}\": GPIO {} is not available", config.name, config.pin))?
+                    .into_output(),
+            ),
+            None => None,
+        };
+        let switch = Switch {
+            output: Mutex::new(Output { pin, value: 0.0 }),
+            config,
+        };
+        switch.set_value(switch.config.value)?;
+        switches.push(switch);
+    }
+    if SWITCHES.set(switches).is_err() {
+        bail!("Switches are already initialized");
+    }
+    Ok(())
+}
+
+/// Ohne Pin (simuliertes Backend) wird nur der Wert gespeichert
+#[derive(Debug)]
+struct Output {
+    pin: Option<OutputPin>,
+    value: f64,
+}
+
+#[derive(Debug)]
+pub struct Switch {
+    config: SwitchConfig,
+    output: Mutex<Output>,
+}
+
+impl Switch {
+    pub fn config(&self) -> &SwitchConfig {
+        &self.config
+    }
+
+    pub fn value(&self) -> f64 {
+        self.output.lock().expect("switch output poisoned").value
+    }
+
+    /// Ein/Aus-Ausgänge schalten ab 0.5 ein, PWM-Ausgänge nehmen den Wert in Prozent
+    pub fn set_value(&self, value: f64) -> Result<()> {
+        if !(0.0..=self.config.max_value()).contains(&value) {
+            bail!(
+                "Value {value} for switch \"{}\" is outside 0..={}",
+                self.config.name,
+                self.config.max_value()
+            );
+        }
+        let value = if self.config.pwm { value } else { value.round() };
+        let mut output = self.output.lock().expect("switch output poisoned");
+        if let Some(pin) = output.pin.as_mut() {
+            let duty = value / self.config.max_value();
+            let duty = if self.config.active_low { 1.0 - duty } else { duty };
+            pin.clear_pwm()
+                .with_context(|| format!("switch \"{}\": PWM failed", self.config.name))?;
+            if duty <= 0.0 {
+                pin.set_low();
+            } else if duty >= 1.0 {
+                pin.set_high();
+            } else {
+                pin.set_pwm_frequency(self.config.frequency, duty)
+                    .with_context(|| format!("switch \"{}\": PWM failed", self.config.name))?;
+            }
+        }
+        output.value = value;
+        Ok(())
+    }
+}
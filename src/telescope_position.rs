@@ -1,33 +1,57 @@
-#[derive(Debug, Clone, Copy)]
+use chrono::{DateTime, Utc};
+use open_pi_scope::gnss::Position;
+
+use crate::astronomy::{
+    apparent_to_mean, equatorial_to_horizontal, horizontal_to_equatorial, local_sidereal_time,
+    mean_to_apparent,
+};
+
+/// Horizontale Koordinaten in Grad, Azimut von Nord über Ost
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct AltAZPostion {
     pub alt: f32,
     pub az: f32,
 }
 
+/// Äquatoriale Koordinaten J2000.0, Rektaszension in Stunden, Deklination in Grad
 #[derive(Debug, Clone, Copy)]
 pub struct EqPostion {
     pub ra: f32,
     pub dec: f32,
 }
 
-impl Into<AltAZPostion> for EqPostion {
-    fn into(self) -> AltAZPostion {
-        // Placeholder conversion logic, should be replaced with actual conversion
+impl EqPostion {
+    /// Rechnet den mittleren Ort J2000.0 in den scheinbaren Horizontort am Standort um
+    pub fn to_alt_az(self, site: &Position, time: DateTime<Utc>) -> AltAZPostion {
+        let (ra, dec) = mean_to_apparent(
+            (self.ra as f64 * 15.0).to_radians(),
+            (self.dec as f64).to_radians(),
+            time,
+        );
+        let lst = local_sidereal_time(time, site.longitude).to_radians();
+        let (alt, az) = equatorial_to_horizontal(lst - ra, dec, site.latitude.to_radians());
         AltAZPostion {
-            alt: self.dec,
-            az: self.ra,
+            alt: alt.to_degrees() as f32,
+            az: az.to_degrees() as f32,
         }
-    } 
+    }
 }
 
-impl Into<EqPostion> for AltAZPostion {
-    fn into(self) -> EqPostion {
-        // Placeholder conversion logic, should be replaced with actual conversion
+impl AltAZPostion {
+    /// Rechnet den Horizontort am Standort in den mittleren Ort J2000.0 um
+    pub fn to_eq(self, site: &Position, time: DateTime<Utc>) -> EqPostion {
+        let (hour_angle, dec) = horizontal_to_equatorial(
+            (self.alt as f64).to_radians(),
+            (self.az as f64).to_radians(),
+            site.latitude.to_radians(),
+        );
+        let lst = local_sidereal_time(time, site.longitude).to_radians();
+        let (ra, dec) = apparent_to_mean(lst - hour_angle, dec, time);
         EqPostion {
-            ra: self.az,
-            dec: self.alt,
+            ra: (ra.to_degrees() / 15.0) as f32,
+            dec: dec.to_degrees() as f32,
         }
-    }  
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -44,18 +68,18 @@ impl TelescopePosition {
     pub fn new_eq(ra: f32, dec: f32) -> Self {
         TelescopePosition::Eq(EqPostion { ra, dec })
     }
-    
-    pub fn get_alt_az(&self) -> AltAZPostion {
+
+    pub fn get_alt_az(&self, site: &Position, time: DateTime<Utc>) -> AltAZPostion {
         match self {
-            TelescopePosition::AltAz(pos) => pos.clone(),
-            TelescopePosition::Eq(pos) => pos.clone().into(),
+            TelescopePosition::AltAz(pos) => *pos,
+            TelescopePosition::Eq(pos) => pos.to_alt_az(site, time),
         }
     }
 
-    pub fn get_eq(&self) -> EqPostion {
+    pub fn get_eq(&self, site: &Position, time: DateTime<Utc>) -> EqPostion {
         match self {
-            TelescopePosition::Eq(pos) => pos.clone(),
-            TelescopePosition::AltAz(pos) => pos.clone().into(),
+            TelescopePosition::Eq(pos) => *pos,
+            TelescopePosition::AltAz(pos) => pos.to_eq(site, time),
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,70 @@
+use ascom_alpaca::api::DriveRate;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::telescope_position::EqPostion;
+
+/// Siderische Nachführrate in Bogensekunden pro Sekunde
+const SIDEREAL_RATE: f64 = 15.041;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+pub enum TrackingRate {
+    #[default]
+    Sidereal,
+    Lunar,
+    Solar,
+    King,
+}
+
+impl TrackingRate {
+    pub const ALL: [TrackingRate; 4] = [
+        TrackingRate::Sidereal,
+        TrackingRate::Lunar,
+        TrackingRate::Solar,
+        TrackingRate::King,
+    ];
+
+    /// Nachführrate in Bogensekunden pro Sekunde
+    pub fn arcsec_per_second(&self) -> f64 {
+        match self {
+            TrackingRate::Sidereal => SIDEREAL_RATE,
+            TrackingRate::Lunar => 14.685,
+            TrackingRate::Solar => 15.0,
+            TrackingRate::King => 15.0369,
+        }
+    }
+
+    /// Verschiebt das Ziel in Rektaszension, so dass es mit dieser Rate statt
+    /// siderisch nachgeführt wird (Mond und Sonne wandern nach Osten).
+    pub fn apply(&self, target: EqPostion, since: DateTime<Utc>, now: DateTime<Utc>) -> EqPostion {
+        let elapsed = (now - since).num_milliseconds() as f64 / 1000.0;
+        // Bogensekunden -> Stunden
+        let drift = (SIDEREAL_RATE - self.arcsec_per_second()) * elapsed / 54_000.0;
+        EqPostion {
+            ra: (target.ra as f64 + drift).rem_euclid(24.0) as f32,
+            dec: target.dec,
+        }
+    }
+}
+
+impl From<DriveRate> for TrackingRate {
+    fn from(value: DriveRate) -> Self {
+        match value {
+            DriveRate::Sidereal => TrackingRate::Sidereal,
+            DriveRate::Lunar => TrackingRate::Lunar,
+            DriveRate::Solar => TrackingRate::Solar,
+            DriveRate::King => TrackingRate::King,
+        }
+    }
+}
+
+impl From<TrackingRate> for DriveRate {
+    fn from(value: TrackingRate) -> Self {
+        match value {
+            TrackingRate::Sidereal => DriveRate::Sidereal,
+            TrackingRate::Lunar => DriveRate::Lunar,
+            TrackingRate::Solar => DriveRate::Solar,
+            TrackingRate::King => DriveRate::King,
+        }
+    }
+}
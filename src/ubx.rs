@@ -0,0 +1,314 @@
+//! u-blox UBX von einem direkt angeschlossenen Empfänger: NAV-PVT für die Lösung, NAV-SAT
+//! für die Satelliten. Die GNSS-IDs von u-blox entsprechen `GnssSystem`.
+
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use open_pi_scope::gnss::{GnssSystem, Mode, Satellite};
+
+use crate::gnss_source::{GnssFix, GnssParser, GnssUpdate};
+
+const SYNC: [u8; 2] = [0xB5, 0x62];
+/// Sync, Klasse, ID und Länge
+const HEADER_LENGTH: usize = 6;
+/// NAV-SAT mit vielen Satelliten bleibt deutlich darunter
+const MAX_PAYLOAD: usize = 4096;
+
+const CLASS_NAV: u8 = 0x01;
+const ID_NAV_PVT: u8 = 0x07;
+const ID_NAV_SAT: u8 = 0x35;
+const CLASS_CFG: u8 = 0x06;
+const ID_CFG_MSG: u8 = 0x01;
+
+#[derive(Debug, Default)]
+pub struct UbxParser {
+    buffer: Vec<u8>,
+}
+
+impl GnssParser for UbxParser {
+    fn feed(&mut self, data: &[u8], updates: &mut Vec<GnssUpdate>) {
+        self.buffer.extend_from_slice(data);
+        loop {
+            // Bis zum nächsten Sync verwerfen, dazwischen liegt z. B. NMEA
+            match self.buffer.windows(2).position(|window| window == SYNC) {
+                Some(start) => {
+                    self.buffer.drain(..start);
+                }
+                None => {
+                    let keep = usize::from(self.buffer.last() == Some(&SYNC[0]));
+                    self.buffer.drain(..self.buffer.len() - keep);
+                    return;
+                }
+            }
+            if self.buffer.len() < HEADER_LENGTH {
+                return;
+            }
+            let length = u16::from_le_bytes([self.buffer[4], self.buffer[5]]) as usize;
+            if length > MAX_PAYLOAD {
+                self.buffer.drain(..SYNC.len());
+                continue;
+            }
+            let end = HEADER_LENGTH + length + 2;
+            if self.buffer.len() < end {
+                return;
+            }
+            if checksum(&self.buffer[2..end - 2]) != [self.buffer[end - 2], self.buffer[end - 1]] {
+                self.buffer.drain(..SYNC.len());
+                continue;
+            }
+            let frame: Vec<u8> = self.buffer.drain(..end).collect();
+            let payload = &frame[HEADER_LENGTH..end - 2];
+            match (frame[2], frame[3]) {
+                (CLASS_NAV, ID_NAV_PVT) => updates.extend(nav_pvt(payload).map(GnssUpdate::Fix)),
+                (CLASS_NAV, ID_NAV_SAT) => {
+                    updates.extend(nav_sat(payload).map(GnssUpdate::Satellites))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Schaltet NAV-PVT und NAV-SAT auf der aktuellen Schnittstelle ein (UBX-CFG-MSG).
+    /// Empfänger ab M10 kennen das nicht mehr, dort per CFG-VALSET oder u-center.
+    fn init_commands(&self) -> Vec<Vec<u8>> {
+        [ID_NAV_PVT, ID_NAV_SAT]
+            .into_iter()
+            .map(|id| frame(CLASS_CFG, ID_CFG_MSG, &[CLASS_NAV, id, 1]))
+            .collect()
+    }
+}
+
+/// 8-Bit-Fletcher über Klasse, ID, Länge und Nutzdaten
+fn checksum(data: &[u8]) -> [u8; 2] {
+    data.iter().fold([0u8, 0u8], |[a, b], &byte| {
+        let a = a.wrapping_add(byte);
+        [a, b.wrapping_add(a)]
+    })
+}
+
+fn frame(class: u8, id: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = SYNC.to_vec();
+    frame.extend([class, id]);
+    frame.extend((payload.len() as u16).to_le_bytes());
+    frame.extend(payload);
+    let checksum = checksum(&frame[2..]);
+    frame.extend(checksum);
+    frame
+}
+
+fn u32_at(payload: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(payload[offset..offset + 4].try_into().unwrap())
+}
+
+fn i32_at(payload: &[u8], offset: usize) -> i32 {
+    u32_at(payload, offset) as i32
+}
+
+/// Positionen in 1e-7 Grad, Strecken in mm, Winkel in 1e-5 Grad
+fn nav_pvt(payload: &[u8]) -> Option<GnssFix> {
+    if payload.len() < 92 {
+        return None;
+    }
+    let time = pvt_time(payload);
+    let fix_ok = payload[21] & 0x01 != 0;
+    let mode = match payload[20] {
+        2 if fix_ok => Mode::Fix2d,
+        // 4: GNSS und Koppelnavigation
+        3 | 4 if fix_ok => Mode::Fix3d,
+        _ => Mode::NoFix,
+    };
+    if mode == Mode::NoFix {
+        return Some(GnssFix {
+            mode: Some(mode),
+            time,
+            ..GnssFix::default()
+        });
+    }
+    let millimeters = |offset| i32_at(payload, offset) as f32 / 1000.0;
+    let accuracy = |offset| u32_at(payload, offset) as f32 / 1000.0;
+    let horizontal = accuracy(40);
+    Some(GnssFix {
+        mode: Some(mode),
+        lon: Some(i32_at(payload, 24) as f64 * 1e-7),
+        lat: Some(i32_at(payload, 28) as f64 * 1e-7),
+        alt: (mode == Mode::Fix3d).then(|| millimeters(36)),
+        estimated_error_plane: Some(horizontal),
+        estimated_error_latitude: Some(horizontal / std::f32::consts::SQRT_2),
+        estimated_error_longitude: Some(horizontal / std::f32::consts::SQRT_2),
+        estimated_error_altitude: (mode == Mode::Fix3d).then(|| accuracy(44)),
+        estimated_error_speed: Some(accuracy(68)),
+        estimated_error_track: Some(u32_at(payload, 72) as f32 * 1e-5),
+        speed: Some(millimeters(60)),
+        track: Some(i32_at(payload, 64) as f32 * 1e-5),
+        // velD zeigt nach unten
+        climb: (mode == Mode::Fix3d).then(|| -millimeters(56)),
+        time,
+        epoch: true,
+        ..GnssFix::default()
+    })
+}
+
+/// UTC-Zeit aus NAV-PVT, nur wenn Datum und Uhrzeit gültig und vollständig aufgelöst sind.
+/// Die Nanosekunden können negativ sein.
+fn pvt_time(payload: &[u8]) -> Option<DateTime<Utc>> {
+    if payload[11] & 0x07 != 0x07 {
+        return None;
+    }
+    let year = u16::from_le_bytes([payload[4], payload[5]]);
+    let date = NaiveDate::from_ymd_opt(year as i32, payload[6] as u32, payload[7] as u32)?;
+    let time = date.and_hms_opt(payload[8] as u32, payload[9] as u32, payload[10] as u32)?;
+    Some(time.and_utc() + TimeDelta::nanoseconds(i32_at(payload, 16) as i64))
+}
+
+/// Kopf mit Anzahl in Byte 5, dann 12 Byte pro Satellit
+fn nav_sat(payload: &[u8]) -> Option<Vec<Satellite>> {
+    if payload.len() < 8 {
+        return None;
+    }
+    let count = payload[5] as usize;
+    if payload.len() < 8 + 12 * count {
+        return None;
+    }
+    let satellites = payload[8..8 + 12 * count]
+        .chunks_exact(12)
+        .map(|block| Satellite {
+            prn: block[1] as i32,
+            signal_strength: block[2] as f32,
+            elevation: block[3] as i8 as f32,
+            azimuth: i16::from_le_bytes([block[4], block[5]]) as f32,
+            used: u32_at(block, 8) & 0x08 != 0,
+            system: GnssSystem::from(block[0]),
+        })
+        .collect();
+    Some(satellites)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(data: &[u8]) -> Vec<GnssUpdate> {
+        let mut updates = Vec::new();
+        UbxParser::default().feed(data, &mut updates);
+        updates
+    }
+
+    /// 3D-Fix bei 48.1173° N, 11.5166667° O am 17.05.2024 21:30:15 UTC
+    fn nav_pvt_payload() -> Vec<u8> {
+        let mut payload = vec![0u8; 92];
+        let mut put = |offset: usize, bytes: &[u8]| {
+            payload[offset..offset + bytes.len()].copy_from_slice(bytes)
+        };
+        put(4, &2024u16.to_le_bytes());
+        put(6, &[5, 17, 21, 30, 15, 0x07]);
+        put(16, &(-500_000i32).to_le_bytes());
+        put(20, &[3, 0x01]);
+        put(24, &115_166_667i32.to_le_bytes());
+        put(28, &481_173_000i32.to_le_bytes());
+        put(36, &545_400i32.to_le_bytes());
+        put(40, &2_500u32.to_le_bytes());
+        put(44, &4_000u32.to_le_bytes());
+        put(56, &(-100i32).to_le_bytes());
+        put(60, &1_500i32.to_le_bytes());
+        put(64, &8_440_000i32.to_le_bytes());
+        put(68, &300u32.to_le_bytes());
+        put(72, &150_000u32.to_le_bytes());
+        payload
+    }
+
+    #[test]
+    fn checksum_of_known_frame() {
+        // UBX-CFG-MSG für NAV-PVT, wie ihn u-center erzeugt
+        assert_eq!(
+            frame(CLASS_CFG, ID_CFG_MSG, &[CLASS_NAV, ID_NAV_PVT, 1]),
+            [0xB5, 0x62, 0x06, 0x01, 0x03, 0x00, 0x01, 0x07, 0x01, 0x13, 0x51]
+        );
+    }
+
+    #[test]
+    fn nav_pvt() {
+        let data = frame(CLASS_NAV, ID_NAV_PVT, &nav_pvt_payload());
+        let updates = parse(&data);
+        let [GnssUpdate::Fix(fix)] = updates.as_slice() else {
+            panic!("expected one fix, got {updates:?}");
+        };
+        assert_eq!(fix.mode, Some(Mode::Fix3d));
+        assert!((fix.lat.unwrap() - 48.1173).abs() < 1e-9);
+        assert!((fix.lon.unwrap() - 11.516_666_7).abs() < 1e-9);
+        assert_eq!(fix.alt, Some(545.4));
+        assert_eq!(fix.estimated_error_plane, Some(2.5));
+        assert_eq!(fix.estimated_error_altitude, Some(4.0));
+        assert_eq!(fix.speed, Some(1.5));
+        assert_eq!(fix.climb, Some(0.1));
+        assert!((fix.track.unwrap() - 84.4).abs() < 1e-4);
+        assert!((fix.estimated_error_track.unwrap() - 1.5).abs() < 1e-6);
+        let expected = NaiveDate::from_ymd_opt(2024, 5, 17)
+            .and_then(|date| date.and_hms_opt(21, 30, 15))
+            .map(|time| time.and_utc() - TimeDelta::microseconds(500));
+        assert_eq!(fix.time, expected);
+    }
+
+    #[test]
+    fn nav_pvt_without_fix() {
+        let mut payload = nav_pvt_payload();
+        payload[21] = 0;
+        let updates = parse(&frame(CLASS_NAV, ID_NAV_PVT, &payload));
+        let [GnssUpdate::Fix(fix)] = updates.as_slice() else {
+            panic!("expected one fix, got {updates:?}");
+        };
+        assert_eq!(fix.mode, Some(Mode::NoFix));
+        assert_eq!(fix.lat, None);
+        assert!(fix.time.is_some());
+    }
+
+    #[test]
+    fn nav_sat() {
+        let mut payload = vec![0u8; 8];
+        payload[5] = 2;
+        // GPS 12, 40 dBHz, 45° hoch bei Azimut 180°, in der Lösung
+        payload.extend([0, 12, 40, 45, 180, 0, 0, 0, 0x08, 0, 0, 0]);
+        // GLONASS 5, 20 dBHz, knapp unter dem Horizont bei Azimut 300°, nicht verwendet
+        payload.extend([6, 5, 20, (-2i8) as u8, 0x2C, 0x01, 0, 0, 0, 0, 0, 0]);
+        let updates = parse(&frame(CLASS_NAV, ID_NAV_SAT, &payload));
+        assert_eq!(
+            updates,
+            [GnssUpdate::Satellites(vec![
+                Satellite {
+                    prn: 12,
+                    elevation: 45.0,
+                    azimuth: 180.0,
+                    signal_strength: 40.0,
+                    used: true,
+                    system: GnssSystem::Gps,
+                },
+                Satellite {
+                    prn: 5,
+                    elevation: -2.0,
+                    azimuth: 300.0,
+                    signal_strength: 20.0,
+                    used: false,
+                    system: GnssSystem::Glonass,
+                },
+            ])]
+        );
+    }
+
+    #[test]
+    fn frames_split_between_reads_and_mixed_with_nmea() {
+        let mut data = b"$GPGGA,123519*00\r\n".to_vec();
+        data.extend(frame(CLASS_NAV, ID_NAV_PVT, &nav_pvt_payload()));
+        let mut corrupt = frame(CLASS_NAV, ID_NAV_PVT, &nav_pvt_payload());
+        let last = corrupt.len() - 1;
+        corrupt[last] ^= 0xFF;
+        data.extend(corrupt);
+        data.extend(frame(CLASS_NAV, ID_NAV_SAT, &[0; 8]));
+
+        let mut parser = UbxParser::default();
+        let mut updates = Vec::new();
+        for chunk in data.chunks(7) {
+            parser.feed(chunk, &mut updates);
+        }
+        assert!(matches!(
+            updates.as_slice(),
+            [GnssUpdate::Fix(_), GnssUpdate::Satellites(satellites)] if satellites.is_empty()
+        ));
+    }
+}
@@ -0,0 +1,205 @@
+use std::f64::consts::PI;
+
+use chrono::{DateTime, Duration, Utc};
+use open_pi_scope::gnss::Position;
+use serde::Serialize;
+
+use crate::astronomy::{local_sidereal_time, mean_to_apparent};
+use crate::telescope_position::EqPostion;
+use crate::tracking::TrackingRate;
+
+/// So weit voraus wird nach einem Zenitdurchgang gesucht, in Sekunden
+const LOOKAHEAD_SECONDS: f64 = 3600.0;
+/// Schritte der Bisektion für den Stundenwinkel, ab dem die Achse wieder folgen kann
+const BISECTION_STEPS: u32 = 40;
+
+/// Zeitraum, in dem die Azimutachse einem Objekt nahe dem Zenit nicht folgen kann
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, utoipa::ToSchema)]
+pub struct ZenithPass {
+    /// Ab hier müsste der Azimut schneller als `max_speed` laufen
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Meridiandurchgang
+    pub culmination: DateTime<Utc>,
+    /// in Grad
+    pub max_altitude: f32,
+    /// Höchste benötigte Azimutgeschwindigkeit in Grad/s
+    pub max_az_rate: f32,
+    /// Begrenzung der Azimutachse in Grad/s
+    pub max_speed: f32,
+    /// +1: Azimut nimmt zu (Durchgang auf der Äquatorseite des Zenits), −1: nimmt ab
+    pub direction: f32,
+}
+
+impl ZenithPass {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.start <= now && now <= self.end
+    }
+}
+
+/// Sucht in der nächsten Stunde den Zeitraum, in dem ein nachgeführtes Ziel mehr als
+/// `max_speed` Grad/s in Azimut verlangt. Die Azimutgeschwindigkeit hängt nur vom
+/// Stundenwinkel ab und ist am Meridian am größten, die Grenzen folgen per Bisektion.
+pub fn predict(
+    target: EqPostion,
+    rate: TrackingRate,
+    since: DateTime<Utc>,
+    site: &Position,
+    now: DateTime<Utc>,
+    max_speed: f32,
+) -> Option<ZenithPass> {
+    let target = rate.apply(target, since, now);
+    let (ra, dec) = mean_to_apparent(
+        (target.ra as f64 * 15.0).to_radians(),
+        (target.dec as f64).to_radians(),
+        now,
+    );
+    let latitude = site.latitude.to_radians();
+    // Der Stundenwinkel wächst mit der Nachführrate, in Grad/s
+    let hour_angle_rate = rate.arcsec_per_second() / 3600.0;
+    let az_rate = |hour_angle: f64| hour_angle_rate * azimuth_rate_factor(hour_angle, dec, latitude);
+    let max_speed = max_speed as f64;
+
+    let max_az_rate = az_rate(0.0);
+    if max_az_rate <= max_speed {
+        return None;
+    }
+    let (mut inside, mut outside) = (0.0, PI);
+    for _ in 0..BISECTION_STEPS {
+        let middle = (inside + outside) / 2.0;
+        if az_rate(middle) > max_speed {
+            inside = middle;
+        } else {
+            outside = middle;
+        }
+    }
+    let seconds = |hour_angle: f64| {
+        Duration::milliseconds((hour_angle.to_degrees() / hour_angle_rate * 1000.0) as i64)
+    };
+    let half_width = seconds(inside);
+
+    let hour_angle = local_sidereal_time(now, site.longitude).to_radians() - ra;
+    let hour_angle = (hour_angle + PI).rem_euclid(2.0 * PI) - PI;
+    let mut culmination = now - seconds(hour_angle);
+    if culmination + half_width < now {
+        culmination += seconds(2.0 * PI);
+    }
+    if culmination - half_width > now + Duration::seconds(LOOKAHEAD_SECONDS as i64) {
+        return None;
+    }
+    Some(ZenithPass {
+        start: culmination - half_width,
+        end: culmination + half_width,
+        culmination,
+        max_altitude: (90.0 - (latitude - dec).to_degrees().abs()) as f32,
+        max_az_rate: max_az_rate.min(f32::MAX as f64) as f32,
+        max_speed: max_speed as f32,
+        // Durchgang über Süd (180°): Ost → Süd → West, über Nord: Ost → Nord → West
+        direction: if dec < latitude { 1.0 } else { -1.0 },
+    })
+}
+
+/// dA/dH = cos δ · (sin φ cos δ − cos φ sin δ cos H) / cos² h, am Meridian cos δ / sin z
+fn azimuth_rate_factor(hour_angle: f64, dec: f64, latitude: f64) -> f64 {
+    let sin_alt = latitude.sin() * dec.sin() + latitude.cos() * dec.cos() * hour_angle.cos();
+    let numerator =
+        dec.cos() * (latitude.sin() * dec.cos() - latitude.cos() * dec.sin() * hour_angle.cos());
+    (numerator / (1.0 - sin_alt * sin_alt)).abs()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::astronomy::{apparent_to_mean, equatorial_to_horizontal};
+
+    const SITE: Position = Position {
+        latitude: 48.0,
+        longitude: 11.0,
+        altitude: 500.0,
+    };
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, 20, 22, 0, 0).unwrap()
+    }
+
+    /// Mittlerer Ort, der `minutes` Sternzeitminuten nach `now()` mit scheinbarer
+    /// Deklination `dec` kulminiert
+    fn target(minutes: f64, dec: f64) -> EqPostion {
+        let ra = local_sidereal_time(now(), SITE.longitude) + minutes / 4.0;
+        let (ra, dec) = apparent_to_mean(ra.to_radians(), dec.to_radians(), now());
+        EqPostion {
+            ra: (ra.to_degrees().rem_euclid(360.0) / 15.0) as f32,
+            dec: dec.to_degrees() as f32,
+        }
+    }
+
+    /// Azimut des nachgeführten Ziels in Grad, direkt aus dem Horizontsystem
+    fn azimuth(target: EqPostion, time: DateTime<Utc>) -> f64 {
+        let (ra, dec) = mean_to_apparent(
+            (target.ra as f64 * 15.0).to_radians(),
+            (target.dec as f64).to_radians(),
+            time,
+        );
+        let hour_angle = local_sidereal_time(time, SITE.longitude).to_radians() - ra;
+        equatorial_to_horizontal(hour_angle, dec, SITE.latitude.to_radians())
+            .1
+            .to_degrees()
+    }
+
+    /// Azimutgeschwindigkeit in Grad/s aus zwei Zeitpunkten im Abstand von 2 s
+    fn azimuth_rate(target: EqPostion, time: DateTime<Utc>) -> f64 {
+        let change =
+            azimuth(target, time + Duration::seconds(1)) - azimuth(target, time - Duration::seconds(1));
+        ((change + 180.0).rem_euclid(360.0) - 180.0) / 2.0
+    }
+
+    #[test]
+    fn pass_north_of_the_zenith() {
+        // 0.5° nördlich des Zenits, Kulmination in zehn Sternzeitminuten
+        let target = target(10.0, 48.5);
+        let pass = predict(target, TrackingRate::Sidereal, now(), &SITE, now(), 0.1).unwrap();
+
+        let expected = now() + Duration::milliseconds((600_000.0 / 1.002_737_909) as i64);
+        assert!((pass.culmination - expected).num_milliseconds().abs() < 500, "{pass:?}");
+        assert!((pass.max_altitude - 89.5).abs() < 1e-3);
+        // Über Nord: Ost → Nord → West, der Azimut nimmt ab
+        assert_eq!(pass.direction, -1.0);
+        assert!(azimuth_rate(target, pass.culmination) < 0.0);
+
+        // Am Meridian cos δ / sin z mal der siderischen Rate
+        let peak = 15.041 / 3600.0 * 48.5f64.to_radians().cos() / 0.5f64.to_radians().sin();
+        assert!((pass.max_az_rate as f64 - peak).abs() < 0.002 * peak, "{}", pass.max_az_rate);
+        assert!((azimuth_rate(target, pass.culmination).abs() - peak).abs() < 0.01 * peak);
+
+        // An den Grenzen erreicht die Achse gerade ihre Höchstgeschwindigkeit
+        for edge in [pass.start, pass.end] {
+            let rate = azimuth_rate(target, edge).abs();
+            assert!((rate - 0.1).abs() < 0.002, "{rate}");
+        }
+        assert!(pass.is_active(pass.culmination));
+        assert!(!pass.is_active(pass.end + Duration::seconds(1)));
+    }
+
+    #[test]
+    fn pass_south_of_the_zenith() {
+        let target = target(-1.0, 47.7);
+        let pass = predict(target, TrackingRate::Sidereal, now(), &SITE, now(), 0.1).unwrap();
+        // Die Kulmination ist schon vorbei, der Durchgang läuft aber noch
+        assert!(pass.culmination < now() && pass.is_active(now()));
+        assert_eq!(pass.direction, 1.0);
+        assert!(azimuth_rate(target, now()) > 0.0);
+    }
+
+    #[test]
+    fn no_pass() {
+        let pass = |target| predict(target, TrackingRate::Sidereal, now(), &SITE, now(), 0.1);
+        // Weit genug vom Zenit kommt die Achse mit
+        assert_eq!(pass(target(10.0, 20.0)), None);
+        // Nach der Stunde Vorschau
+        assert_eq!(pass(target(70.0, 48.5)), None);
+        // Schon vorbei, der nächste Durchgang folgt erst nach einem Sterntag
+        assert_eq!(pass(target(-20.0, 48.5)), None);
+    }
+}